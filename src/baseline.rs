@@ -0,0 +1,237 @@
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::BufWriter,
+};
+
+use camino::Utf8Path;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{Error, IOAction},
+    fingerprint::Fingerprint,
+    irritation::Irritation,
+    result::Result,
+    source_path::PrettyPath,
+};
+
+/// A record of known problems, which are not reported by subsequent checks.
+#[derive(Debug, Default)]
+pub struct Baseline {
+    remaining: BTreeMap<BaselineEntry, usize>,
+}
+
+impl Baseline {
+    pub fn load(path: &Utf8Path) -> Result<Self> {
+        let pretty_path = || PrettyPath::from(path.as_str());
+        let content = fs::read_to_string(path).map_err(|cause| Error::IO {
+            path: pretty_path(),
+            action: IOAction::Read,
+            cause,
+        })?;
+        let file: BaselineFile =
+            serde_json::from_str(&content).map_err(|cause| Error::InvalidBaseline {
+                path: pretty_path(),
+                reason: cause.to_string(),
+            })?;
+        if file.version != BaselineFile::VERSION {
+            return Err(Error::InvalidBaseline {
+                path: pretty_path(),
+                reason: format!(
+                    "unsupported version {}, expected {}",
+                    file.version,
+                    BaselineFile::VERSION
+                ),
+            });
+        }
+
+        let mut remaining = BTreeMap::new();
+        for entry in file.irritations {
+            *remaining.entry(entry).or_default() += 1;
+        }
+        Ok(Self { remaining })
+    }
+
+    pub fn write(path: &Utf8Path, irritations: &[Irritation]) -> Result<()> {
+        let io_error = |cause| Error::IO {
+            path: PrettyPath::from(path.as_str()),
+            action: IOAction::Write,
+            cause,
+        };
+
+        let mut entries: Vec<_> = irritations.iter().map(BaselineEntry::new).collect();
+        entries.sort();
+        let file = BaselineFile {
+            version: BaselineFile::VERSION,
+            irritations: entries,
+        };
+        let mut writer = BufWriter::new(File::create(path).map_err(io_error)?);
+        serde_json::to_writer_pretty(&mut writer, &file)?;
+        writer
+            .into_inner()
+            .map_err(|err| io_error(err.into_error()))?;
+        Ok(())
+    }
+
+    /// Remove irritations which are present in the baseline. Each baseline entry suppresses at
+    /// most one irritation, so new instances of known problems are still reported.
+    pub fn filter(&mut self, irritations: Vec<Irritation>) -> Vec<Irritation> {
+        irritations
+            .into_iter()
+            .filter(|irritation| {
+                let Some(count) = self.remaining.get_mut(&BaselineEntry::new(irritation)) else {
+                    return true;
+                };
+                if *count == 0 {
+                    return true;
+                }
+                *count -= 1;
+                false
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BaselineFile {
+    version: u32,
+    irritations: Vec<BaselineEntry>,
+}
+
+impl BaselineFile {
+    const VERSION: u32 = 1;
+}
+
+/// The identifying features of an irritation. Locations are deliberately omitted so that
+/// entries remain valid as surrounding code moves.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BaselineEntry {
+    lint_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    path: Option<String>,
+    message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    fingerprint: Option<Fingerprint>,
+}
+
+impl BaselineEntry {
+    fn new(irritation: &Irritation) -> Self {
+        let at = irritation.at().map(|(src, _)| src);
+        Self {
+            lint_id: irritation.lint_id().to_string(),
+            path: at.map(|src| src.path().to_string()),
+            message: irritation.message().to_owned(),
+            fingerprint: at.map(|src| src.fingerprint()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use camino::Utf8PathBuf;
+    use indoc::indoc;
+
+    use crate::vextest::VexTest;
+
+    use super::*;
+
+    fn irritations(source: &str) -> Vec<Irritation> {
+        VexTest::new("baseline")
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        vex.warn('project-level', 'no location')
+                        vex.search(
+                            'rust',
+                            '(integer_literal) @lit',
+                            on_match,
+                        )
+
+                    def on_match(event):
+                        vex.warn('literal', 'literal found', at=event.captures['lit'])
+                "#},
+            )
+            .with_source_file("src/main.rs", source)
+            .try_run()
+            .unwrap()
+            .irritations
+    }
+
+    #[test]
+    fn round_trip() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = Utf8PathBuf::try_from(tempdir.path().join("baseline.json")).unwrap();
+
+        let source = indoc! {r#"
+            fn main() {
+                let x = 1;
+                let y = 2;
+            }
+        "#};
+        Baseline::write(&path, &irritations(source)).unwrap();
+        let mut baseline = Baseline::load(&path).unwrap();
+        assert_eq!(baseline.remaining.values().sum::<usize>(), 3);
+        assert!(baseline.filter(irritations(source)).is_empty());
+    }
+
+    #[test]
+    fn survives_moved_code() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = Utf8PathBuf::try_from(tempdir.path().join("baseline.json")).unwrap();
+
+        Baseline::write(
+            &path,
+            &irritations(indoc! {r#"
+                fn main() {
+                    let x = 1;
+                }
+            "#}),
+        )
+        .unwrap();
+
+        let mut baseline = Baseline::load(&path).unwrap();
+        let new_irritations = baseline.filter(irritations(indoc! {r#"
+            // A new comment which shifts everything down.
+            fn main() {
+                let y = 2;
+
+                let x = 1;
+                let z = 1;
+            }
+        "#}));
+        let new_irritations: Vec<_> = new_irritations
+            .iter()
+            .map(|irr| {
+                let (src, _) = irr.at().unwrap();
+                (irr.lint_id().as_str(), src.location().start_row)
+            })
+            .collect();
+        assert_eq!(new_irritations, [("literal", 3), ("literal", 6)]);
+    }
+
+    #[test]
+    fn invalid() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = Utf8PathBuf::try_from(tempdir.path().join("baseline.json")).unwrap();
+
+        fs::write(&path, r#"{"version": 1, "irritations": [{"foo": "bar"}]}"#).unwrap();
+        let err = Baseline::load(&path).unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with(&format!("cannot load baseline {path}: ")),
+            "unexpected error: {err}"
+        );
+
+        fs::write(&path, r#"{"version": 1000, "irritations": []}"#).unwrap();
+        assert_eq!(
+            Baseline::load(&path).unwrap_err().to_string(),
+            format!("cannot load baseline {path}: unsupported version 1000, expected 1")
+        );
+    }
+}
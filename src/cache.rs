@@ -0,0 +1,539 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::{self, File},
+    io::{BufWriter, ErrorKind},
+    ops::Range,
+    sync::Mutex,
+};
+
+use camino::Utf8PathBuf;
+use dupe::Dupe;
+use log::{info, log_enabled};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    context::Context,
+    error::{Error, IOAction},
+    fingerprint::Fingerprint,
+    id::{GroupId, LintId},
+    irritation::{Irritation, IrritationSource},
+    language::Language,
+    result::Result,
+    scan::FileRunData,
+    scriptlets::{Location, VexingStore},
+    severity::Severity,
+    source_file::SourceFile,
+    source_path::PrettyPath,
+};
+
+/// An on-disk record of the irritations found in each file, used to avoid rescanning unchanged
+/// files.
+#[derive(Debug)]
+pub struct Cache {
+    dir: Utf8PathBuf,
+    key: Fingerprint,
+    entries: HashMap<PrettyPath, CacheEntry>,
+    updates: Mutex<Vec<(PrettyPath, CacheEntry)>>,
+}
+
+impl Cache {
+    pub const DIR_NAME: &'static str = ".vex-cache";
+    const FILE_NAME: &'static str = "results.json";
+
+    pub fn load(ctx: &Context, store: &VexingStore) -> Result<Self> {
+        let dir = ctx.project_root.join(Self::DIR_NAME);
+        let key = Self::key(ctx, store)?;
+        let path = dir.join(Self::FILE_NAME);
+        let entries = match fs::read_to_string(&path) {
+            Ok(content) => match serde_json::from_str::<CacheFile>(&content) {
+                Ok(cache_file) if cache_file.key == key => cache_file.entries,
+                Ok(_) => {
+                    if log_enabled!(log::Level::Info) {
+                        info!("discarding cache: vexes or config have changed");
+                    }
+                    HashMap::new()
+                }
+                Err(err) => {
+                    if log_enabled!(log::Level::Info) {
+                        info!("discarding cache: cannot parse {path}: {err}");
+                    }
+                    HashMap::new()
+                }
+            },
+            Err(err) if err.kind() == ErrorKind::NotFound => HashMap::new(),
+            Err(cause) => {
+                return Err(Error::IO {
+                    path: PrettyPath::new(&path),
+                    action: IOAction::Read,
+                    cause,
+                })
+            }
+        };
+        Ok(Self {
+            dir,
+            key,
+            entries,
+            updates: Mutex::new(vec![]),
+        })
+    }
+
+    /// Compute a key which changes whenever any of the inputs common to all file scans changes.
+    fn key(ctx: &Context, store: &VexingStore) -> Result<Fingerprint> {
+        let manifest = &ctx.manifest;
+        let languages: BTreeMap<_, _> = manifest.languages.iter().collect();
+        let parts = [
+            serde_json::to_string(&manifest.run)?,
+            serde_json::to_string(&manifest.script_args)?,
+            serde_json::to_string(&manifest.lints)?,
+            serde_json::to_string(&manifest.groups)?,
+            serde_json::to_string(&languages)?,
+        ];
+        Ok(Fingerprint::of_parts(
+            [
+                env!("CARGO_PKG_VERSION").as_bytes(),
+                &store.fingerprint().to_string().into_bytes(),
+            ]
+            .into_iter()
+            .chain(parts.iter().map(|part| part.as_bytes())),
+        ))
+    }
+
+    /// Return the cached results of scanning the given file if it is unchanged, otherwise scan it
+    /// and record the results.
+    pub fn get_or_scan(
+        &self,
+        file: &SourceFile,
+        language: &Language,
+        scan: impl FnOnce() -> Result<FileRunData>,
+    ) -> Result<FileRunData> {
+        let path = &file.path().pretty_path;
+        // Key by the content which is actually scanned, which may not be that on disk.
+        let content = match file.content() {
+            Some(content) => Fingerprint::of(content.as_bytes()),
+            None => {
+                let content =
+                    fs::read(file.path().abs_path.as_str()).map_err(|cause| Error::IO {
+                        path: path.dupe(),
+                        action: IOAction::Read,
+                        cause,
+                    })?;
+                Fingerprint::of(&content)
+            }
+        };
+
+        if let Some(entry) = self.entries.get(path) {
+            if entry.content == content && &entry.language == language {
+                if log_enabled!(log::Level::Info) {
+                    info!("skipping {path}: unchanged since last run");
+                }
+                let irritations = entry
+                    .irritations
+                    .iter()
+                    .map(CachedIrritation::to_irritation)
+                    .collect();
+                return Ok(FileRunData {
+                    irritations,
+                    num_bytes_scanned: 0,
+                });
+            }
+        }
+
+        let run = scan()?;
+        let entry = CacheEntry {
+            content,
+            language: language.dupe(),
+            irritations: run.irritations.iter().map(CachedIrritation::new).collect(),
+        };
+        self.updates
+            .lock()
+            .expect("internal error: cache updates lock poisoned")
+            .push((path.dupe(), entry));
+        Ok(run)
+    }
+
+    /// Write the cache to disk. Entries for files which no longer exist are dropped.
+    pub fn save(self, ctx: &Context) -> Result<()> {
+        let Self {
+            dir,
+            key,
+            mut entries,
+            updates,
+        } = self;
+        entries.extend(
+            updates
+                .into_inner()
+                .expect("internal error: cache updates lock poisoned"),
+        );
+        entries.retain(|path, _| ctx.project_root.join(path.as_str()).is_file());
+
+        let io_error = |path: &Utf8PathBuf, action| {
+            let path = PrettyPath::new(path);
+            move |cause| Error::IO {
+                path,
+                action,
+                cause,
+            }
+        };
+        fs::create_dir_all(&dir).map_err(io_error(&dir, IOAction::Write))?;
+
+        // The cache is local to each machine, so should never be committed.
+        let gitignore_path = dir.join(".gitignore");
+        fs::write(&gitignore_path, "*\n").map_err(io_error(&gitignore_path, IOAction::Write))?;
+
+        let path = dir.join(Self::FILE_NAME);
+        let mut writer =
+            BufWriter::new(File::create(&path).map_err(io_error(&path, IOAction::Write))?);
+        serde_json::to_writer(&mut writer, &CacheFile { key, entries })?;
+        writer
+            .into_inner()
+            .map_err(|err| io_error(&path, IOAction::Write)(err.into_error()))?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheFile {
+    key: Fingerprint,
+    entries: HashMap<PrettyPath, CacheEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheEntry {
+    content: Fingerprint,
+    language: Language,
+    irritations: Vec<CachedIrritation>,
+}
+
+/// An irritation as stored in the cache, which unlike the public output formats keeps the
+/// fingerprint of each source and the rendered form.
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedIrritation {
+    lint_id: LintId,
+    group_id: Option<GroupId>,
+    level: Severity,
+    message: String,
+    at: Option<(CachedSource, Option<String>)>,
+    show_also: Vec<(CachedSource, String)>,
+    info: Option<String>,
+    fix: Vec<(CachedSource, String)>,
+    rendered: String,
+}
+
+impl CachedIrritation {
+    fn new(irritation: &Irritation) -> Self {
+        let Irritation {
+            lint_id,
+            group_id,
+            level,
+            message,
+            at,
+            show_also,
+            info,
+            fix,
+            rendered,
+        } = irritation;
+        let labelled = |sources: &[(IrritationSource, String)]| -> Vec<_> {
+            sources
+                .iter()
+                .map(|(source, label)| (CachedSource::new(source), label.clone()))
+                .collect()
+        };
+        Self {
+            lint_id: lint_id.clone(),
+            group_id: group_id.clone(),
+            level: *level,
+            message: message.clone(),
+            at: at
+                .as_ref()
+                .map(|(source, label)| (CachedSource::new(source), label.clone())),
+            show_also: labelled(show_also),
+            info: info.clone(),
+            fix: labelled(fix),
+            rendered: rendered.clone(),
+        }
+    }
+
+    fn to_irritation(&self) -> Irritation {
+        let Self {
+            lint_id,
+            group_id,
+            level,
+            message,
+            at,
+            show_also,
+            info,
+            fix,
+            rendered,
+        } = self;
+        let labelled = |sources: &[(CachedSource, String)]| -> Vec<_> {
+            sources
+                .iter()
+                .map(|(source, label)| (source.to_source(), label.clone()))
+                .collect()
+        };
+        Irritation {
+            lint_id: lint_id.clone(),
+            group_id: group_id.clone(),
+            level: *level,
+            message: message.clone(),
+            at: at
+                .as_ref()
+                .map(|(source, label)| (source.to_source(), label.clone())),
+            show_also: labelled(show_also),
+            info: info.clone(),
+            fix: labelled(fix),
+            rendered: rendered.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedSource {
+    path: PrettyPath,
+    byte_range: Range<usize>,
+    location: Location,
+    fingerprint: Fingerprint,
+}
+
+impl CachedSource {
+    fn new(source: &IrritationSource) -> Self {
+        let IrritationSource {
+            path,
+            byte_range,
+            location,
+            fingerprint,
+        } = source;
+        Self {
+            path: path.dupe(),
+            byte_range: byte_range.clone(),
+            location: location.clone(),
+            fingerprint: *fingerprint,
+        }
+    }
+
+    fn to_source(&self) -> IrritationSource {
+        let Self {
+            path,
+            byte_range,
+            location,
+            fingerprint,
+        } = self;
+        IrritationSource {
+            path: path.dupe(),
+            byte_range: byte_range.clone(),
+            location: location.clone(),
+            fingerprint: *fingerprint,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cell::Cell,
+        process::{Command, Stdio},
+    };
+
+    use indoc::indoc;
+
+    use crate::{
+        cli::MaxConcurrentFileLimit,
+        context::Manifest,
+        git,
+        scriptlets::{source, InitOptions, PreinitOptions, PreinitingStore, ScriptArgsValueMap},
+        source_file,
+        verbosity::Verbosity,
+    };
+
+    use super::*;
+
+    struct Project {
+        _tempdir: tempfile::TempDir,
+        root: Utf8PathBuf,
+    }
+
+    impl Project {
+        fn new() -> Self {
+            let tempdir = tempfile::tempdir().unwrap();
+            let root = Utf8PathBuf::try_from(tempdir.path().to_owned()).unwrap();
+            fs::write(
+                root.join(Manifest::FILE_NAME),
+                "[vex]\nversion = '1'\n[args]\nsome-id.key = 'value'\n",
+            )
+            .unwrap();
+            fs::create_dir(root.join("vexes")).unwrap();
+            Self::write_vex(&root, "literal");
+            fs::write(root.join("main.rs"), "fn main() { let x = 1; }\n").unwrap();
+            Self {
+                _tempdir: tempdir,
+                root,
+            }
+        }
+
+        fn write_vex(root: &Utf8PathBuf, lint_id: &str) {
+            fs::write(
+                root.join("vexes/test.star"),
+                indoc! {r#"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        vex.search('rust', '(integer_literal) @lit', on_match)
+
+                    def on_match(event):
+                        vex.warn('LINT_ID', 'found literal', at=event.captures['lit'])
+                "#}
+                .replace("LINT_ID", lint_id),
+            )
+            .unwrap();
+        }
+
+        /// Load the cache and check each file, returning the number of files scanned.
+        fn check(&self) -> usize {
+            self.check_with(|file| file)
+        }
+
+        /// Load the cache and check each file as staged in git, returning the number of files
+        /// scanned.
+        fn check_staged(&self) -> usize {
+            self.check_with(|file| {
+                let content = git::staged_content(&self.root, &file.path().pretty_path).unwrap();
+                file.with_content(content)
+            })
+        }
+
+        fn git(&self, args: &[&str]) {
+            let status = Command::new("git")
+                .args(args)
+                .current_dir(&self.root)
+                .stdout(Stdio::null())
+                .status()
+                .unwrap();
+            assert!(status.success(), "git {args:?} failed");
+        }
+
+        fn check_with(&self, prepare: impl Fn(SourceFile) -> SourceFile) -> usize {
+            let ctx = Context::acquire_in(&self.root).unwrap();
+            let script_args_heap = starlark::values::FrozenHeap::new();
+            let script_args = ScriptArgsValueMap::with_args(&ctx.script_args, &script_args_heap);
+            let store = PreinitingStore::new(&source::sources_in_dir(&ctx.vex_dir()).unwrap())
+                .unwrap()
+                .preinit(
+                    &ctx,
+                    PreinitOptions {
+                        script_args: &script_args,
+                        verbosity: Verbosity::default(),
+                    },
+                )
+                .unwrap()
+                .init(
+                    &ctx,
+                    InitOptions {
+                        script_args: &script_args,
+                        verbosity: Verbosity::default(),
+                    },
+                )
+                .unwrap();
+
+            let cache = Cache::load(&ctx, &store).unwrap();
+            let num_scanned = Cell::new(0);
+            let files = source_file::sources_in_dir(&ctx, MaxConcurrentFileLimit::new(1))
+                .unwrap()
+                .into_iter()
+                .filter(|file| file.language().is_some())
+                .map(prepare);
+            for file in files {
+                let run = cache
+                    .get_or_scan(&file, file.language().unwrap(), || {
+                        num_scanned.set(num_scanned.get() + 1);
+                        Ok(FileRunData {
+                            irritations: vec![],
+                            num_bytes_scanned: 1,
+                        })
+                    })
+                    .unwrap();
+                assert!(run.irritations.is_empty());
+            }
+            cache.save(&ctx).unwrap();
+            num_scanned.get()
+        }
+    }
+
+    #[test]
+    fn invalidation() {
+        let project = Project::new();
+        assert_eq!(project.check(), 1, "cold cache");
+        assert_eq!(project.check(), 0, "warm cache");
+        assert_eq!(
+            fs::read_to_string(project.root.join(".vex-cache/.gitignore")).unwrap(),
+            "*\n"
+        );
+
+        fs::write(project.root.join("main.rs"), "fn main() { let x = 2; }\n").unwrap();
+        assert_eq!(project.check(), 1, "changed source");
+        assert_eq!(project.check(), 0);
+
+        Project::write_vex(&project.root, "other-lint");
+        assert_eq!(project.check(), 1, "changed vex");
+        assert_eq!(project.check(), 0);
+
+        fs::write(
+            project.root.join(Manifest::FILE_NAME),
+            "[vex]\nversion = '1'\n[args]\nsome-id.key = 'other-value'\n",
+        )
+        .unwrap();
+        assert_eq!(project.check(), 1, "changed args");
+        assert_eq!(project.check(), 0);
+
+        fs::write(project.root.join(".vex-cache/results.json"), "garbage").unwrap();
+        assert_eq!(project.check(), 1, "corrupt cache");
+    }
+
+    #[test]
+    fn staged_content() {
+        let project = Project::new();
+        project.git(&["init", "--quiet"]);
+        project.git(&["add", "main.rs"]);
+        fs::write(project.root.join("main.rs"), "fn main() { let x = 2; }\n").unwrap();
+
+        assert_eq!(project.check(), 1, "cold cache");
+        assert_eq!(project.check(), 0);
+        assert_eq!(project.check_staged(), 1, "staged differs from worktree");
+        assert_eq!(project.check_staged(), 0);
+        assert_eq!(project.check(), 1, "worktree differs from staged");
+    }
+
+    #[test]
+    fn cached_irritations() {
+        let irritations = crate::vextest::VexTest::new("cached")
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        vex.search('rust', '(integer_literal) @lit', on_match)
+
+                    def on_match(event):
+                        lit = event.captures['lit']
+                        vex.warn('literal', 'found literal', at=(lit, 'here'), fix=[(lit, '0')])
+                "#},
+            )
+            .with_source_file("src/main.rs", "fn main() { let x = 1; }\n")
+            .try_run()
+            .unwrap()
+            .irritations;
+        assert_eq!(irritations.len(), 1);
+
+        let cached: Vec<_> = irritations.iter().map(CachedIrritation::new).collect();
+        let serialised = serde_json::to_string(&cached).unwrap();
+        let restored: Vec<_> = serde_json::from_str::<Vec<CachedIrritation>>(&serialised)
+            .unwrap()
+            .iter()
+            .map(CachedIrritation::to_irritation)
+            .collect();
+        assert_eq!(restored, irritations);
+        assert_eq!(restored[0].to_string(), irritations[0].to_string());
+    }
+}
@@ -6,10 +6,10 @@ use clap::{
         styling::{AnsiColor, Effects},
         StringValueParser, Styles, TypedValueParser,
     },
-    ArgAction, Parser, Subcommand,
+    ArgAction, ArgGroup, Parser, Subcommand,
 };
 
-use crate::{language::Language, Result};
+use crate::{language::Language, output_format::OutputFormat, Result};
 
 #[derive(Debug, Parser)]
 #[command(
@@ -78,8 +78,14 @@ pub enum Command {
     /// Create new vex project with this directory as the root
     Init(InitCmd),
 
+    /// List each vex with the events it observes, the queries it searches for and its lints
+    List,
+
+    /// Run a language server over stdio, reporting problems in open files
+    Lsp,
+
     /// Test available lints
-    Test,
+    Test(TestCmd),
 }
 
 #[cfg(test)]
@@ -104,10 +110,26 @@ impl Command {
             _ => None,
         }
     }
+
+    pub fn into_test_cmd(self) -> Option<TestCmd> {
+        match self {
+            Self::Test(t) => Some(t),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Default, PartialEq, Eq, Parser)]
+#[command(group(ArgGroup::new("diff_base").args(["changed_since", "staged"])))]
 pub struct CheckCmd {
+    /// Files or directories to check, defaults to the whole project
+    #[arg(value_name = "paths")]
+    pub paths: Vec<Utf8PathBuf>,
+
+    /// Set output format
+    #[arg(long, value_enum, default_value_t = OutputFormat::default(), value_name = "format")]
+    pub format: OutputFormat,
+
     /// Set concurrency limit
     #[arg(long, default_value_t = MaxConcurrentFileLimit::default(), value_parser = MaxConcurrentFileLimit::parser(), value_name = "max")]
     pub max_concurrent_files: MaxConcurrentFileLimit,
@@ -115,6 +137,50 @@ pub struct CheckCmd {
     /// Exit early after this many problems (pass `unlimited` for no max)
     #[arg(long, default_value_t = MaxProblems::default(), value_parser = MaxProblems::parser(), value_name = "max")]
     pub max_problems: MaxProblems,
+
+    /// Only check files changed since the given git revision, including untracked files
+    #[arg(long, value_name = "rev")]
+    pub changed_since: Option<String>,
+
+    /// Only check files with changes staged in git, as they are in the index
+    #[arg(long)]
+    pub staged: bool,
+
+    /// Only report problems on changed lines
+    #[arg(long, requires = "diff_base")]
+    pub changed_lines_only: bool,
+
+    /// Apply available fixes
+    #[arg(long, conflicts_with_all = ["fix_dry_run", "staged"])]
+    pub fix: bool,
+
+    /// Print available fixes as a diff to stderr without applying them
+    #[arg(long, conflicts_with = "staged")]
+    pub fix_dry_run: bool,
+
+    /// Ignore problems recorded in the given baseline file
+    #[arg(long, value_name = "file", conflicts_with = "write_baseline")]
+    pub baseline: Option<Utf8PathBuf>,
+
+    /// Record all current problems to the given baseline file
+    #[arg(long, value_name = "file")]
+    pub write_baseline: Option<Utf8PathBuf>,
+
+    /// Rescan all files, ignoring and not updating the cache
+    #[arg(long)]
+    pub no_cache: bool,
+
+    /// Keep running, rechecking files as they change
+    #[arg(long, conflicts_with_all = ["diff_base", "fix", "fix_dry_run", "write_baseline", "profile"])]
+    pub watch: bool,
+
+    /// Print the time spent in each vex callback, query and file, ignoring the cache
+    #[arg(long)]
+    pub profile: bool,
+
+    /// Also write the profile as a Chrome trace to the given file
+    #[arg(long, value_name = "file", requires = "profile")]
+    pub profile_trace: Option<Utf8PathBuf>,
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
@@ -292,6 +358,25 @@ pub struct InitCmd {
     pub force: bool,
 }
 
+#[derive(Debug, Default, PartialEq, Eq, Parser)]
+pub struct TestCmd {
+    /// Only run tests of vexes whose path or tested lint IDs contain this pattern
+    #[arg(value_name = "pattern")]
+    pub pattern: Option<String>,
+
+    /// Also write results as JUnit XML to the given file
+    #[arg(long, value_name = "file")]
+    pub junit: Option<Utf8PathBuf>,
+
+    /// Record the problems found by each vex's tests as its snapshot
+    #[arg(long)]
+    pub update_snapshots: bool,
+
+    /// Also write which lines of each vex ran as an lcov tracefile to the given file
+    #[arg(long, value_name = "file")]
+    pub coverage: Option<Utf8PathBuf>,
+}
+
 fn parse_overrides() {
     if env::args().count() > 2 {
         return;
@@ -403,6 +488,33 @@ mod tests {
 
             let check_cmd = cmd.into_check_cmd().unwrap();
             assert_eq!(check_cmd.max_problems, MaxProblems::Limited(100));
+            assert_eq!(check_cmd.format, OutputFormat::Pretty);
+            assert_eq!(check_cmd.paths, [] as [Utf8PathBuf; 0]);
+        }
+
+        #[test]
+        fn paths() {
+            let args =
+                Args::try_parse_from(["vex", "check", "src/main.rs", "./tests/", "/abs"]).unwrap();
+            let check_cmd = args.into_command().into_check_cmd().unwrap();
+            assert_eq!(check_cmd.paths, ["src/main.rs", "./tests/", "/abs"]);
+        }
+
+        #[test]
+        fn format() {
+            for (raw_format, format) in [
+                ("pretty", OutputFormat::Pretty),
+                ("json", OutputFormat::Json),
+                ("jsonl", OutputFormat::Jsonl),
+                ("sarif", OutputFormat::Sarif),
+            ] {
+                let args = Args::try_parse_from(["vex", "check", "--format", raw_format]).unwrap();
+                let check_cmd = args.into_command().into_check_cmd().unwrap();
+                assert_eq!(check_cmd.format, format);
+                assert_eq!(check_cmd.format.to_string(), raw_format);
+            }
+
+            Args::try_parse_from(["vex", "check", "--format", "xml"]).unwrap_err();
         }
 
         #[test]
@@ -425,6 +537,195 @@ mod tests {
             let check_cmd = cmd.into_check_cmd().unwrap();
             assert_eq!(check_cmd.max_problems, MaxProblems::Unlimited);
         }
+
+        #[test]
+        fn changed() {
+            let check_cmd = Args::try_parse_from(["vex", "check"])
+                .unwrap()
+                .into_command()
+                .into_check_cmd()
+                .unwrap();
+            assert_eq!(check_cmd.changed_since, None);
+            assert!(!check_cmd.staged);
+            assert!(!check_cmd.changed_lines_only);
+
+            let check_cmd = Args::try_parse_from([
+                "vex",
+                "check",
+                "--changed-since",
+                "main",
+                "--changed-lines-only",
+            ])
+            .unwrap()
+            .into_command()
+            .into_check_cmd()
+            .unwrap();
+            assert_eq!(check_cmd.changed_since.as_deref(), Some("main"));
+            assert!(!check_cmd.staged);
+            assert!(check_cmd.changed_lines_only);
+
+            let check_cmd = Args::try_parse_from(["vex", "check", "--staged"])
+                .unwrap()
+                .into_command()
+                .into_check_cmd()
+                .unwrap();
+            assert_eq!(check_cmd.changed_since, None);
+            assert!(check_cmd.staged);
+
+            Args::try_parse_from(["vex", "check", "--staged", "--changed-since", "main"])
+                .unwrap_err();
+            Args::try_parse_from(["vex", "check", "--changed-lines-only"]).unwrap_err();
+        }
+
+        #[test]
+        fn fix() {
+            let check_cmd = Args::try_parse_from(["vex", "check"])
+                .unwrap()
+                .into_command()
+                .into_check_cmd()
+                .unwrap();
+            assert!(!check_cmd.fix);
+            assert!(!check_cmd.fix_dry_run);
+
+            let check_cmd = Args::try_parse_from(["vex", "check", "--fix"])
+                .unwrap()
+                .into_command()
+                .into_check_cmd()
+                .unwrap();
+            assert!(check_cmd.fix);
+            assert!(!check_cmd.fix_dry_run);
+
+            let check_cmd = Args::try_parse_from(["vex", "check", "--fix-dry-run"])
+                .unwrap()
+                .into_command()
+                .into_check_cmd()
+                .unwrap();
+            assert!(!check_cmd.fix);
+            assert!(check_cmd.fix_dry_run);
+
+            Args::try_parse_from(["vex", "check", "--fix", "--fix-dry-run"]).unwrap_err();
+            Args::try_parse_from(["vex", "check", "--fix", "--staged"]).unwrap_err();
+            Args::try_parse_from(["vex", "check", "--fix-dry-run", "--staged"]).unwrap_err();
+        }
+
+        #[test]
+        fn baseline() {
+            let check_cmd = Args::try_parse_from(["vex", "check"])
+                .unwrap()
+                .into_command()
+                .into_check_cmd()
+                .unwrap();
+            assert_eq!(check_cmd.baseline, None);
+            assert_eq!(check_cmd.write_baseline, None);
+
+            let check_cmd =
+                Args::try_parse_from(["vex", "check", "--baseline", "vex-baseline.json"])
+                    .unwrap()
+                    .into_command()
+                    .into_check_cmd()
+                    .unwrap();
+            assert_eq!(
+                check_cmd.baseline.as_deref(),
+                Some("vex-baseline.json".into())
+            );
+
+            let check_cmd =
+                Args::try_parse_from(["vex", "check", "--write-baseline", "vex-baseline.json"])
+                    .unwrap()
+                    .into_command()
+                    .into_check_cmd()
+                    .unwrap();
+            assert_eq!(
+                check_cmd.write_baseline.as_deref(),
+                Some("vex-baseline.json".into())
+            );
+
+            Args::try_parse_from([
+                "vex",
+                "check",
+                "--baseline",
+                "old.json",
+                "--write-baseline",
+                "new.json",
+            ])
+            .unwrap_err();
+        }
+
+        #[test]
+        fn no_cache() {
+            let check_cmd = Args::try_parse_from(["vex", "check"])
+                .unwrap()
+                .into_command()
+                .into_check_cmd()
+                .unwrap();
+            assert!(!check_cmd.no_cache);
+
+            let check_cmd = Args::try_parse_from(["vex", "check", "--no-cache"])
+                .unwrap()
+                .into_command()
+                .into_check_cmd()
+                .unwrap();
+            assert!(check_cmd.no_cache);
+        }
+
+        #[test]
+        fn watch() {
+            let check_cmd = Args::try_parse_from(["vex", "check"])
+                .unwrap()
+                .into_command()
+                .into_check_cmd()
+                .unwrap();
+            assert!(!check_cmd.watch);
+
+            let check_cmd = Args::try_parse_from(["vex", "check", "--watch", "src/"])
+                .unwrap()
+                .into_command()
+                .into_check_cmd()
+                .unwrap();
+            assert!(check_cmd.watch);
+
+            for incompatible in [
+                ["--staged"].as_slice(),
+                &["--changed-since", "main"],
+                &["--fix"],
+                &["--fix-dry-run"],
+                &["--write-baseline", "vex-baseline.json"],
+                &["--profile"],
+            ] {
+                let args = ["vex", "check", "--watch"].iter().chain(incompatible);
+                Args::try_parse_from(args).unwrap_err();
+            }
+        }
+
+        #[test]
+        fn profile() {
+            let check_cmd = Args::try_parse_from(["vex", "check"])
+                .unwrap()
+                .into_command()
+                .into_check_cmd()
+                .unwrap();
+            assert!(!check_cmd.profile);
+            assert_eq!(check_cmd.profile_trace, None);
+
+            let check_cmd = Args::try_parse_from([
+                "vex",
+                "check",
+                "--profile",
+                "--profile-trace",
+                "trace.json",
+            ])
+            .unwrap()
+            .into_command()
+            .into_check_cmd()
+            .unwrap();
+            assert!(check_cmd.profile);
+            assert_eq!(
+                check_cmd.profile_trace.as_deref(),
+                Some("trace.json".into())
+            );
+
+            Args::try_parse_from(["vex", "check", "--profile-trace", "trace.json"]).unwrap_err();
+        }
     }
 
     mod dump {
@@ -493,12 +794,62 @@ mod tests {
     }
 
     #[test]
-    fn test() {
+    fn list() {
         assert_eq!(
-            Args::try_parse_from(["vex", "test"])
+            Args::try_parse_from(["vex", "list"])
                 .unwrap()
                 .into_command(),
-            Command::Test,
+            Command::List,
+        )
+    }
+
+    #[test]
+    fn lsp() {
+        assert_eq!(
+            Args::try_parse_from(["vex", "lsp"]).unwrap().into_command(),
+            Command::Lsp,
         )
     }
+
+    #[test]
+    fn test() {
+        assert_eq!(
+            Args::try_parse_from(["vex", "test"])
+                .unwrap()
+                .into_command()
+                .into_test_cmd()
+                .unwrap(),
+            TestCmd::default(),
+        );
+        assert_eq!(
+            Args::try_parse_from(["vex", "test", "no-unwrap", "--junit", "results.xml"])
+                .unwrap()
+                .into_command()
+                .into_test_cmd()
+                .unwrap(),
+            TestCmd {
+                pattern: Some("no-unwrap".into()),
+                junit: Some("results.xml".into()),
+                ..TestCmd::default()
+            },
+        );
+        assert_eq!(
+            Args::try_parse_from([
+                "vex",
+                "test",
+                "--update-snapshots",
+                "--coverage",
+                "lcov.info"
+            ])
+            .unwrap()
+            .into_command()
+            .into_test_cmd()
+            .unwrap(),
+            TestCmd {
+                update_snapshots: true,
+                coverage: Some("lcov.info".into()),
+                ..TestCmd::default()
+            },
+        );
+    }
 }
@@ -15,7 +15,8 @@ use std::collections::{BTreeMap, HashMap};
 use std::fmt::Write as _;
 use std::io::{BufWriter, ErrorKind, Read, Write as _};
 use std::ops::Deref;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 use std::{
     env,
     fs::{self, File},
@@ -29,10 +30,15 @@ use crate::error::{
 };
 use crate::id::Id;
 use crate::language::Language;
+use crate::lsp::LanguageServer;
+use crate::profile::{Profiler, Subject};
 use crate::query::Query;
 use crate::result::Result;
 use crate::scriptlets::query_cache::QueryCacheForLanguage;
-use crate::source_path::PrettyPath;
+use crate::severity::Severity;
+use crate::source_file::ParsedSourceFile;
+use crate::source_path::{PrettyPath, SourcePath};
+use crate::test::Coverage;
 use crate::trigger::RawFilePattern;
 use crate::warn;
 
@@ -41,6 +47,12 @@ pub struct Context {
     pub project_root: PrettyPath,
     pub manifest: Manifest,
     languages: ArenaMap<Language, Option<LanguageData>>,
+    language_servers: ArenaMap<Language, Option<LanguageServer>>,
+    coverage: Option<Arc<Coverage>>,
+    profiler: Option<Arc<Profiler>>,
+    project_files_read: AtomicBool,
+    project_sources: Mutex<Option<Arc<[SourcePath]>>>,
+    parsed_project_files: Mutex<HashMap<(PrettyPath, Language), Arc<ParsedSourceFile>>>,
 }
 
 pub const EXAMPLE_VEX_FILE: &str = "example.star";
@@ -68,6 +80,12 @@ impl Context {
             project_root,
             manifest,
             languages,
+            language_servers: ArenaMap::new(),
+            coverage: None,
+            profiler: None,
+            project_files_read: AtomicBool::default(),
+            project_sources: Mutex::default(),
+            parsed_project_files: Mutex::default(),
         })
     }
 
@@ -76,10 +94,31 @@ impl Context {
             project_root: PrettyPath::new(project_root),
             manifest,
             languages: ArenaMap::new(),
+            language_servers: ArenaMap::new(),
+            coverage: None,
+            profiler: None,
+            project_files_read: AtomicBool::default(),
+            project_sources: Mutex::default(),
+            parsed_project_files: Mutex::default(),
+        }
+    }
+
+    /// Create a context for a project at another root which shares this project's configuration.
+    /// The parser directories of external languages are still found relative to this project.
+    pub fn with_project_root(&self, project_root: &Utf8Path) -> Self {
+        let mut manifest = self.manifest.clone();
+        manifest.languages.0.values_mut().for_each(|options| {
+            if let Some(parser_dir) = &mut options.parser_dir {
+                *parser_dir = self.project_root.join(&parser_dir);
+            }
+        });
+        Self {
+            coverage: self.coverage.clone(),
+            profiler: self.profiler.clone(),
+            ..Self::new_with_manifest(project_root, manifest)
         }
     }
 
-    #[cfg(test)]
     pub fn acquire_in(project_root: &Utf8Path) -> Result<Self> {
         let (project_root, raw_data) = Manifest::acquire_content_in(project_root)?;
         let project_root = PrettyPath::new(&project_root);
@@ -90,6 +129,12 @@ impl Context {
             project_root,
             manifest,
             languages,
+            language_servers: ArenaMap::new(),
+            coverage: None,
+            profiler: None,
+            project_files_read: AtomicBool::default(),
+            project_sources: Mutex::default(),
+            parsed_project_files: Mutex::default(),
         })
     }
 
@@ -195,6 +240,125 @@ impl Context {
             .map(Option::as_ref)
     }
 
+    /// Return the language server configured for the given language, starting it on first use.
+    pub fn language_server(&self, language: &Language) -> Result<Option<&LanguageServer>> {
+        self.language_servers
+            .get_or_init(language, || {
+                self.manifest
+                    .languages
+                    .get(language)
+                    .and_then(|opts| opts.language_server.as_ref())
+                    .map(|command| {
+                        LanguageServer::start(&self.project_root, language.dupe(), command)
+                    })
+                    .transpose()
+            })
+            .map(Option::as_ref)
+    }
+
+    #[cfg(test)]
+    pub fn set_language_server(&self, language: &Language, language_server: LanguageServer) {
+        self.language_servers
+            .get_or_init(language, || Ok(Some(language_server)))
+            .expect("internal error: cannot set language server");
+    }
+
+    /// Start recording which lines of each scriptlet are executed.
+    pub fn enable_coverage(&mut self) {
+        self.coverage = Some(Arc::default());
+    }
+
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.coverage.as_deref()
+    }
+
+    /// Start timing scriptlet callbacks, queries and files, keeping a trace of each if `trace` is
+    /// set.
+    pub fn enable_profiling(&mut self, trace: bool) {
+        self.profiler = Some(Arc::new(Profiler::new(trace)));
+    }
+
+    pub fn profiler(&self) -> Option<&Profiler> {
+        self.profiler.as_deref()
+    }
+
+    /// Run `f`, timing it against the given subject if profiling is enabled.
+    pub fn profiled<T>(
+        &self,
+        subject: Subject,
+        name: impl FnOnce() -> String,
+        f: impl FnOnce() -> T,
+    ) -> T {
+        match self.profiler() {
+            Some(profiler) => profiler.time(subject, name, f),
+            None => f(),
+        }
+    }
+
+    /// Record that a vex has read files from the project, so results for one file may depend on
+    /// the content of others.
+    pub fn note_project_files_read(&self) {
+        self.project_files_read.store(true, Ordering::Relaxed);
+    }
+
+    pub fn project_files_read(&self) -> bool {
+        self.project_files_read.load(Ordering::Relaxed)
+    }
+
+    /// Get the files in the project which would be checked, finding them with `find` only on
+    /// first use since the last call to `forget_project_files`.
+    pub fn project_sources(
+        &self,
+        find: impl FnOnce() -> Result<Vec<SourcePath>>,
+    ) -> Result<Arc<[SourcePath]>> {
+        let mut project_sources = self
+            .project_sources
+            .lock()
+            .expect("internal error: project sources lock poisoned");
+        if let Some(project_sources) = &*project_sources {
+            return Ok(project_sources.dupe());
+        }
+        let found: Arc<[SourcePath]> = find()?.into();
+        *project_sources = Some(found.dupe());
+        Ok(found)
+    }
+
+    /// Get the given project file parsed as the given language, parsing it with `parse` only on
+    /// first use since the last call to `forget_project_files`.
+    pub fn parsed_project_file(
+        &self,
+        path: &PrettyPath,
+        language: &Language,
+        parse: impl FnOnce() -> Result<ParsedSourceFile>,
+    ) -> Result<Arc<ParsedSourceFile>> {
+        let lock = || {
+            self.parsed_project_files
+                .lock()
+                .expect("internal error: parsed project files lock poisoned")
+        };
+        let key = (path.dupe(), language.dupe());
+        if let Some(parsed_file) = lock().get(&key) {
+            return Ok(parsed_file.dupe());
+        }
+
+        // Other files may be parsed concurrently, so the lock is not held while parsing.
+        let parsed_file = Arc::new(parse()?);
+        Ok(lock().entry(key).or_insert(parsed_file).dupe())
+    }
+
+    /// Forget the files found by `project_sources` and parsed by `parsed_project_file`, as the
+    /// project may have changed since.
+    pub fn forget_project_files(&self) {
+        *self
+            .project_sources
+            .lock()
+            .expect("internal error: project sources lock poisoned") = None;
+        self.parsed_project_files
+            .lock()
+            .expect("internal error: parsed project files lock poisoned")
+            .clear();
+    }
+
     pub fn vex_dir(&self) -> Utf8PathBuf {
         self.project_root.join(self.manifest.run.vexes_dir.as_str())
     }
@@ -339,6 +503,23 @@ pub struct RunConfig {
     #[serde(default)]
     #[serde(rename = "directory")]
     pub vexes_dir: VexesDir,
+
+    /// The maximum number of statements a single callback may execute. Work done within a
+    /// single expression, such as a comprehension, is not counted, so this is best combined with
+    /// `max_callback_seconds` or `max_callback_heap_mib`.
+    #[serde(default)]
+    #[serde(rename = "max-callback-steps")]
+    pub max_callback_steps: Option<u64>,
+
+    /// The maximum wall time a single callback may take, in seconds.
+    #[serde(default)]
+    #[serde(rename = "max-callback-seconds")]
+    pub max_callback_seconds: Option<f64>,
+
+    /// The maximum heap size a single callback may use, in mebibytes.
+    #[serde(default)]
+    #[serde(rename = "max-callback-heap-mib")]
+    pub max_callback_heap_mib: Option<u64>,
 }
 
 #[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
@@ -517,8 +698,11 @@ pub enum ScriptArgValue {
 #[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
 #[serde(deny_unknown_fields)]
 pub struct LintsConfig {
-    #[serde(rename = "active")]
+    #[serde(rename = "active", default)]
     pub active_lints_config: BTreeMap<String, bool>,
+
+    #[serde(rename = "level", default)]
+    pub lint_levels_config: BTreeMap<String, Severity>,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
@@ -590,7 +774,6 @@ pub enum LanguageServerCommand {
 }
 
 impl LanguageServerCommand {
-    #[allow(unused)]
     pub fn parts(&self) -> impl Iterator<Item = &str> {
         let parts_slice = match self {
             Self::JustName(cmd) => slice::from_ref(cmd),
@@ -818,6 +1001,7 @@ mod tests {
         cli::{MaxConcurrentFileLimit, MaxProblems},
         scan::{self, ProjectRunData},
         scriptlets::{source, InitOptions, PreinitOptions, PreinitingStore, ScriptArgsValueMap},
+        source_file,
         verbosity::Verbosity,
         vextest::VexTest,
         warning_filter::WarningFilter,
@@ -956,11 +1140,12 @@ mod tests {
         let ProjectRunData { irritations, .. } = scan::scan_project(
             &ctx,
             &store,
+            source_file::sources_in_dir(&ctx, MaxConcurrentFileLimit::new(1))?,
             WarningFilter::all(),
             MaxProblems::Unlimited,
-            MaxConcurrentFileLimit::new(1),
             &ScriptArgsValueMap::new(),
             Verbosity::default(),
+            None,
         )?;
         assert_yaml_snapshot!(irritations
             .into_iter()
@@ -1025,6 +1210,10 @@ mod tests {
             lint-id-1 = false
             lint-id-2 = true
 
+            [lints.level]
+            lint-id-1 = "error"
+            group-id-1 = "note"
+
             [groups.active]
             group-id-1 = false
             group-id-2 = true
@@ -1071,6 +1260,13 @@ mod tests {
             parsed_manifest.lints.active_lints_config,
             BTreeMap::from_iter([("lint-id-1".into(), false), ("lint-id-2".into(), true)])
         );
+        assert_eq!(
+            parsed_manifest.lints.lint_levels_config,
+            BTreeMap::from_iter([
+                ("group-id-1".into(), Severity::Note),
+                ("lint-id-1".into(), Severity::Error)
+            ])
+        );
         assert_eq!(
             parsed_manifest.groups.active_groups_config,
             BTreeMap::from_iter([("group-id-1".into(), false), ("group-id-2".into(), true)])
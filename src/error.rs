@@ -1,4 +1,4 @@
-use std::{fmt, io, num, path, str::Utf8Error};
+use std::{fmt, io, num, path, str::Utf8Error, time::Duration};
 
 use derive_more::Display;
 use joinery::JoinableIterator;
@@ -8,6 +8,7 @@ use crate::{
     language::Language,
     query::Query,
     scriptlets::{action::Action, event::EventKind, LoadPath, Location},
+    severity::Severity,
     source_path::PrettyPath,
 };
 
@@ -29,6 +30,17 @@ pub enum Error {
         other_language: Language,
     },
 
+    #[error(
+        "{observer} exceeded limit of {limit} while handling {event} event{}",
+        file.as_ref().map(|file| format!(" on {file}")).unwrap_or_default(),
+    )]
+    CallbackLimitExceeded {
+        observer: String,
+        event: &'static str,
+        file: Option<PrettyPath>,
+        limit: CallbackLimit,
+    },
+
     #[error(transparent)]
     Clap(#[from] clap::Error),
 
@@ -47,12 +59,24 @@ pub enum Error {
     #[error(transparent)]
     FromPathBuf(#[from] camino::FromPathBufError),
 
+    #[error("git {subcommand} failed: {stderr}")]
+    Git {
+        subcommand: &'static str,
+        stderr: String,
+    },
+
+    #[error("cannot access {0}: path is hidden or ignored")]
+    IgnoredPath(PrettyPath),
+
     #[error("cannot load {language} parser: {cause}")]
     InaccessibleParserFiles {
         language: Language,
         cause: anyhow::Error,
     },
 
+    #[error("cannot load baseline {path}: {reason}")]
+    InvalidBaseline { path: PrettyPath, reason: String },
+
     #[error("invalid ID '{raw_id}': {reason}")]
     InvalidID {
         raw_id: String,
@@ -75,6 +99,9 @@ pub enum Error {
         reason: InvalidLoadReason,
     },
 
+    #[error("invalid language server message: {0}")]
+    InvalidLspMessage(String),
+
     #[error("invalid ignore query: {0}")]
     InvalidIgnoreQuery(InvalidIgnoreQueryReason),
 
@@ -91,12 +118,21 @@ pub enum Error {
         cause: io::Error,
     },
 
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+
     #[error(transparent)]
     Language(#[from] tree_sitter::LanguageError),
 
+    #[error("{language} language server failed: {reason}")]
+    LanguageServer { language: Language, reason: String },
+
     #[error("cannot find manifest, try running `vex init` in the project’s root")]
     ManifestNotFound,
 
+    #[error("no language server configured for {0}")]
+    NoLanguageServer(Language),
+
     #[error("cannot discern language of {0}")]
     NoParserForFile(PrettyPath),
 
@@ -109,9 +145,12 @@ pub enum Error {
     #[error("cannot find vexes directory at {0}")]
     NoVexesDir(PrettyPath),
 
-    #[error("{0} is not a check path")]
+    #[error("cannot check {0}: path is outside the project")]
     NotACheckPath(PrettyPath),
 
+    #[error("cannot access {0}: path is outside the project")]
+    OutsideProject(PrettyPath),
+
     #[error(transparent)]
     ParseInt(#[from] num::ParseIntError),
 
@@ -128,21 +167,32 @@ pub enum Error {
     #[error("ignoring '*' makes other ignore ids redundant")]
     RedundantIgnore,
 
+    #[error(transparent)]
+    Regex(#[from] regex::Error),
+
     #[error(transparent)]
     SetLogger(#[from] log::SetLoggerError),
 
+    #[error(
+        "irritations differ from snapshot {path}, rerun with --update-snapshots to accept:\n{diff}"
+    )]
+    SnapshotMismatch { path: PrettyPath, diff: String },
+
     #[error(transparent)]
     Starlark(anyhow::Error),
 
     #[error(transparent)]
     StripPrefix(#[from] path::StripPrefixError),
 
-    #[error("test run invalid")]
-    TestRunInvalid,
-
     #[error(transparent)]
     Toml(#[from] toml_edit::de::Error),
 
+    #[error(
+        "irritations differ from expectations:{}",
+        .0.iter().map(|mismatch| format!("\n  {mismatch}")).join_with(""),
+    )]
+    UnexpectedIrritations(Vec<String>),
+
     #[error(
         "unknown event '{name}'{}, expected one of: {}",
         suggestion.map(|suggestion| format!(" (did you mean '{suggestion}'?)")).unwrap_or_default(),
@@ -164,6 +214,16 @@ pub enum Error {
         suggestion: Option<&'static str>,
     },
 
+    #[error(
+        "unknown level '{name}'{}, expected one of: {}",
+        suggestion.map(|suggestion| format!(" (did you mean '{suggestion}'?)")).unwrap_or_default(),
+        Severity::iter().map(|severity| severity.name()).join_with(", "),
+    )]
+    UnknownSeverity {
+        name: String,
+        suggestion: Option<&'static str>,
+    },
+
     #[error("{path}:{location}: cannot parse {language}")]
     UnparseableAsLanguage {
         path: PrettyPath,
@@ -173,6 +233,12 @@ pub enum Error {
 
     #[error(transparent)]
     Utf8(#[from] Utf8Error),
+
+    #[error("cannot watch {path}: {cause}")]
+    Watch {
+        path: PrettyPath,
+        cause: notify::Error,
+    },
 }
 
 impl From<anyhow::Error> for Error {
@@ -190,6 +256,18 @@ impl From<starlark::Error> for Error {
     }
 }
 
+#[derive(Clone, Copy, Debug, Display, PartialEq)]
+pub enum CallbackLimit {
+    #[display(fmt = "{_0} steps")]
+    Steps(u64),
+
+    #[display(fmt = "{:?}", _0)]
+    Time(Duration),
+
+    #[display(fmt = "{_0} MiB of heap")]
+    HeapMib(u64),
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum ExternalLanguageError {
     #[error("manifest info missing `parser-dir` field")]
@@ -228,6 +306,9 @@ pub enum IOAction {
     #[display(fmt = "read")]
     Read,
 
+    #[display(fmt = "run")]
+    Run,
+
     #[display(fmt = "write")]
     Write,
 }
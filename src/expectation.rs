@@ -0,0 +1,319 @@
+use std::fmt::{self, Display};
+
+use allocative::Allocative;
+use starlark::values::{dict::DictRef, Value};
+
+use crate::{error::Error, id::LintId, irritation::Irritation, result::Result};
+
+/// An irritation which a test file is expected to produce.
+#[derive(Clone, Debug, PartialEq, Eq, Allocative)]
+pub struct Expectation {
+    pub lint_id: LintId,
+
+    /// The one-indexed row on which the irritation starts.
+    pub line: Option<usize>,
+
+    /// The column at which the irritation starts, as in `Location`.
+    pub column: Option<usize>,
+
+    /// The source text the irritation points at.
+    pub text: Option<String>,
+
+    /// A substring of the irritation's message.
+    pub message: Option<String>,
+}
+
+impl Expectation {
+    pub const MARKER: &'static str = "vex:expect";
+
+    fn new(lint_id: LintId) -> Self {
+        Self {
+            lint_id,
+            line: None,
+            column: None,
+            text: None,
+            message: None,
+        }
+    }
+
+    /// Parse an element of the `expect` list passed to `vex.scan`, which is either a lint id or
+    /// a dict with an `id` and optionally a `line`, `column`, `text` or `message`.
+    pub fn from_value(value: Value<'_>) -> Result<Self> {
+        if let Some(lint_id) = value.unpack_str() {
+            return Ok(Self::new(LintId::try_from(lint_id.to_owned())?));
+        }
+        let Some(dict) = DictRef::from_value(value) else {
+            return Err(Error::InvalidTest(format!(
+                "expectation must be a str or dict: got {}",
+                value.get_type()
+            )));
+        };
+
+        let field_error = |field: &str, expected: &str| {
+            Error::InvalidTest(format!("expectation field '{field}' must be {expected}"))
+        };
+        let mut lint_id = None;
+        let (mut line, mut column, mut text, mut message) = (None, None, None, None);
+        for (key, value) in dict.iter() {
+            let Some(key) = key.unpack_str() else {
+                return Err(Error::InvalidTest(format!(
+                    "expectation keys must be str: got {}",
+                    key.get_type()
+                )));
+            };
+            let unpack_position = || match value.unpack_i32() {
+                Some(position) if position >= 0 => Ok(position as usize),
+                _ => Err(field_error(key, "a non-negative int")),
+            };
+            let unpack_str = || {
+                value
+                    .unpack_str()
+                    .map(str::to_owned)
+                    .ok_or_else(|| field_error(key, "a str"))
+            };
+            match key {
+                "id" => lint_id = Some(LintId::try_from(unpack_str()?)?),
+                "line" => line = Some(unpack_position()?),
+                "column" => column = Some(unpack_position()?),
+                "text" => text = Some(unpack_str()?),
+                "message" => message = Some(unpack_str()?),
+                _ => {
+                    return Err(Error::InvalidTest(format!(
+                        "unknown expectation field '{key}'"
+                    )))
+                }
+            }
+        }
+        let Some(lint_id) = lint_id else {
+            return Err(Error::InvalidTest("expectation has no 'id'".into()));
+        };
+        Ok(Self {
+            lint_id,
+            line,
+            column,
+            text,
+            message,
+        })
+    }
+
+    /// Find the expectations declared by `vex:expect <lint-id>` annotations in some content.
+    /// Annotations which follow code on the same line refer to that line, others refer to the
+    /// next line which is not itself an annotation.
+    pub fn from_annotations(content: &str) -> Result<Vec<Self>> {
+        let mut expectations = vec![];
+        let mut pending = vec![];
+        for (index, line) in content.lines().enumerate() {
+            let line_number = index + 1;
+            let Some(marker_index) = line.find(Self::MARKER) else {
+                expectations.extend(pending.drain(..).map(|lint_id| Self {
+                    line: Some(line_number),
+                    ..Self::new(lint_id)
+                }));
+                continue;
+            };
+
+            let Some(lint_id) = line[marker_index + Self::MARKER.len()..]
+                .split_whitespace()
+                .next()
+            else {
+                return Err(Error::InvalidTest(format!(
+                    "line {line_number}: {} annotation has no lint id",
+                    Self::MARKER
+                )));
+            };
+            let lint_id = LintId::try_from(lint_id.to_owned())?;
+
+            let code = line[..marker_index]
+                .trim_end()
+                .trim_end_matches(|c: char| !c.is_alphanumeric() && !c.is_whitespace())
+                .trim();
+            if code.is_empty() {
+                pending.push(lint_id);
+            } else {
+                expectations.push(Self {
+                    line: Some(line_number),
+                    ..Self::new(lint_id)
+                });
+            }
+        }
+        if !pending.is_empty() {
+            return Err(Error::InvalidTest(format!(
+                "{} annotation at end of file refers to no code",
+                Self::MARKER
+            )));
+        }
+        Ok(expectations)
+    }
+
+    fn matches(&self, irritation: &Irritation, content: &str) -> bool {
+        if *irritation.lint_id() != self.lint_id {
+            return false;
+        }
+        if let Some(message) = &self.message {
+            if !irritation.message().contains(message.as_str()) {
+                return false;
+            }
+        }
+        if self.line.is_none() && self.column.is_none() && self.text.is_none() {
+            return true;
+        }
+
+        let Some((src, _)) = irritation.at() else {
+            return false;
+        };
+        let location = src.location();
+        self.line.map_or(true, |line| line == location.start_row)
+            && self
+                .column
+                .map_or(true, |column| column == location.start_column)
+            && self.text.as_ref().map_or(true, |text| {
+                content.get(src.byte_range().clone()) == Some(text.as_str())
+            })
+    }
+
+    /// The number of properties this expectation constrains, besides its lint id.
+    fn specificity(&self) -> usize {
+        [
+            self.line.is_some(),
+            self.column.is_some(),
+            self.text.is_some(),
+            self.message.is_some(),
+        ]
+        .into_iter()
+        .filter(|constrained| *constrained)
+        .count()
+    }
+}
+
+impl Display for Expectation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.lint_id)?;
+        match (self.line, self.column) {
+            (Some(line), Some(column)) => write!(f, " at {line}:{column}")?,
+            (Some(line), None) => write!(f, " at line {line}")?,
+            (None, Some(column)) => write!(f, " at column {column}")?,
+            (None, None) => {}
+        }
+        if let Some(text) = &self.text {
+            write!(f, " on '{text}'")?;
+        }
+        if let Some(message) = &self.message {
+            write!(f, " with message containing '{message}'")?;
+        }
+        Ok(())
+    }
+}
+
+/// A difference between the irritations expected of a test file and those actually produced.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Mismatch<'a> {
+    Missing(&'a Expectation),
+    Unexpected(&'a Irritation),
+}
+
+impl Display for Mismatch<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Missing(expectation) => write!(f, "missing {expectation}"),
+            Self::Unexpected(irritation) => {
+                write!(f, "unexpected {}", irritation.lint_id())?;
+                if let Some((src, _)) = irritation.at() {
+                    write!(f, " at {}", src.location())?;
+                }
+                write!(f, ": {}", irritation.message())
+            }
+        }
+    }
+}
+
+/// Pair up expectations with the irritations which satisfy them, returning whatever is left over.
+/// More specific expectations are paired first so that vaguer ones cannot steal their matches.
+pub fn diff<'a>(
+    expectations: &'a [Expectation],
+    irritations: &[&'a Irritation],
+    content: &str,
+) -> Vec<Mismatch<'a>> {
+    let mut ordered_expectations: Vec<_> = expectations.iter().collect();
+    ordered_expectations.sort_by_key(|expectation| std::cmp::Reverse(expectation.specificity()));
+
+    let mut matched = vec![false; irritations.len()];
+    let mut mismatches = vec![];
+    for expectation in ordered_expectations {
+        let found = irritations.iter().enumerate().find(|(index, irritation)| {
+            !matched[*index] && expectation.matches(irritation, content)
+        });
+        match found {
+            Some((index, _)) => matched[index] = true,
+            None => mismatches.push(Mismatch::Missing(expectation)),
+        }
+    }
+    mismatches.extend(
+        irritations
+            .iter()
+            .zip(matched)
+            .filter(|(_, matched)| !matched)
+            .map(|(irritation, _)| Mismatch::Unexpected(irritation)),
+    );
+    mismatches
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use super::*;
+
+    fn expect(lint_id: &str, line: usize) -> Expectation {
+        Expectation {
+            line: Some(line),
+            ..Expectation::new(LintId::try_from(lint_id.to_owned()).unwrap())
+        }
+    }
+
+    #[test]
+    fn annotations() {
+        let content = indoc! {"
+            fn main() {
+                let x = 1; // vex:expect some-lint
+                // vex:expect other-lint
+                # vex:expect third-lint
+                let y = 2;
+            }
+        "};
+        assert_eq!(
+            Expectation::from_annotations(content).unwrap(),
+            [
+                expect("some-lint", 2),
+                expect("other-lint", 5),
+                expect("third-lint", 5),
+            ]
+        );
+
+        assert_eq!(
+            Expectation::from_annotations("x // vex:expect")
+                .unwrap_err()
+                .to_string(),
+            "test invalid: line 1: vex:expect annotation has no lint id"
+        );
+        assert_eq!(
+            Expectation::from_annotations("x\n// vex:expect some-lint\n")
+                .unwrap_err()
+                .to_string(),
+            "test invalid: vex:expect annotation at end of file refers to no code"
+        );
+    }
+
+    #[test]
+    fn display() {
+        let expectation = Expectation {
+            column: Some(4),
+            text: Some("1 + 1".into()),
+            message: Some("oh no".into()),
+            ..expect("some-lint", 2)
+        };
+        assert_eq!(
+            expectation.to_string(),
+            "some-lint at 2:4 on '1 + 1' with message containing 'oh no'"
+        );
+    }
+}
@@ -0,0 +1,117 @@
+use std::{fmt::Display, hash::Hasher, str::FromStr};
+
+use allocative::Allocative;
+use dupe::Dupe;
+use fnv::FnvHasher;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+/// A stable hash of some content, suitable for persisting between runs.
+#[derive(Copy, Clone, Debug, Dupe, PartialEq, Eq, PartialOrd, Ord, Hash, Allocative)]
+pub struct Fingerprint(u64);
+
+impl Fingerprint {
+    pub fn of(content: &[u8]) -> Self {
+        let mut hasher = FnvHasher::default();
+        hasher.write(content);
+        Self(hasher.finish())
+    }
+
+    /// Fingerprint a sequence of byte strings. Boundaries between parts are significant.
+    pub fn of_parts<'a>(parts: impl IntoIterator<Item = &'a [u8]>) -> Self {
+        let mut hasher = FnvHasher::default();
+        for part in parts {
+            hasher.write_usize(part.len());
+            hasher.write(part);
+        }
+        Self(hasher.finish())
+    }
+
+    /// Fingerprint a snippet of code, ignoring differences in whitespace.
+    pub fn of_code(code: &str) -> Self {
+        let mut hasher = FnvHasher::default();
+        for word in code.split_whitespace() {
+            hasher.write(word.as_bytes());
+            hasher.write_u8(b' ');
+        }
+        Self(hasher.finish())
+    }
+}
+
+impl Display for Fingerprint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+impl FromStr for Fingerprint {
+    type Err = std::num::ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        u64::from_str_radix(s, 16).map(Self)
+    }
+}
+
+impl Serialize for Fingerprint {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Fingerprint {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let raw = String::deserialize(deserializer)?;
+        raw.parse().map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stable() {
+        // Fingerprints are persisted, so must not change between versions.
+        assert_eq!(Fingerprint::of(b"").to_string(), "cbf29ce484222325");
+        assert_eq!(Fingerprint::of(b"vex").to_string(), "691216194edd9734");
+    }
+
+    #[test]
+    fn code_whitespace_insensitive() {
+        assert_eq!(
+            Fingerprint::of_code("let x =\n    1 + 2;"),
+            Fingerprint::of_code("  let x = 1 +   2;  ")
+        );
+        assert_ne!(
+            Fingerprint::of_code("let x = 1 + 2;"),
+            Fingerprint::of_code("let x = 1 + 3;")
+        );
+    }
+
+    #[test]
+    fn parts() {
+        assert_ne!(
+            Fingerprint::of_parts([b"ab".as_slice(), b"c"]),
+            Fingerprint::of_parts([b"a".as_slice(), b"bc"])
+        );
+        assert_eq!(
+            Fingerprint::of_parts([b"ab".as_slice(), b"c"]),
+            Fingerprint::of_parts([b"ab".as_slice(), b"c"])
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let fingerprint = Fingerprint::of(b"some content");
+        let serialised = serde_json::to_string(&fingerprint).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Fingerprint>(&serialised).unwrap(),
+            fingerprint
+        );
+    }
+}
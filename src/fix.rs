@@ -0,0 +1,440 @@
+use std::{collections::BTreeMap, fmt::Write, fs, ops::Range};
+
+use dupe::Dupe;
+
+use crate::{
+    context::Context,
+    error::{Error, IOAction},
+    irritation::Irritation,
+    plural::Plural,
+    result::Result,
+    source_path::PrettyPath,
+};
+
+#[derive(Debug, Default)]
+pub struct FixRunData {
+    pub unfixed: Vec<Irritation>,
+    pub num_fixed: usize,
+}
+
+pub fn fix(ctx: &Context, irritations: Vec<Irritation>, dry_run: bool) -> Result<FixRunData> {
+    let plan = FixPlan::new(&irritations);
+
+    for (path, edits) in &plan.edits {
+        let abs_path = ctx.project_root.join(path.as_str());
+        let content = fs::read_to_string(&abs_path).map_err(|cause| Error::IO {
+            path: (*path).dupe(),
+            action: IOAction::Read,
+            cause,
+        })?;
+        if dry_run {
+            // Machine-readable output formats own stdout.
+            eprint!("{}", render_diff(path, &content, edits));
+        } else {
+            fs::write(&abs_path, apply_edits(&content, edits)).map_err(|cause| Error::IO {
+                path: (*path).dupe(),
+                action: IOAction::Write,
+                cause,
+            })?;
+        }
+    }
+
+    for &conflict in &plan.conflicts {
+        let irritation = &irritations[conflict];
+        let location = irritation
+            .at()
+            .map(|(src, _)| format!(" at {src}"))
+            .unwrap_or_default();
+        crate::warn!(
+            "cannot apply fix for ‘{}’{location}: conflicts with another fix",
+            irritation.lint_id()
+        );
+    }
+    if !plan.conflicts.is_empty() {
+        log::info!(
+            "skipped {}, re-run to apply",
+            Plural::new(plan.conflicts.len(), "conflicting fix", "conflicting fixes")
+        );
+    }
+
+    let FixPlan { fixed, .. } = plan;
+    let num_fixed = fixed.len();
+    if dry_run {
+        // Nothing was written, so every problem remains.
+        return Ok(FixRunData {
+            unfixed: irritations,
+            num_fixed,
+        });
+    }
+    let unfixed = irritations
+        .into_iter()
+        .enumerate()
+        .filter(|(idx, _)| fixed.binary_search(idx).is_err())
+        .map(|(_, irr)| irr)
+        .collect();
+    Ok(FixRunData { unfixed, num_fixed })
+}
+
+#[derive(Debug)]
+struct FixPlan<'a> {
+    edits: BTreeMap<&'a PrettyPath, Vec<Edit<'a>>>,
+    fixed: Vec<usize>,
+    conflicts: Vec<usize>,
+}
+
+impl<'a> FixPlan<'a> {
+    fn new(irritations: &'a [Irritation]) -> Self {
+        let mut edits: BTreeMap<_, Vec<Edit<'_>>> = BTreeMap::new();
+        let mut fixed = vec![];
+        let mut conflicts = vec![];
+        for (idx, irritation) in irritations.iter().enumerate() {
+            if irritation.fix().is_empty() {
+                continue;
+            }
+
+            let mut new_edits: Vec<_> = irritation
+                .fix()
+                .iter()
+                .map(|(src, replacement)| {
+                    (
+                        src.path(),
+                        Edit {
+                            range: src.byte_range().clone(),
+                            replacement,
+                        },
+                    )
+                })
+                .filter(|(path, edit)| {
+                    // Edits identical to those of an earlier fix have already been applied.
+                    !edits
+                        .get(path)
+                        .is_some_and(|accepted| accepted.contains(edit))
+                })
+                .collect();
+            new_edits.sort_by(|(l_path, l_edit), (r_path, r_edit)| {
+                (l_path, l_edit.range.start).cmp(&(r_path, r_edit.range.start))
+            });
+            new_edits.dedup();
+
+            let self_overlapping = new_edits
+                .windows(2)
+                .any(|w| w[0].0 == w[1].0 && w[0].1.overlaps(&w[1].1));
+            let overlaps_accepted = new_edits.iter().any(|(path, edit)| {
+                edits
+                    .get(path)
+                    .is_some_and(|accepted| accepted.iter().any(|a| a.overlaps(edit)))
+            });
+            if self_overlapping || overlaps_accepted {
+                conflicts.push(idx);
+                continue;
+            }
+
+            for (path, edit) in new_edits {
+                edits.entry(path).or_default().push(edit);
+            }
+            fixed.push(idx);
+        }
+        edits
+            .values_mut()
+            .for_each(|edits| edits.sort_by_key(|edit| edit.range.start));
+
+        Self {
+            edits,
+            fixed,
+            conflicts,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Edit<'a> {
+    range: Range<usize>,
+    replacement: &'a str,
+}
+
+impl Edit<'_> {
+    fn overlaps(&self, other: &Self) -> bool {
+        // Insertions at the same point are ambiguously ordered, so are considered to overlap.
+        (self.range.start < other.range.end && other.range.start < self.range.end)
+            || (self.range.start == other.range.start
+                && (self.range.is_empty() || other.range.is_empty()))
+    }
+}
+
+/// Apply the given non-overlapping edits, which must be sorted by start position.
+fn apply_edits(content: &str, edits: &[Edit<'_>]) -> String {
+    let mut ret = String::with_capacity(content.len());
+    let mut cursor = 0;
+    for edit in edits {
+        ret.push_str(&content[cursor..edit.range.start]);
+        ret.push_str(edit.replacement);
+        cursor = edit.range.end;
+    }
+    ret.push_str(&content[cursor..]);
+    ret
+}
+
+/// Render the given edits as a unified diff.
+fn render_diff(path: &PrettyPath, content: &str, edits: &[Edit<'_>]) -> String {
+    let line_start = |index: usize| content[..index].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = |index: usize| {
+        content[index..]
+            .find('\n')
+            .map_or(content.len(), |i| i + index)
+    };
+
+    // Group edits which touch the same lines into hunks.
+    let mut hunks: Vec<(Range<usize>, &[Edit<'_>])> = vec![];
+    let mut hunk_start_idx = 0;
+    for (idx, edit) in edits.iter().enumerate() {
+        let lines = line_start(edit.range.start)..line_end(edit.range.end);
+        match hunks.last_mut() {
+            Some((hunk_lines, hunk_edits)) if lines.start <= hunk_lines.end => {
+                hunk_lines.end = lines.end;
+                *hunk_edits = &edits[hunk_start_idx..=idx];
+            }
+            _ => {
+                hunk_start_idx = idx;
+                hunks.push((lines, &edits[idx..=idx]));
+            }
+        }
+    }
+
+    let mut ret = String::new();
+    writeln!(ret, "--- a/{path}").unwrap();
+    writeln!(ret, "+++ b/{path}").unwrap();
+    let mut line_delta = 0isize;
+    for (lines, hunk_edits) in hunks {
+        let old = &content[lines.clone()];
+        let new = {
+            let rebased_edits: Vec<_> = hunk_edits
+                .iter()
+                .map(|edit| Edit {
+                    range: edit.range.start - lines.start..edit.range.end - lines.start,
+                    replacement: edit.replacement,
+                })
+                .collect();
+            apply_edits(old, &rebased_edits)
+        };
+        let old_start = 1 + content[..lines.start].matches('\n').count();
+        let old_len = old.split('\n').count();
+        let new_len = new.split('\n').count();
+        let new_start = old_start.saturating_add_signed(line_delta);
+        line_delta += new_len as isize - old_len as isize;
+
+        writeln!(ret, "@@ -{old_start},{old_len} +{new_start},{new_len} @@").unwrap();
+        old.split('\n')
+            .for_each(|line| writeln!(ret, "-{line}").unwrap());
+        new.split('\n')
+            .for_each(|line| writeln!(ret, "+{line}").unwrap());
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use camino::Utf8PathBuf;
+    use indoc::{formatdoc, indoc};
+
+    use crate::{context::Manifest, vextest::VexTest};
+
+    use super::*;
+
+    const SOURCE: &str = indoc! {r#"
+        fn main() {
+            let x = 1000000 + 2000000;
+            let y = 3000000;
+        }
+    "#};
+
+    fn irritations(on_match_body: &str) -> Vec<Irritation> {
+        VexTest::new("fix")
+            .with_scriptlet(
+                "vexes/test.star",
+                formatdoc! {r#"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        vex.search(
+                            'rust',
+                            '(integer_literal) @lit',
+                            on_match,
+                        )
+
+                    def on_match(event):
+                        lit = event.captures['lit']
+                        {on_match_body}
+                "#},
+            )
+            .with_source_file("src/main.rs", SOURCE)
+            .try_run()
+            .unwrap()
+            .irritations
+    }
+
+    fn fix_source(irritations: &[Irritation]) -> (FixPlan<'_>, String) {
+        let plan = FixPlan::new(irritations);
+        let fixed = apply_edits(
+            SOURCE,
+            plan.edits
+                .get(&PrettyPath::from("src/main.rs"))
+                .map(Vec::as_slice)
+                .unwrap_or_default(),
+        );
+        (plan, fixed)
+    }
+
+    #[test]
+    fn apply() {
+        let irritations =
+            irritations("vex.warn('zero', 'nonzero literal', at=lit, fix=[(lit, '0')])");
+        assert_eq!(irritations.len(), 3);
+        assert!(irritations.iter().all(|irr| irr.fix().len() == 1));
+
+        let (plan, fixed) = fix_source(&irritations);
+        assert_eq!(plan.fixed, [0, 1, 2]);
+        assert!(plan.conflicts.is_empty());
+        assert_eq!(
+            fixed,
+            indoc! {r#"
+                fn main() {
+                    let x = 0 + 0;
+                    let y = 0;
+                }
+            "#}
+        );
+    }
+
+    #[test]
+    fn conflicts() {
+        let irritations = irritations(indoc! {r#"
+            vex.warn('zero', 'nonzero literal', at=lit, fix=[(lit, '0')])
+                vex.warn('hex', 'decimal literal', at=lit, fix=[(lit, '0x0')])
+        "#});
+        assert_eq!(irritations.len(), 6);
+
+        let (plan, fixed) = fix_source(&irritations);
+        assert_eq!(plan.fixed, [0, 2, 4]);
+        assert_eq!(plan.conflicts, [1, 3, 5]);
+        assert_eq!(
+            fixed,
+            indoc! {r#"
+                fn main() {
+                    let x = 0x0 + 0x0;
+                    let y = 0x0;
+                }
+            "#}
+        );
+    }
+
+    #[test]
+    fn duplicate_fixes() {
+        let irritations = irritations(indoc! {r#"
+            vex.warn('zero', 'nonzero literal', at=lit, fix=[(lit, '0')])
+                vex.warn('also-zero', 'nonzero literal', at=lit, fix=[(lit, '0')])
+        "#});
+        assert_eq!(irritations.len(), 6);
+
+        let (plan, fixed) = fix_source(&irritations);
+        assert_eq!(plan.fixed.len(), 6);
+        assert!(plan.conflicts.is_empty());
+        assert!(fixed.contains("let x = 0 + 0;"));
+    }
+
+    #[test]
+    fn self_overlapping_fix() {
+        let irritations = irritations(
+            "vex.warn('zero', 'nonzero literal', at=lit, fix=[(lit, '0'), (lit, '1')])",
+        );
+        let (plan, fixed) = fix_source(&irritations);
+        assert!(plan.fixed.is_empty());
+        assert_eq!(plan.conflicts, [0, 1, 2]);
+        assert_eq!(fixed, SOURCE);
+    }
+
+    #[test]
+    fn fix_requires_node() {
+        VexTest::new("fix-without-at")
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        vex.search(
+                            'rust',
+                            '(integer_literal) @lit',
+                            on_match,
+                        )
+
+                    def on_match(event):
+                        lit = event.captures['lit']
+                        vex.warn('test', 'test message', fix=[(lit, '0')])
+                "#},
+            )
+            .with_source_file("src/main.rs", SOURCE)
+            .returns_error("cannot apply `fix` without an `at` argument containing a Node");
+    }
+
+    #[test]
+    fn dry_run() {
+        let irritations =
+            irritations("vex.warn('zero', 'nonzero literal', at=lit, fix=[(lit, '0')])");
+        let tempdir = tempfile::tempdir().unwrap();
+        let root = Utf8PathBuf::try_from(tempdir.path().to_owned()).unwrap();
+        fs::create_dir(root.join("src")).unwrap();
+        fs::write(root.join("src/main.rs"), SOURCE).unwrap();
+        let ctx = Context::new_with_manifest(&root, Manifest::default());
+
+        let FixRunData { unfixed, num_fixed } = fix(&ctx, irritations.clone(), true).unwrap();
+        assert_eq!(unfixed, irritations);
+        assert_eq!(num_fixed, 3);
+        assert_eq!(
+            fs::read_to_string(root.join("src/main.rs")).unwrap(),
+            SOURCE
+        );
+
+        let FixRunData { unfixed, num_fixed } = fix(&ctx, irritations, false).unwrap();
+        assert_eq!(unfixed, []);
+        assert_eq!(num_fixed, 3);
+        assert_ne!(
+            fs::read_to_string(root.join("src/main.rs")).unwrap(),
+            SOURCE
+        );
+    }
+
+    #[test]
+    fn diff() {
+        let edit_of = |find: &str, replacement| {
+            let start = SOURCE.find(find).unwrap();
+            Edit {
+                range: start..start + find.len(),
+                replacement,
+            }
+        };
+        let edits = [
+            edit_of("{", "{\n"),
+            edit_of("1000000", "1_000_000"),
+            edit_of("3000000", "3_000_000"),
+        ];
+        assert_eq!(
+            render_diff(&PrettyPath::from("src/main.rs"), SOURCE, &edits),
+            indoc! {r#"
+                --- a/src/main.rs
+                +++ b/src/main.rs
+                @@ -1,1 +1,2 @@
+                -fn main() {
+                +fn main() {
+                +
+                @@ -2,1 +3,1 @@
+                -    let x = 1000000 + 2000000;
+                +    let x = 1_000_000 + 2000000;
+                @@ -3,1 +4,1 @@
+                -    let y = 3000000;
+                +    let y = 3_000_000;
+            "#}
+        );
+    }
+}
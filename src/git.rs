@@ -0,0 +1,392 @@
+use std::{collections::BTreeMap, ops::Range, process::Command};
+
+use camino::Utf8Path;
+
+use crate::{
+    error::{Error, IOAction},
+    irritation::Irritation,
+    result::Result,
+    source_path::PrettyPath,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DiffBase<'a> {
+    Revision(&'a str),
+    Index,
+}
+
+/// The lines changed in each file relative to some base.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Diff {
+    changed_lines: BTreeMap<PrettyPath, Vec<Range<usize>>>,
+}
+
+impl Diff {
+    /// Find the lines changed relative to the given base. When comparing against a revision,
+    /// untracked files which are not ignored by git count as changed throughout.
+    pub fn new(project_root: &Utf8Path, base: DiffBase<'_>) -> Result<Self> {
+        let diff_args = |extra_args: &[&'static str]| {
+            let mut args = vec![
+                "--relative",
+                "--no-color",
+                "--no-ext-diff",
+                "--no-renames",
+                "--diff-filter=d",
+            ];
+            args.extend(extra_args);
+            match base {
+                DiffBase::Revision(rev) => args.extend(["--end-of-options", rev]),
+                DiffBase::Index => args.push("--cached"),
+            }
+            args.push("--");
+            args
+        };
+
+        // Prefixes are set explicitly as they may be changed by the user's git config.
+        let raw_diff = run_git(
+            project_root,
+            "diff",
+            &diff_args(&["--src-prefix=a/", "--dst-prefix=b/", "--unified=0"]),
+        )?;
+        let mut diff = Self::parse(&raw_diff);
+
+        // Paths in the diff itself may be quoted, so changed files are listed separately.
+        let changed_files = run_git(project_root, "diff", &diff_args(&["--name-only", "-z"]))?;
+        for path in changed_files.split('\0').filter(|path| !path.is_empty()) {
+            diff.changed_lines
+                .entry(PrettyPath::from(path))
+                .or_default();
+        }
+
+        if let DiffBase::Revision(_) = base {
+            let untracked_files = run_git(
+                project_root,
+                "ls-files",
+                &["--others", "--exclude-standard", "-z"],
+            )?;
+            for path in untracked_files.split('\0').filter(|path| !path.is_empty()) {
+                let all_lines = 1..usize::MAX;
+                diff.changed_lines
+                    .insert(PrettyPath::from(path), Vec::from([all_lines]));
+            }
+        }
+
+        Ok(diff)
+    }
+
+    fn parse(raw_diff: &str) -> Self {
+        let mut changed_lines: BTreeMap<_, Vec<_>> = BTreeMap::new();
+        let mut curr_path = None;
+        for line in raw_diff.lines() {
+            if line.starts_with("diff ") {
+                curr_path = None;
+            } else if let Some(path) = line.strip_prefix("+++ b/") {
+                let path = PrettyPath::from(path);
+                changed_lines.entry(path.clone()).or_default();
+                curr_path = Some(path);
+            } else if let Some(hunk_header) = line.strip_prefix("@@ ") {
+                let Some(path) = &curr_path else {
+                    continue;
+                };
+                let Some(lines) = Self::parse_hunk_header(hunk_header) else {
+                    continue;
+                };
+                if !lines.is_empty() {
+                    changed_lines
+                        .get_mut(path)
+                        .expect("internal error: path not registered")
+                        .push(lines);
+                }
+            }
+        }
+        Self { changed_lines }
+    }
+
+    /// Parse the post-image line range from a hunk header of the form
+    /// `-<start>[,<len>] +<start>[,<len>] @@ ...`.
+    fn parse_hunk_header(hunk_header: &str) -> Option<Range<usize>> {
+        let post_image = hunk_header.split(' ').nth(1)?.strip_prefix('+')?;
+        let (start, len) = match post_image.split_once(',') {
+            Some((start, len)) => (start.parse().ok()?, len.parse().ok()?),
+            None => (post_image.parse().ok()?, 1),
+        };
+        Some(start..start + len)
+    }
+
+    pub fn changed_files(&self) -> impl Iterator<Item = &PrettyPath> {
+        self.changed_lines.keys()
+    }
+
+    /// Returns whether the given irritation was raised at a changed line. Irritations not
+    /// associated with a file and those raised against whole files are considered touched if
+    /// their file has changed at all.
+    pub fn touches(&self, irritation: &Irritation) -> bool {
+        let Some((src, _)) = irritation.at() else {
+            return true;
+        };
+        let Some(changed_lines) = self.changed_lines.get(src.path()) else {
+            return false;
+        };
+        if src.is_whole_file() {
+            return true;
+        }
+
+        let location = src.location();
+        let irritation_lines = location.start_row..location.end_row + 1;
+        changed_lines
+            .iter()
+            .any(|lines| lines.start < irritation_lines.end && irritation_lines.start < lines.end)
+    }
+}
+
+/// Read the content of the given file as staged in the index.
+pub fn staged_content(project_root: &Utf8Path, path: &PrettyPath) -> Result<String> {
+    run_git(project_root, "show", &[&format!(":./{path}")])
+}
+
+fn run_git(project_root: &Utf8Path, subcommand: &'static str, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .args(["-c", "core.quotePath=false", subcommand])
+        .args(args)
+        .current_dir(project_root)
+        .output()
+        .map_err(|cause| Error::IO {
+            path: PrettyPath::from("git"),
+            action: IOAction::Run,
+            cause,
+        })?;
+    if !output.status.success() {
+        return Err(Error::Git {
+            subcommand,
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+        });
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use camino::Utf8PathBuf;
+    use indoc::indoc;
+
+    use crate::vextest::VexTest;
+
+    use super::*;
+
+    #[test]
+    #[allow(clippy::single_range_in_vec_init)]
+    fn parse() {
+        let diff = Diff::parse(indoc! {r#"
+            diff --git a/src/main.rs b/src/main.rs
+            index 1234567..89abcde 100644
+            --- a/src/main.rs
+            +++ b/src/main.rs
+            @@ -1,0 +2,3 @@ fn main() {
+            +    let x = 1;
+            +    let y = 2;
+            +    let z = 3;
+            @@ -10 +12 @@ fn other() {
+            -    old();
+            +    new();
+            @@ -20,2 +21,0 @@ fn removed() {
+            -    gone();
+            -    also_gone();
+            diff --git a/assets/logo.png b/assets/logo.png
+            Binary files a/assets/logo.png and b/assets/logo.png differ
+            diff --git a/src/new.rs b/src/new.rs
+            new file mode 100644
+            --- /dev/null
+            +++ b/src/new.rs
+            @@ -0,0 +1,2 @@
+            +fn new() {
+            +}
+        "#});
+        assert_eq!(
+            diff.changed_files().map(|p| p.as_str()).collect::<Vec<_>>(),
+            ["src/main.rs", "src/new.rs"]
+        );
+        assert_eq!(
+            diff.changed_lines[&PrettyPath::from("src/main.rs")],
+            [2..5, 12..13]
+        );
+        assert_eq!(diff.changed_lines[&PrettyPath::from("src/new.rs")], [1..3]);
+    }
+
+    struct Repo {
+        _tempdir: tempfile::TempDir,
+        root: Utf8PathBuf,
+    }
+
+    impl Repo {
+        fn new() -> Self {
+            let tempdir = tempfile::tempdir().unwrap();
+            let root = Utf8PathBuf::try_from(tempdir.path().to_owned()).unwrap();
+            let repo = Self {
+                _tempdir: tempdir,
+                root,
+            };
+            repo.git(&["init", "--quiet"]);
+            fs::write(repo.root.join("unchanged.rs"), "fn unchanged() {}\n").unwrap();
+            fs::write(repo.root.join("changed.rs"), "fn changed() {\n}\n").unwrap();
+            repo.git(&["add", "."]);
+            repo.git(&["commit", "--quiet", "-m", "initial"]);
+
+            fs::write(
+                repo.root.join("changed.rs"),
+                "fn changed() {\n    body();\n}\n",
+            )
+            .unwrap();
+            fs::write(repo.root.join("staged.rs"), "fn staged() {}\n").unwrap();
+            repo.git(&["add", "staged.rs"]);
+            fs::write(
+                repo.root.join("staged.rs"),
+                "fn staged() {}\nfn unstaged() {}\n",
+            )
+            .unwrap();
+            fs::write(repo.root.join("untracked.rs"), "fn untracked() {}\n").unwrap();
+            fs::write(repo.root.join("ignored.rs"), "fn ignored() {}\n").unwrap();
+            fs::write(repo.root.join(".git/info/exclude"), "ignored.rs\n").unwrap();
+            repo
+        }
+
+        fn git(&self, args: &[&str]) {
+            let output = Command::new("git")
+                .args([
+                    "-c",
+                    "user.name=test",
+                    "-c",
+                    "user.email=test@example.com",
+                    "-c",
+                    "commit.gpgsign=false",
+                ])
+                .args(args)
+                .current_dir(&self.root)
+                .output()
+                .unwrap();
+            assert!(output.status.success(), "{output:?}");
+        }
+
+        #[allow(clippy::single_range_in_vec_init)]
+        fn check_diffs(&self) {
+            let since_head = Diff::new(&self.root, DiffBase::Revision("HEAD")).unwrap();
+            assert_eq!(
+                since_head
+                    .changed_files()
+                    .map(|p| p.as_str())
+                    .collect::<Vec<_>>(),
+                ["changed.rs", "staged.rs", "untracked.rs"]
+            );
+            assert_eq!(
+                since_head.changed_lines[&PrettyPath::from("changed.rs")],
+                [2..3]
+            );
+            assert_eq!(
+                since_head.changed_lines[&PrettyPath::from("staged.rs")],
+                [1..3]
+            );
+            assert_eq!(
+                since_head.changed_lines[&PrettyPath::from("untracked.rs")],
+                [1..usize::MAX]
+            );
+
+            let staged = Diff::new(&self.root, DiffBase::Index).unwrap();
+            assert_eq!(
+                staged
+                    .changed_files()
+                    .map(|p| p.as_str())
+                    .collect::<Vec<_>>(),
+                ["staged.rs"]
+            );
+            assert_eq!(staged.changed_lines[&PrettyPath::from("staged.rs")], [1..2]);
+        }
+    }
+
+    #[test]
+    fn against_repo() {
+        let repo = Repo::new();
+        repo.check_diffs();
+
+        assert_eq!(
+            staged_content(&repo.root, &PrettyPath::from("staged.rs")).unwrap(),
+            "fn staged() {}\n"
+        );
+
+        let err = Diff::new(&repo.root, DiffBase::Revision("no-such-revision")).unwrap_err();
+        assert!(matches!(err, Error::Git { .. }), "unexpected error: {err}");
+    }
+
+    #[test]
+    fn user_diff_config() {
+        for config in ["diff.noprefix", "diff.mnemonicPrefix"] {
+            let repo = Repo::new();
+            repo.git(&["config", config, "true"]);
+            repo.check_diffs();
+        }
+    }
+
+    #[test]
+    fn touches() {
+        let irritations = VexTest::new("touches")
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+                        vex.observe('open_file', on_open_file)
+
+                    def on_open_project(event):
+                        vex.warn('project-level', 'no location')
+                        vex.search(
+                            'rust',
+                            '(integer_literal) @lit',
+                            on_match,
+                        )
+
+                    def on_open_file(event):
+                        vex.warn('file-level', 'whole file', at=event.path)
+
+                    def on_match(event):
+                        vex.warn('literal', 'literal', at=event.captures['lit'])
+                "#},
+            )
+            .with_source_file(
+                "src/main.rs",
+                indoc! {r#"
+                    fn main() {
+                        let x = 1;
+                        let y = 2;
+                        let z = 3;
+                    }
+                "#},
+            )
+            .with_source_file("src/unchanged.rs", "const X: u32 = 4;\n")
+            .try_run()
+            .unwrap()
+            .irritations;
+        assert_eq!(irritations.len(), 7);
+
+        let diff = Diff::parse(indoc! {r#"
+            diff --git a/src/main.rs b/src/main.rs
+            --- a/src/main.rs
+            +++ b/src/main.rs
+            @@ -3 +3 @@ fn main() {
+            -    let y = 0;
+            +    let y = 2;
+        "#});
+        let touched: Vec<_> = irritations
+            .iter()
+            .filter(|irr| diff.touches(irr))
+            .map(|irr| (irr.lint_id().as_str(), irr.path().map(|path| path.as_str())))
+            .collect();
+        assert_eq!(
+            touched,
+            [
+                ("project-level", None),
+                ("file-level", Some("src/main.rs")),
+                ("literal", Some("src/main.rs")),
+            ]
+        );
+    }
+}
@@ -18,7 +18,9 @@ use crate::{
     result::Result,
 };
 
-#[derive(Debug, Clone, Eq, PartialEq, Hash, Ord, PartialOrd, Allocative, Serialize)]
+#[derive(
+    Debug, Clone, Eq, PartialEq, Hash, Ord, PartialOrd, Allocative, Serialize, Deserialize,
+)]
 pub struct LintId(Id);
 
 impl LintId {
@@ -47,7 +49,9 @@ impl Display for LintId {
     }
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Hash, Ord, PartialOrd, Allocative, Serialize)]
+#[derive(
+    Debug, Clone, Eq, PartialEq, Hash, Ord, PartialOrd, Allocative, Serialize, Deserialize,
+)]
 pub struct GroupId(Id);
 
 impl GroupId {
@@ -4,27 +4,37 @@ use allocative::Allocative;
 use annotate_snippets::{Annotation, AnnotationType, Slice, Snippet, SourceAnnotation};
 use dupe::Dupe;
 use serde::Serialize;
-use starlark::values::{list::AllocList, AllocValue, Heap, StarlarkValue, Value};
+use starlark::{
+    typing::Ty,
+    values::{
+        list::AllocList, type_repr::StarlarkTypeRepr, AllocValue, Heap, StarlarkValue, Value,
+    },
+};
 use starlark_derive::{
     starlark_attrs, starlark_value, NoSerialize, ProvidesStaticType, StarlarkAttrs, Trace,
 };
 
 use crate::{
+    fingerprint::Fingerprint,
     id::{GroupId, LintId},
     logger,
     scriptlets::{main_annotation::MainAnnotation, Location, Node},
+    severity::Severity,
     source_path::PrettyPath,
 };
 
 #[derive(Debug, Clone, PartialEq, Eq, Allocative, Serialize, ProvidesStaticType)]
 #[non_exhaustive]
 pub struct Irritation {
-    lint_id: LintId,
-    group_id: Option<GroupId>,
-    message: String,
-    at: Option<(IrritationSource, Option<String>)>,
-    show_also: Vec<(IrritationSource, String)>,
-    info: Option<String>,
+    pub(crate) lint_id: LintId,
+    pub(crate) group_id: Option<GroupId>,
+    pub(crate) level: Severity,
+    pub(crate) message: String,
+    pub(crate) at: Option<(IrritationSource, Option<String>)>,
+    pub(crate) show_also: Vec<(IrritationSource, String)>,
+    pub(crate) info: Option<String>,
+    pub(crate) fix: Vec<(IrritationSource, String)>,
+    #[serde(skip)]
     pub(crate) rendered: String,
 }
 
@@ -33,11 +43,34 @@ impl Irritation {
         &self.lint_id
     }
 
-    #[allow(unused)]
     pub fn group_id(&self) -> Option<&GroupId> {
         self.group_id.as_ref()
     }
 
+    pub fn level(&self) -> Severity {
+        self.level
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn at(&self) -> Option<(&IrritationSource, Option<&str>)> {
+        self.at.as_ref().map(|(src, label)| (src, label.as_deref()))
+    }
+
+    pub fn show_also(&self) -> &[(IrritationSource, String)] {
+        &self.show_also
+    }
+
+    pub fn info(&self) -> Option<&str> {
+        self.info.as_deref()
+    }
+
+    pub fn fix(&self) -> &[(IrritationSource, String)] {
+        &self.fix
+    }
+
     pub fn path(&self) -> Option<&PrettyPath> {
         self.at.as_ref().map(|(loc, _)| &loc.path)
     }
@@ -46,10 +79,12 @@ impl Irritation {
         let Self {
             lint_id,
             group_id,
+            level,
             message,
             at,
             show_also,
             info,
+            fix,
             rendered,
         } = self;
         let lint_id = heap.alloc(lint_id.as_str());
@@ -57,6 +92,7 @@ impl Irritation {
             .as_ref()
             .map(|group_id| heap.alloc(group_id.as_str()))
             .unwrap_or_else(Value::new_none);
+        let level = heap.alloc(level.name());
         let message = heap.alloc(message);
         let at = at
             .as_ref()
@@ -71,14 +107,20 @@ impl Irritation {
             .as_ref()
             .map(|info| heap.alloc(info))
             .unwrap_or_default();
+        let fix = heap
+            .alloc(AllocList(fix.iter().map(|(loc, replacement)| {
+                heap.alloc((loc.clone(), replacement))
+            })));
         let rendered = rendered.clone();
         heap.alloc(IrritationValue {
             lint_id,
             group_id,
+            level,
             message,
             at,
             show_also,
             info,
+            fix,
             rendered,
         })
     }
@@ -89,10 +131,12 @@ impl Ord for Irritation {
         let Self {
             lint_id,
             group_id: _,
+            level,
             message,
             at,
             show_also,
             info,
+            fix,
             rendered: _,
         } = self;
 
@@ -112,6 +156,8 @@ impl Ord for Irritation {
             at.as_ref().map(label),
             ComparableIterator(show_also.iter().map(label)),
             message,
+            fix,
+            level,
         )
             .cmp(&(
                 other.at.as_ref().map(loc),
@@ -121,6 +167,8 @@ impl Ord for Irritation {
                 other.at.as_ref().map(label),
                 ComparableIterator(other.show_also.iter().map(label)),
                 &other.message,
+                &other.fix,
+                &other.level,
             ));
 
         // ComparableIterator implements Ord on the lexicographic order of its contents.
@@ -182,20 +230,24 @@ impl Display for Irritation {
 struct IrritationValue<'v> {
     lint_id: Value<'v>,
     group_id: Value<'v>,
+    level: Value<'v>,
     message: Value<'v>,
     at: Value<'v>,
     show_also: Value<'v>,
     info: Value<'v>,
+    fix: Value<'v>,
     rendered: String,
 }
 
 impl<'v> IrritationValue<'v> {
     const LINT_ID_ATTR_NAME: &'static str = "id";
     const GROUP_ID_ATTR_NAME: &'static str = "group";
+    const LEVEL_ATTR_NAME: &'static str = "level";
     const MESSAGE_ATTR_NAME: &'static str = "message";
     const AT_ATTR_NAME: &'static str = "at";
     const SHOW_ALSO_ATTR_NAME: &'static str = "show_also";
     const INFO_ATTR_NAME: &'static str = "info";
+    const FIX_ATTR_NAME: &'static str = "fix";
 }
 
 #[starlark_value(type = "Irritation")]
@@ -204,10 +256,12 @@ impl<'v> StarlarkValue<'v> for IrritationValue<'v> {
         [
             Self::LINT_ID_ATTR_NAME,
             Self::GROUP_ID_ATTR_NAME,
+            Self::LEVEL_ATTR_NAME,
             Self::MESSAGE_ATTR_NAME,
             Self::AT_ATTR_NAME,
             Self::SHOW_ALSO_ATTR_NAME,
             Self::INFO_ATTR_NAME,
+            Self::FIX_ATTR_NAME,
         ]
         .into_iter()
         .map(Into::into)
@@ -218,10 +272,12 @@ impl<'v> StarlarkValue<'v> for IrritationValue<'v> {
         match attr {
             Self::LINT_ID_ATTR_NAME => Some(self.lint_id.dupe()),
             Self::GROUP_ID_ATTR_NAME => Some(self.group_id.dupe()),
+            Self::LEVEL_ATTR_NAME => Some(self.level.dupe()),
             Self::MESSAGE_ATTR_NAME => Some(self.message.dupe()),
             Self::AT_ATTR_NAME => Some(self.at.dupe()),
             Self::SHOW_ALSO_ATTR_NAME => Some(self.show_also.dupe()),
             Self::INFO_ATTR_NAME => Some(self.info.dupe()),
+            Self::FIX_ATTR_NAME => Some(self.fix.dupe()),
             _ => None,
         }
     }
@@ -230,10 +286,12 @@ impl<'v> StarlarkValue<'v> for IrritationValue<'v> {
         [
             Self::LINT_ID_ATTR_NAME,
             Self::GROUP_ID_ATTR_NAME,
+            Self::LEVEL_ATTR_NAME,
             Self::MESSAGE_ATTR_NAME,
             Self::AT_ATTR_NAME,
             Self::SHOW_ALSO_ATTR_NAME,
             Self::INFO_ATTR_NAME,
+            Self::FIX_ATTR_NAME,
         ]
         .contains(&attr)
     }
@@ -253,11 +311,14 @@ impl Display for IrritationValue<'_> {
 
 #[derive(Debug, Clone, PartialEq, Eq, Allocative, Serialize, StarlarkAttrs, ProvidesStaticType)]
 pub struct IrritationSource {
-    path: PrettyPath,
+    pub(crate) path: PrettyPath,
     #[starlark(skip)]
     #[allocative(skip)]
-    byte_range: Range<usize>,
-    location: Location,
+    pub(crate) byte_range: Range<usize>,
+    pub(crate) location: Location,
+    #[starlark(skip)]
+    #[serde(skip)]
+    pub(crate) fingerprint: Fingerprint,
 }
 
 impl IrritationSource {
@@ -266,6 +327,7 @@ impl IrritationSource {
             path: node.source_file.path.pretty_path.dupe(),
             byte_range: node.byte_range(),
             location: Location::of(node),
+            fingerprint: Fingerprint::of_code(&node.source_file.content[node.byte_range()]),
         }
     }
 
@@ -274,8 +336,30 @@ impl IrritationSource {
             path,
             byte_range: 0..0,
             location: Location::start_of_file(),
+            fingerprint: Fingerprint::of(b""),
         }
     }
+
+    pub fn path(&self) -> &PrettyPath {
+        &self.path
+    }
+
+    pub fn byte_range(&self) -> &Range<usize> {
+        &self.byte_range
+    }
+
+    pub fn location(&self) -> &Location {
+        &self.location
+    }
+
+    /// A fingerprint of the source code at this location.
+    pub fn fingerprint(&self) -> Fingerprint {
+        self.fingerprint
+    }
+
+    pub fn is_whole_file(&self) -> bool {
+        self.byte_range == (0..0)
+    }
 }
 
 impl Ord for IrritationSource {
@@ -299,11 +383,20 @@ impl Dupe for IrritationSource {
     // .path: Dupe
     // .byte_range: !Dupe but cheap
     // .location: Dupe
+    // .fingerprint: Dupe
 }
 
 #[starlark_value(type = "IrritationSource")]
 impl<'v> StarlarkValue<'v> for IrritationSource {
     starlark_attrs!();
+
+    fn attr_ty(attr: &str) -> Option<Ty> {
+        match attr {
+            "path" => Some(PrettyPath::starlark_type_repr()),
+            "location" => Some(Location::starlark_type_repr()),
+            _ => None,
+        }
+    }
 }
 
 impl<'v> AllocValue<'v> for IrritationSource {
@@ -322,10 +415,12 @@ impl Display for IrritationSource {
 pub struct IrritationRenderer<'v> {
     lint_id: LintId,
     group_id: Option<GroupId>,
+    level: Severity,
     message: &'v str,
     source: Option<MainAnnotation<'v>>,
     show_also: Vec<(Node<'v>, &'v str)>,
     info: Option<&'v str>,
+    fix: Vec<(Node<'v>, &'v str)>,
 }
 
 impl<'v> IrritationRenderer<'v> {
@@ -333,10 +428,12 @@ impl<'v> IrritationRenderer<'v> {
         Self {
             lint_id,
             group_id: None,
+            level: Severity::default(),
             message,
             source: None,
             show_also: Vec::with_capacity(0),
             info: None,
+            fix: Vec::with_capacity(0),
         }
     }
 
@@ -344,6 +441,10 @@ impl<'v> IrritationRenderer<'v> {
         self.group_id = Some(group_id);
     }
 
+    pub fn set_level(&mut self, level: Severity) {
+        self.level = level;
+    }
+
     pub fn set_source(&mut self, source: MainAnnotation<'v>) {
         self.source = Some(source);
     }
@@ -356,25 +457,33 @@ impl<'v> IrritationRenderer<'v> {
         self.info = Some(info);
     }
 
+    pub fn set_fix(&mut self, fix: Vec<(Node<'v>, &'v str)>) {
+        self.fix = fix;
+    }
+
     pub fn render(self) -> Irritation {
         let Self {
             lint_id,
             group_id,
+            level,
             source,
             message,
             show_also,
             info,
+            fix,
         } = self;
 
         let file_name = source.as_ref().map(|source| source.pretty_path().as_str());
         let group_info = group_id
             .as_ref()
             .map(|group_id| format!("this lint is from the ‘{group_id}’ group"));
+        let fix_info =
+            (!fix.is_empty()).then_some("a fix is available, run `vex check --fix` to apply it");
         let snippet = Snippet {
             title: Some(Annotation {
                 id: Some(lint_id.as_str()),
                 label: Some(message),
-                annotation_type: AnnotationType::Warning,
+                annotation_type: level.annotation_type(),
             }),
             slices: source
                 .iter()
@@ -386,7 +495,7 @@ impl<'v> IrritationRenderer<'v> {
                         annotations: vec![SourceAnnotation {
                             range: (0, 1),
                             label: label.unwrap_or_default(),
-                            annotation_type: AnnotationType::Warning,
+                            annotation_type: level.annotation_type(),
                         }],
                         fold: false,
                     },
@@ -414,7 +523,7 @@ impl<'v> IrritationRenderer<'v> {
                                     node.end_byte() - range.start,
                                 ),
                                 label: label.unwrap_or_default(),
-                                annotation_type: AnnotationType::Warning,
+                                annotation_type: level.annotation_type(),
                             }]
                             .into_iter()
                             .chain(show_also.iter().map(|(node, label)| SourceAnnotation {
@@ -443,6 +552,11 @@ impl<'v> IrritationRenderer<'v> {
                     label: Some(group_info),
                     annotation_type: AnnotationType::Note,
                 }))
+                .chain(fix_info.map(|fix_info| Annotation {
+                    id: None,
+                    label: Some(fix_info),
+                    annotation_type: AnnotationType::Help,
+                }))
                 .collect(),
         };
 
@@ -462,13 +576,19 @@ impl<'v> IrritationRenderer<'v> {
             .map(|(node, label)| (IrritationSource::at(&node), label.to_string()))
             .collect();
         let info = info.map(|e| e.to_string());
+        let fix = fix
+            .into_iter()
+            .map(|(node, replacement)| (IrritationSource::at(&node), replacement.to_string()))
+            .collect();
         Irritation {
             lint_id,
             group_id,
+            level,
             message,
             at,
             show_also,
             info,
+            fix,
             rendered,
         }
     }
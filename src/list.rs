@@ -0,0 +1,250 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display, Formatter},
+};
+
+use camino::{Utf8Path, Utf8PathBuf};
+use starlark::values::FrozenHeap;
+use strum::IntoEnumIterator;
+
+use crate::{
+    context::Context,
+    logger,
+    result::Result,
+    scan,
+    scriptlets::{event::EventKind, LintDeclaration, Observer, ScriptArgsValueMap, VexingStore},
+    source_path::PrettyPath,
+    verbosity::Verbosity,
+    warning_filter::WarningFilter,
+};
+
+pub fn list() -> Result<()> {
+    let ctx = Context::acquire()?;
+    let verbosity = logger::verbosity();
+
+    let script_args_heap = FrozenHeap::new();
+    let script_args = ScriptArgsValueMap::with_args(&ctx.script_args, &script_args_heap);
+    let store = crate::load_store(&ctx, &script_args, verbosity)?;
+    let warning_filter = crate::try_make_warning_filter(&ctx.manifest)?;
+
+    let listing = Listing::new(&ctx, &store, &warning_filter, &script_args, verbosity)?;
+    print!("{listing}");
+    Ok(())
+}
+
+/// What each scriptlet observes, searches for and may emit.
+#[derive(Debug)]
+struct Listing {
+    vexes_dir: Utf8PathBuf,
+    scriptlets: BTreeMap<PrettyPath, ScriptletListing>,
+}
+
+#[derive(Debug, Default)]
+struct ScriptletListing {
+    events: Vec<EventKind>,
+    queries: Vec<String>,
+    lints: Vec<(LintDeclaration, bool)>,
+}
+
+impl Listing {
+    /// List the contents of the given store. Queries are found by opening the project, so those
+    /// searched for only when a particular file is opened are not listed.
+    fn new(
+        ctx: &Context,
+        store: &VexingStore,
+        warning_filter: &WarningFilter,
+        script_args: &ScriptArgsValueMap,
+        verbosity: Verbosity,
+    ) -> Result<Self> {
+        let mut scriptlets: BTreeMap<_, _> = store
+            .lint_declarations()
+            .iter()
+            .map(|(path, declarations)| {
+                let lints = declarations
+                    .iter()
+                    .map(|declaration| {
+                        let active = match &declaration.group {
+                            Some(group) => {
+                                warning_filter.is_active_with_group(&declaration.id, group)
+                            }
+                            None => warning_filter.is_active(&declaration.id),
+                        };
+                        (declaration.clone(), active)
+                    })
+                    .collect();
+                let listing = ScriptletListing {
+                    lints,
+                    ..Default::default()
+                };
+                (path.clone(), listing)
+            })
+            .collect();
+
+        let mut add_event = |observer: &Observer, event_kind| {
+            let events = &mut scriptlets
+                .entry(observer.scriptlet().clone())
+                .or_default()
+                .events;
+            if !events.contains(&event_kind) {
+                events.push(event_kind);
+            }
+        };
+        for event_kind in EventKind::iter() {
+            match event_kind {
+                EventKind::OpenProject | EventKind::OpenFile => store
+                    .observers_for(event_kind)
+                    .iter()
+                    .for_each(|observer| add_event(observer, event_kind)),
+                EventKind::Match => {}
+                EventKind::PreTestRun | EventKind::PostTestRun => store
+                    .test_observer_data()
+                    .values()
+                    .flat_map(|observer_data| observer_data.observers_for(event_kind))
+                    .for_each(|observer| add_event(observer, event_kind)),
+            }
+        }
+
+        let (_, project_queries) =
+            scan::open_project(ctx, store, warning_filter, script_args, verbosity)?;
+        for (language, query, on_match) in project_queries {
+            scriptlets
+                .entry(on_match.scriptlet().clone())
+                .or_default()
+                .queries
+                .push(format!("{language}: {}", query.summary()));
+        }
+
+        Ok(Self {
+            vexes_dir: Utf8Path::new(ctx.manifest.run.vexes_dir.as_str()).to_owned(),
+            scriptlets,
+        })
+    }
+}
+
+impl Display for Listing {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (path, listing) in &self.scriptlets {
+            let ScriptletListing {
+                events,
+                queries,
+                lints,
+            } = listing;
+
+            writeln!(f, "{}", self.vexes_dir.join(path.as_str()))?;
+            if !events.is_empty() {
+                let events: Vec<_> = events.iter().map(|event| event.name()).collect();
+                writeln!(f, "  events: {}", events.join(", "))?;
+            }
+            if !queries.is_empty() {
+                writeln!(f, "  queries:")?;
+                for query in queries {
+                    writeln!(f, "    {query}")?;
+                }
+            }
+            if !lints.is_empty() {
+                writeln!(f, "  lints:")?;
+                for (declaration, active) in lints {
+                    write!(f, "    {}", declaration.id)?;
+                    if let Some(group) = &declaration.group {
+                        write!(f, " [{group}]")?;
+                    }
+                    if !active {
+                        write!(f, " (inactive)")?;
+                    }
+                    if let Some(description) = &declaration.description {
+                        write!(f, ": {description}")?;
+                    }
+                    writeln!(f)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use indoc::indoc;
+
+    use crate::context::Manifest;
+
+    use super::*;
+
+    #[test]
+    fn listing() {
+        let root_dir = tempfile::tempdir().unwrap();
+        let root_path = Utf8PathBuf::try_from(root_dir.path().to_path_buf()).unwrap();
+        fs::write(
+            root_path.join(Manifest::FILE_NAME),
+            indoc! {r#"
+                [vex]
+                version = "1"
+
+                [lints.active]
+                old-style = false
+            "#},
+        )
+        .unwrap();
+        fs::create_dir_all(root_path.join("vexes/lib")).unwrap();
+        fs::write(
+            root_path.join("vexes/numbers.star"),
+            indoc! {r#"
+                def init():
+                    vex.declare_lint('big-number', group='numbers', description='numbers which are too big')
+                    vex.declare_lint('old-style')
+                    vex.observe('open_project', on_open_project)
+                    vex.observe('post_test_run', on_post_test_run)
+
+                def on_open_project(event):
+                    vex.search(
+                        'rust',
+                        '''
+                            (integer_literal) @lit
+                        ''',
+                        on_match,
+                    )
+
+                def on_match(event):
+                    pass
+
+                def on_post_test_run(event):
+                    pass
+            "#},
+        )
+        .unwrap();
+        fs::write(
+            root_path.join("vexes/lib/helpers.star"),
+            "def helper():\n    pass\n",
+        )
+        .unwrap();
+
+        let ctx = Context::acquire_in(&root_path).unwrap();
+        let script_args_heap = FrozenHeap::new();
+        let script_args = ScriptArgsValueMap::with_args(&ctx.script_args, &script_args_heap);
+        let store = crate::load_store(&ctx, &script_args, Verbosity::Quiet).unwrap();
+        let warning_filter = crate::try_make_warning_filter(&ctx.manifest).unwrap();
+        let listing = Listing::new(
+            &ctx,
+            &store,
+            &warning_filter,
+            &script_args,
+            Verbosity::Quiet,
+        )
+        .unwrap();
+        assert_eq!(
+            listing.to_string(),
+            indoc! {"
+                vexes/lib/helpers.star
+                vexes/numbers.star
+                  events: open_project, post_test_run
+                  queries:
+                    rust: (integer_literal) @lit
+                  lints:
+                    big-number [numbers]: numbers which are too big
+                    old-style (inactive)
+            "}
+        );
+    }
+}
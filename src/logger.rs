@@ -8,6 +8,7 @@ use owo_colors::Style;
 use crate::{result::Result, verbosity::Verbosity};
 
 pub static NUM_ERRS: Mutex<u32> = Mutex::new(0);
+pub static NUM_LINT_ERRS: Mutex<u32> = Mutex::new(0);
 pub static NUM_WARNINGS: Mutex<u32> = Mutex::new(0);
 
 static mut VERBOSITY: Verbosity = Verbosity::Terse;
@@ -20,9 +21,13 @@ pub fn init(level: Verbosity) -> Result<()> {
     Ok(())
 }
 
+/// The exit code for the run: 255 if vex itself failed, 2 if error-level problems were found and
+/// 1 if warnings were.
 pub fn exit_code() -> ExitCode {
     if *NUM_ERRS.lock().expect("failed to lock NUM_ERRS") > 0 {
         ExitCode::from(u8::MAX)
+    } else if *NUM_LINT_ERRS.lock().expect("failed to lock NUM_LINT_ERRS") > 0 {
+        ExitCode::from(2)
     } else if *NUM_WARNINGS.lock().expect("failed to lock NUM_WARNINGS") > 0 {
         ExitCode::from(1)
     } else {
@@ -42,6 +47,15 @@ macro_rules! error {
     }}
 }
 
+/// Log a problem found by a vex, as opposed to a failure of vex itself.
+#[macro_export]
+macro_rules! lint_error {
+    ($($arg:tt)+) => {{
+        *$crate::logger::NUM_LINT_ERRS.lock().expect("failed to lock NUM_LINT_ERRS") += 1;
+        ::log::error!($($arg)+)
+    }}
+}
+
 #[macro_export]
 macro_rules! warn {
     ($($arg:tt)+) => {{
@@ -52,6 +66,7 @@ macro_rules! warn {
 
 lazy_static! {
     pub static ref SUCCESS_STYLE: Style = Style::new().green().bold();
+    pub static ref NOTE_STYLE: Style = Style::new().bold();
 }
 
 #[macro_export]
@@ -69,6 +84,21 @@ macro_rules! success {
     };
 }
 
+#[macro_export]
+macro_rules! note {
+    ($($arg:tt)+) => {
+        {
+            use ::owo_colors::OwoColorize;
+            ::log::warn!(
+                custom=true;
+                "{}: {}",
+                "note".if_supports_color(::owo_colors::Stream::Stdout, |text| text.style(*$crate::logger::NOTE_STYLE)),
+                format!($($arg)+),
+            )
+        }
+    };
+}
+
 struct Logger {
     level: Level,
 }
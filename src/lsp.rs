@@ -0,0 +1,9 @@
+mod client;
+mod position;
+mod server;
+mod transport;
+
+pub use self::client::LanguageServer;
+#[cfg(test)]
+pub use self::client::MockResponder;
+pub use self::server::serve;
@@ -0,0 +1,728 @@
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    io::{BufReader, Read, Write},
+    process::{Child, Command, Stdio},
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError},
+        Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use camino::{Utf8Path, Utf8PathBuf};
+use dupe::Dupe;
+use lsp_types::{
+    notification::{DidChangeTextDocument, DidOpenTextDocument, Exit, Initialized, Notification},
+    request::{GotoDefinition, HoverRequest, Initialize, References, Request, Shutdown},
+    ClientCapabilities, DidChangeTextDocumentParams, DidOpenTextDocumentParams,
+    GotoDefinitionParams, GotoDefinitionResponse, HoverContents, HoverParams, InitializeParams,
+    InitializedParams, LanguageString, MarkedString, ReferenceContext, ReferenceParams,
+    TextDocumentContentChangeEvent, TextDocumentIdentifier, TextDocumentItem,
+    TextDocumentPositionParams, Url, VersionedTextDocumentIdentifier, WorkspaceFolder,
+};
+use serde_json::{json, Value};
+
+use crate::{
+    context::LanguageServerCommand,
+    error::{Error, IOAction},
+    fingerprint::Fingerprint,
+    language::Language,
+    lsp::{
+        position::{location_in, position_in},
+        transport::{read_message, write_message},
+    },
+    result::Result,
+    scriptlets::LspLocation,
+    source_file::ParsedSourceFile,
+    source_path::PrettyPath,
+};
+
+/// How long to wait for a response before giving up on the server.
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long to wait for the server to acknowledge a shutdown before killing it.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// A running language server, which answers questions about the files of one language.
+pub struct LanguageServer {
+    language: Language,
+    project_root: Utf8PathBuf,
+    connection: Mutex<Connection>,
+}
+
+impl LanguageServer {
+    /// Spawn the given command and perform the initial handshake.
+    pub fn start(
+        project_root: &Utf8Path,
+        language: Language,
+        command: &LanguageServerCommand,
+    ) -> Result<Self> {
+        let mut parts = command.parts();
+        let Some(program) = parts.next() else {
+            return Err(Error::LanguageServer {
+                language,
+                reason: "command is empty".into(),
+            });
+        };
+        let mut child = Command::new(program)
+            .args(parts)
+            .current_dir(project_root)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|cause| Error::IO {
+                path: PrettyPath::from(program),
+                action: IOAction::Run,
+                cause,
+            })?;
+        let stdin = child.stdin.take().expect("internal error: stdin not piped");
+        let stdout = child
+            .stdout
+            .take()
+            .expect("internal error: stdout not piped");
+        Self::connect(project_root, language, stdin, stdout, Some(child))
+    }
+
+    fn connect(
+        project_root: &Utf8Path,
+        language: Language,
+        writer: impl Write + Send + 'static,
+        reader: impl Read + Send + 'static,
+        child: Option<Child>,
+    ) -> Result<Self> {
+        let name = format!("{language} language server");
+        let (tx, messages) = mpsc::channel();
+        thread::spawn({
+            let name = name.clone();
+            move || {
+                let mut reader = BufReader::new(reader);
+                while let Some(message) = read_message(&mut reader, &name).transpose() {
+                    let failed = message.is_err();
+                    if tx.send(message).is_err() || failed {
+                        break;
+                    }
+                }
+            }
+        });
+
+        let server = Self {
+            language: language.dupe(),
+            project_root: project_root.to_owned(),
+            connection: Mutex::new(Connection {
+                language,
+                name,
+                writer: Box::new(writer),
+                messages,
+                next_id: 0,
+                open_documents: HashMap::new(),
+                child,
+            }),
+        };
+
+        let root_uri = Url::from_directory_path(project_root).ok();
+        #[allow(deprecated)] // `root_uri` is still the most widely supported field.
+        let params = InitializeParams {
+            process_id: Some(std::process::id()),
+            root_uri: root_uri.clone(),
+            workspace_folders: root_uri.map(|uri| {
+                vec![WorkspaceFolder {
+                    uri,
+                    name: project_root.file_name().unwrap_or_default().to_owned(),
+                }]
+            }),
+            capabilities: ClientCapabilities::default(),
+            ..Default::default()
+        };
+        {
+            let mut connection = server.connection();
+            connection.request::<Initialize>(params, TIMEOUT)?;
+            connection.notify::<Initialized>(InitializedParams {})?;
+        }
+        Ok(server)
+    }
+
+    /// Return the hover text for the given position, rendered as markdown.
+    pub fn hover(&self, file: &ParsedSourceFile, byte_offset: usize) -> Result<Option<String>> {
+        let mut connection = self.connection();
+        let text_document_position_params = connection.position_params(file, byte_offset)?;
+        let hover = connection.request::<HoverRequest>(
+            HoverParams {
+                text_document_position_params,
+                work_done_progress_params: Default::default(),
+            },
+            TIMEOUT,
+        )?;
+        Ok(hover.map(|hover| hover_text(hover.contents)))
+    }
+
+    /// Return the type of the expression at the given position, as reported in the first code
+    /// block of its hover text.
+    pub fn type_of(&self, file: &ParsedSourceFile, byte_offset: usize) -> Result<Option<String>> {
+        Ok(self
+            .hover(file, byte_offset)?
+            .as_deref()
+            .and_then(first_code_block))
+    }
+
+    pub fn definition(
+        &self,
+        file: &ParsedSourceFile,
+        byte_offset: usize,
+    ) -> Result<Vec<LspLocation>> {
+        let mut connection = self.connection();
+        let text_document_position_params = connection.position_params(file, byte_offset)?;
+        let response = connection.request::<GotoDefinition>(
+            GotoDefinitionParams {
+                text_document_position_params,
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            },
+            TIMEOUT,
+        )?;
+        drop(connection);
+
+        let locations = match response {
+            None => vec![],
+            Some(GotoDefinitionResponse::Scalar(location)) => vec![location],
+            Some(GotoDefinitionResponse::Array(locations)) => locations,
+            Some(GotoDefinitionResponse::Link(links)) => links
+                .into_iter()
+                .map(|link| lsp_types::Location::new(link.target_uri, link.target_selection_range))
+                .collect(),
+        };
+        Ok(self.convert_locations(file, locations))
+    }
+
+    pub fn references(
+        &self,
+        file: &ParsedSourceFile,
+        byte_offset: usize,
+        include_declaration: bool,
+    ) -> Result<Vec<LspLocation>> {
+        let mut connection = self.connection();
+        let text_document_position = connection.position_params(file, byte_offset)?;
+        let locations = connection.request::<References>(
+            ReferenceParams {
+                text_document_position,
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+                context: ReferenceContext {
+                    include_declaration,
+                },
+            },
+            TIMEOUT,
+        )?;
+        drop(connection);
+
+        Ok(self.convert_locations(file, locations.unwrap_or_default()))
+    }
+
+    fn connection(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.connection
+            .lock()
+            .expect("internal error: language server connection poisoned")
+    }
+
+    /// Convert locations returned by the server into project-relative paths with vex-style
+    /// locations.
+    fn convert_locations(
+        &self,
+        file: &ParsedSourceFile,
+        locations: Vec<lsp_types::Location>,
+    ) -> Vec<LspLocation> {
+        locations
+            .into_iter()
+            .map(|location| {
+                let Some(abs_path) = location
+                    .uri
+                    .to_file_path()
+                    .ok()
+                    .and_then(|path| Utf8PathBuf::from_path_buf(path).ok())
+                else {
+                    return LspLocation {
+                        path: PrettyPath::from(location.uri.as_str()),
+                        location: location_in(None, &location.range),
+                    };
+                };
+                let text = if abs_path == file.path.abs_path.as_ref() {
+                    Some(file.content.clone())
+                } else {
+                    fs::read_to_string(&abs_path).ok()
+                };
+                let path = match abs_path.strip_prefix(&self.project_root) {
+                    Ok(rel_path) => PrettyPath::new(rel_path),
+                    Err(_) => PrettyPath::new(&abs_path),
+                };
+                LspLocation {
+                    path,
+                    location: location_in(text.as_deref(), &location.range),
+                }
+            })
+            .collect()
+    }
+}
+
+impl fmt::Debug for LanguageServer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LanguageServer")
+            .field("language", &self.language)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for LanguageServer {
+    fn drop(&mut self) {
+        let Ok(connection) = self.connection.get_mut() else {
+            return;
+        };
+        let _ = connection.request::<Shutdown>((), SHUTDOWN_TIMEOUT);
+        let _ = connection.notify::<Exit>(());
+        if let Some(child) = &mut connection.child {
+            if !matches!(child.try_wait(), Ok(Some(_))) {
+                let _ = child.kill();
+            }
+            let _ = child.wait();
+        }
+    }
+}
+
+struct Connection {
+    language: Language,
+    name: String,
+    writer: Box<dyn Write + Send>,
+    messages: Receiver<Result<Value>>,
+    next_id: i64,
+    open_documents: HashMap<Url, OpenDocument>,
+    child: Option<Child>,
+}
+
+/// The state of a file as last sent to the server.
+struct OpenDocument {
+    version: i32,
+    content: Fingerprint,
+}
+
+impl Connection {
+    fn request<R: Request>(&mut self, params: R::Params, timeout: Duration) -> Result<R::Result> {
+        self.next_id += 1;
+        let id = self.next_id;
+        self.send(&json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": R::METHOD,
+            "params": params,
+        }))?;
+
+        let deadline = Instant::now() + timeout;
+        loop {
+            let message = match self
+                .messages
+                .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+            {
+                Ok(message) => message?,
+                Err(RecvTimeoutError::Timeout) => {
+                    return Err(self.error(format!(
+                        "no response to {} within {}s",
+                        R::METHOD,
+                        timeout.as_secs()
+                    )))
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(self.error("server exited unexpectedly".into()))
+                }
+            };
+
+            if let Some(method) = message.get("method") {
+                // Servers may make requests of their own, such as to register capabilities. None
+                // of these are supported, but each must be answered.
+                if let Some(request_id) = message.get("id") {
+                    let response = if method == "client/registerCapability"
+                        || method == "window/workDoneProgress/create"
+                    {
+                        json!({ "jsonrpc": "2.0", "id": request_id, "result": null })
+                    } else {
+                        json!({
+                            "jsonrpc": "2.0",
+                            "id": request_id,
+                            "error": { "code": -32601, "message": "method not found" },
+                        })
+                    };
+                    self.send(&response)?;
+                }
+                continue;
+            }
+            if message.get("id").and_then(Value::as_i64) != Some(id) {
+                continue; // Stale response to an abandoned request.
+            }
+
+            if let Some(error) = message.get("error") {
+                let reason = error
+                    .get("message")
+                    .and_then(Value::as_str)
+                    .unwrap_or("unknown error");
+                return Err(self.error(format!("{} failed: {reason}", R::METHOD)));
+            }
+            let result = message.get("result").cloned().unwrap_or(Value::Null);
+            return serde_json::from_value(result)
+                .map_err(|err| self.error(format!("invalid response to {}: {err}", R::METHOD)));
+        }
+    }
+
+    fn notify<N: Notification>(&mut self, params: N::Params) -> Result<()> {
+        self.send(&json!({
+            "jsonrpc": "2.0",
+            "method": N::METHOD,
+            "params": params,
+        }))
+    }
+
+    fn send(&mut self, message: &Value) -> Result<()> {
+        write_message(&mut self.writer, message, &self.name)
+    }
+
+    /// Open the given file in the server, or send its new content if it has changed since it was
+    /// last sent, returning its URI.
+    fn open(&mut self, file: &ParsedSourceFile) -> Result<Url> {
+        let uri = Url::from_file_path(file.path.abs_path.as_str())
+            .map_err(|()| self.error(format!("cannot refer to {}", file.path.pretty_path)))?;
+        let content = Fingerprint::of(file.content.as_bytes());
+        let Some(document) = self.open_documents.get_mut(&uri) else {
+            self.notify::<DidOpenTextDocument>(DidOpenTextDocumentParams {
+                text_document: TextDocumentItem {
+                    uri: uri.clone(),
+                    language_id: self.language.to_string(),
+                    version: 0,
+                    text: file.content.clone(),
+                },
+            })?;
+            self.open_documents.insert(
+                uri.clone(),
+                OpenDocument {
+                    version: 0,
+                    content,
+                },
+            );
+            return Ok(uri);
+        };
+        if document.content == content {
+            return Ok(uri);
+        }
+
+        document.version += 1;
+        document.content = content;
+        let version = document.version;
+        self.notify::<DidChangeTextDocument>(DidChangeTextDocumentParams {
+            text_document: VersionedTextDocumentIdentifier {
+                uri: uri.clone(),
+                version,
+            },
+            content_changes: vec![TextDocumentContentChangeEvent {
+                range: None,
+                range_length: None,
+                text: file.content.clone(),
+            }],
+        })?;
+        Ok(uri)
+    }
+
+    fn position_params(
+        &mut self,
+        file: &ParsedSourceFile,
+        byte_offset: usize,
+    ) -> Result<TextDocumentPositionParams> {
+        Ok(TextDocumentPositionParams {
+            text_document: TextDocumentIdentifier {
+                uri: self.open(file)?,
+            },
+            position: position_in(&file.content, byte_offset),
+        })
+    }
+
+    fn error(&self, reason: String) -> Error {
+        Error::LanguageServer {
+            language: self.language.dupe(),
+            reason,
+        }
+    }
+}
+
+fn hover_text(contents: HoverContents) -> String {
+    let marked_text = |marked_string| match marked_string {
+        MarkedString::String(text) => text,
+        MarkedString::LanguageString(LanguageString { language, value }) => {
+            format!("```{language}\n{value}\n```")
+        }
+    };
+    match contents {
+        HoverContents::Scalar(marked_string) => marked_text(marked_string),
+        HoverContents::Array(marked_strings) => marked_strings
+            .into_iter()
+            .map(marked_text)
+            .collect::<Vec<_>>()
+            .join("\n\n"),
+        HoverContents::Markup(markup) => markup.value,
+    }
+}
+
+/// Return the content of the first fenced code block in some markdown, or failing that, its
+/// first non-empty line.
+fn first_code_block(markdown: &str) -> Option<String> {
+    let is_fence = |line: &&str| line.trim_start().starts_with("```");
+    let mut lines = markdown.lines().skip_while(|line| !is_fence(line));
+    if lines.next().is_some() {
+        let block = lines
+            .take_while(|line| !is_fence(line))
+            .collect::<Vec<_>>()
+            .join("\n");
+        if !block.trim().is_empty() {
+            return Some(block);
+        }
+    }
+    markdown
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .map(str::to_owned)
+}
+
+#[cfg(test)]
+pub use self::mock::MockResponder;
+
+#[cfg(test)]
+mod mock {
+    use std::{
+        collections::VecDeque,
+        io::{self, BufReader, Read, Write},
+        sync::mpsc::{self, Receiver, Sender},
+        thread,
+    };
+
+    use camino::Utf8Path;
+    use serde_json::{json, Value};
+
+    use crate::{
+        language::Language,
+        lsp::transport::{read_message, write_message},
+        result::Result,
+    };
+
+    use super::LanguageServer;
+
+    /// Computes the result of a request from its method and parameters. Document notifications
+    /// are also passed, and their results ignored.
+    pub type MockResponder = Box<dyn Fn(&str, &Value) -> Value + Send>;
+
+    impl LanguageServer {
+        /// Start an in-process server which answers requests using the given function.
+        pub fn mock(
+            project_root: &Utf8Path,
+            language: Language,
+            responder: MockResponder,
+        ) -> Result<Self> {
+            let (client_writer, server_reader) = pipe();
+            let (server_writer, client_reader) = pipe();
+            thread::spawn(move || serve(server_reader, server_writer, responder));
+            Self::connect(project_root, language, client_writer, client_reader, None)
+        }
+    }
+
+    fn serve(reader: PipeReader, mut writer: PipeWriter, responder: MockResponder) {
+        let mut reader = BufReader::new(reader);
+        while let Ok(Some(message)) = read_message(&mut reader, "mock client") {
+            let method = message["method"].as_str().unwrap_or_default();
+            if method == "exit" {
+                return;
+            }
+            let Some(id) = message.get("id") else {
+                // Document notifications are shown to the responder so that tests can observe
+                // them.
+                if method.starts_with("textDocument/") {
+                    responder(method, &message["params"]);
+                }
+                continue;
+            };
+            let result = match method {
+                "initialize" => json!({ "capabilities": {} }),
+                "shutdown" => Value::Null,
+                _ => responder(method, &message["params"]),
+            };
+            let response = json!({ "jsonrpc": "2.0", "id": id, "result": result });
+            if write_message(&mut writer, &response, "mock server").is_err() {
+                return;
+            }
+        }
+    }
+
+    fn pipe() -> (PipeWriter, PipeReader) {
+        let (tx, rx) = mpsc::channel();
+        (
+            PipeWriter(tx),
+            PipeReader {
+                chunks: rx,
+                buf: VecDeque::new(),
+            },
+        )
+    }
+
+    struct PipeWriter(Sender<Vec<u8>>);
+
+    impl Write for PipeWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0
+                .send(buf.to_vec())
+                .map_err(|_| io::ErrorKind::BrokenPipe)?;
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    struct PipeReader {
+        chunks: Receiver<Vec<u8>>,
+        buf: VecDeque<u8>,
+    }
+
+    impl Read for PipeReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.buf.is_empty() {
+                match self.chunks.recv() {
+                    Ok(chunk) => self.buf.extend(chunk),
+                    Err(_) => return Ok(0),
+                }
+            }
+            self.buf.read(buf)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use indoc::indoc;
+    use lsp_types::MarkupContent;
+
+    use crate::{
+        context::{Context, Manifest},
+        source_path::SourcePath,
+    };
+
+    use super::*;
+
+    #[test]
+    fn hover_contents() {
+        assert_eq!(
+            hover_text(HoverContents::Array(vec![
+                MarkedString::LanguageString(LanguageString {
+                    language: "rust".into(),
+                    value: "fn f()".into(),
+                }),
+                MarkedString::String("Does things.".into()),
+            ])),
+            "```rust\nfn f()\n```\n\nDoes things."
+        );
+        assert_eq!(
+            hover_text(HoverContents::Markup(MarkupContent {
+                kind: lsp_types::MarkupKind::PlainText,
+                value: "i32".into(),
+            })),
+            "i32"
+        );
+    }
+
+    #[test]
+    fn code_blocks() {
+        assert_eq!(
+            first_code_block(indoc! {"
+                Some preamble
+
+                ```rust
+                Result<Db, Error>
+                ```
+
+                ```rust
+                ignored
+                ```
+            "})
+            .as_deref(),
+            Some("Result<Db, Error>")
+        );
+        assert_eq!(first_code_block("\n  i32  \nmore").as_deref(), Some("i32"));
+        assert_eq!(first_code_block(""), None);
+    }
+
+    #[test]
+    fn document_sync() {
+        let notifications = Arc::new(Mutex::new(vec![]));
+        let responder: MockResponder = {
+            let notifications = notifications.clone();
+            Box::new(move |method, params| {
+                if method != "textDocument/hover" {
+                    let text = params["textDocument"]
+                        .get("text")
+                        .unwrap_or(&params["contentChanges"][0]["text"]);
+                    notifications.lock().unwrap().push(format!(
+                        "{method} v{}: {text}",
+                        params["textDocument"]["version"]
+                    ));
+                }
+                Value::Null
+            })
+        };
+        let project_root = Utf8Path::new("/project");
+        let server = LanguageServer::mock(project_root, Language::Rust, responder).unwrap();
+
+        let ctx = Context::new_with_manifest(project_root, Manifest::default());
+        let language_data = ctx.language_data(&Language::Rust).unwrap().unwrap();
+        let file = |content: &str| {
+            ParsedSourceFile::new_with_content(
+                SourcePath::new_in("src/main.rs".into(), project_root),
+                content,
+                language_data.dupe(),
+            )
+            .unwrap()
+        };
+        for content in ["fn f() {}", "fn f() {}", "fn g() {}", "fn h() {}"] {
+            server.hover(&file(content), 0).unwrap();
+        }
+        assert_eq!(
+            *notifications.lock().unwrap(),
+            [
+                r#"textDocument/didOpen v0: "fn f() {}""#,
+                r#"textDocument/didChange v1: "fn g() {}""#,
+                r#"textDocument/didChange v2: "fn h() {}""#,
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_command() {
+        let err = LanguageServer::start(
+            Utf8Path::new("."),
+            Language::Rust,
+            &LanguageServerCommand::JustName("vex-no-such-language-server".into()),
+        )
+        .unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with("cannot run vex-no-such-language-server: "),
+            "unexpected error: {err}"
+        );
+
+        let err = LanguageServer::start(
+            Utf8Path::new("."),
+            Language::Rust,
+            &LanguageServerCommand::NameWithArgs(vec![]),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "rust language server failed: command is empty"
+        );
+    }
+}
@@ -0,0 +1,95 @@
+use std::ops;
+
+use lsp_types::{Position, Range};
+
+use crate::scriptlets::Location;
+
+/// Convert a byte range into an LSP range, whose columns count UTF-16 code units.
+pub fn range_in(text: &str, byte_range: &ops::Range<usize>) -> Range {
+    Range::new(
+        position_in(text, byte_range.start),
+        position_in(text, byte_range.end),
+    )
+}
+
+/// Convert a byte offset into an LSP position.
+pub fn position_in(text: &str, byte_offset: usize) -> Position {
+    let before = text.get(..byte_offset).unwrap_or(text);
+    let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+    Position::new(
+        before.matches('\n').count() as u32,
+        before[line_start..].encode_utf16().count() as u32,
+    )
+}
+
+/// Convert an LSP range into a location, whose rows are one-indexed and whose columns count
+/// bytes. If the text is unavailable, columns are assumed to be ASCII.
+pub fn location_in(text: Option<&str>, range: &Range) -> Location {
+    let column = |position: &Position| match text
+        .and_then(|text| text.lines().nth(position.line as usize))
+    {
+        Some(line) => byte_column(line, position.character),
+        None => position.character as usize,
+    };
+    Location {
+        start_row: range.start.line as usize + 1,
+        start_column: column(&range.start),
+        end_row: range.end.line as usize + 1,
+        end_column: column(&range.end),
+    }
+}
+
+fn byte_column(line: &str, character: u32) -> usize {
+    let mut utf16_len = 0;
+    line.char_indices()
+        .find(|(_, c)| {
+            let found = utf16_len >= character as usize;
+            utf16_len += c.len_utf16();
+            found
+        })
+        .map_or(line.len(), |(index, _)| index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn positions() {
+        let text = "ab\né😀x\n";
+        assert_eq!(position_in(text, 0), Position::new(0, 0));
+        assert_eq!(position_in(text, 2), Position::new(0, 2));
+        assert_eq!(position_in(text, 3), Position::new(1, 0));
+        assert_eq!(position_in(text, 5), Position::new(1, 1));
+        assert_eq!(position_in(text, 9), Position::new(1, 3));
+        assert_eq!(position_in(text, 1000), Position::new(2, 0));
+    }
+
+    #[test]
+    fn locations() {
+        let text = "ab\né😀x\n";
+        let range = Range::new(Position::new(1, 1), Position::new(1, 4));
+        assert_eq!(
+            location_in(Some(text), &range),
+            Location {
+                start_row: 2,
+                start_column: 2,
+                end_row: 2,
+                end_column: 7,
+            }
+        );
+        assert_eq!(
+            location_in(None, &range),
+            Location {
+                start_row: 2,
+                start_column: 1,
+                end_row: 2,
+                end_column: 4,
+            }
+        );
+
+        // Positions past the end of a line are clamped to it.
+        let range = Range::new(Position::new(0, 1), Position::new(0, 100));
+        assert_eq!(location_in(Some(text), &range).end_column, 2);
+    }
+}
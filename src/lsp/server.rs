@@ -0,0 +1,520 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, BufRead, Write},
+};
+
+use camino::Utf8PathBuf;
+use log::{info, log_enabled};
+use lsp_types::{
+    notification::{
+        DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, Exit, Notification,
+        PublishDiagnostics,
+    },
+    request::{Initialize, Request, Shutdown},
+    Diagnostic, DiagnosticRelatedInformation, DiagnosticSeverity, DidChangeTextDocumentParams,
+    DidCloseTextDocumentParams, DidOpenTextDocumentParams, InitializeResult, NumberOrString,
+    Position, PublishDiagnosticsParams, Range, ServerCapabilities, ServerInfo,
+    TextDocumentSyncCapability, TextDocumentSyncKind, Url,
+};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use starlark::values::FrozenHeap;
+
+use crate::{
+    context::Context,
+    error::Error,
+    irritation::{Irritation, IrritationSource},
+    lsp::{
+        position::range_in,
+        transport::{read_message, write_message},
+    },
+    result::Result,
+    scan,
+    scriptlets::{ScriptArgsValueMap, VexingStore},
+    severity::Severity,
+    source_file,
+    verbosity::Verbosity,
+    warning_filter::WarningFilter,
+};
+
+const STDIN: &str = "stdin";
+const STDOUT: &str = "stdout";
+
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+pub fn serve() -> Result<()> {
+    let ctx = Context::acquire()?;
+
+    // Scriptlet output would be interleaved with protocol messages on stdout, so is suppressed.
+    let verbosity = Verbosity::Quiet;
+    let script_args_heap = FrozenHeap::new();
+    let script_args = ScriptArgsValueMap::with_args(&ctx.script_args, &script_args_heap);
+    let store = crate::load_store(&ctx, &script_args, verbosity)?;
+    let warning_filter = crate::try_make_warning_filter(&ctx.manifest)?;
+
+    Server::new(&ctx, &store, &script_args, warning_filter)
+        .serve(&mut io::stdin().lock(), &mut io::stdout().lock())
+}
+
+/// A language server which checks documents as they are edited, using their in-memory content
+/// rather than that on disk.
+struct Server<'a> {
+    ctx: &'a Context,
+    store: &'a VexingStore,
+    script_args: &'a ScriptArgsValueMap,
+    warning_filter: WarningFilter,
+    documents: HashMap<Url, Document>,
+    shutting_down: bool,
+}
+
+#[derive(Debug)]
+struct Document {
+    version: i32,
+    text: String,
+}
+
+#[derive(Debug)]
+struct ResponseError {
+    code: i64,
+    message: String,
+}
+
+impl<'a> Server<'a> {
+    fn new(
+        ctx: &'a Context,
+        store: &'a VexingStore,
+        script_args: &'a ScriptArgsValueMap,
+        warning_filter: WarningFilter,
+    ) -> Self {
+        Self {
+            ctx,
+            store,
+            script_args,
+            warning_filter,
+            documents: HashMap::new(),
+            shutting_down: false,
+        }
+    }
+
+    fn serve(mut self, reader: &mut impl BufRead, writer: &mut impl Write) -> Result<()> {
+        while let Some(message) = read_message(reader, STDIN)? {
+            let Some(method) = message.get("method").and_then(Value::as_str) else {
+                // Responses to requests are ignored as none are sent.
+                continue;
+            };
+            if let Some(id) = message.get("id") {
+                let response = match self.handle_request(method) {
+                    Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                    Err(ResponseError { code, message }) => json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "error": { "code": code, "message": message },
+                    }),
+                };
+                write_message(writer, &response, STDOUT)?;
+                continue;
+            }
+
+            if method == Exit::METHOD {
+                return Ok(());
+            }
+            let params = message.get("params").cloned().unwrap_or(Value::Null);
+            if let Err(err) = self.handle_notification(method, params, writer) {
+                crate::error!("{err}");
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_request(&mut self, method: &str) -> std::result::Result<Value, ResponseError> {
+        if self.shutting_down {
+            return Err(ResponseError {
+                code: INVALID_REQUEST,
+                message: "server is shutting down".into(),
+            });
+        }
+
+        match method {
+            Initialize::METHOD => {
+                let result = InitializeResult {
+                    capabilities: ServerCapabilities {
+                        text_document_sync: Some(TextDocumentSyncCapability::Kind(
+                            TextDocumentSyncKind::FULL,
+                        )),
+                        ..Default::default()
+                    },
+                    server_info: Some(ServerInfo {
+                        name: env!("CARGO_PKG_NAME").into(),
+                        version: Some(env!("CARGO_PKG_VERSION").into()),
+                    }),
+                };
+                serde_json::to_value(result).map_err(|err| ResponseError {
+                    code: INVALID_PARAMS,
+                    message: err.to_string(),
+                })
+            }
+            Shutdown::METHOD => {
+                self.shutting_down = true;
+                Ok(Value::Null)
+            }
+            _ => Err(ResponseError {
+                code: METHOD_NOT_FOUND,
+                message: format!("unknown method '{method}'"),
+            }),
+        }
+    }
+
+    fn handle_notification(
+        &mut self,
+        method: &str,
+        params: Value,
+        writer: &mut impl Write,
+    ) -> Result<()> {
+        match method {
+            DidOpenTextDocument::METHOD => {
+                let DidOpenTextDocumentParams { text_document } = parse_params(method, params)?;
+                let document = Document {
+                    version: text_document.version,
+                    text: text_document.text,
+                };
+                self.documents.insert(text_document.uri.clone(), document);
+                self.publish_diagnostics(text_document.uri, writer)
+            }
+            DidChangeTextDocument::METHOD => {
+                let DidChangeTextDocumentParams {
+                    text_document,
+                    mut content_changes,
+                } = parse_params(method, params)?;
+                // Only full-document synchronisation is advertised, so the last change contains
+                // the entire new content.
+                let Some(change) = content_changes.pop() else {
+                    return Ok(());
+                };
+                let document = Document {
+                    version: text_document.version,
+                    text: change.text,
+                };
+                self.documents.insert(text_document.uri.clone(), document);
+                self.publish_diagnostics(text_document.uri, writer)
+            }
+            DidCloseTextDocument::METHOD => {
+                let DidCloseTextDocumentParams { text_document } = parse_params(method, params)?;
+                self.documents.remove(&text_document.uri);
+                let params = PublishDiagnosticsParams {
+                    uri: text_document.uri,
+                    diagnostics: vec![],
+                    version: None,
+                };
+                write_notification::<PublishDiagnostics>(writer, params)
+            }
+            _ => {
+                if log_enabled!(log::Level::Info) {
+                    info!("ignoring {method} notification");
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn publish_diagnostics(&self, uri: Url, writer: &mut impl Write) -> Result<()> {
+        let Some(diagnostics) = self.diagnostics(&uri)? else {
+            return Ok(());
+        };
+        let params = PublishDiagnosticsParams {
+            version: self.documents.get(&uri).map(|document| document.version),
+            uri,
+            diagnostics,
+        };
+        write_notification::<PublishDiagnostics>(writer, params)
+    }
+
+    /// Check an open document. Returns `None` if the document is not checked by vex or cannot
+    /// currently be parsed, in which case any previous diagnostics are left in place.
+    fn diagnostics(&self, uri: &Url) -> Result<Option<Vec<Diagnostic>>> {
+        let Some(document) = self.documents.get(uri) else {
+            return Ok(None);
+        };
+        let Some(path) = uri
+            .to_file_path()
+            .ok()
+            .and_then(|path| Utf8PathBuf::from_path_buf(path).ok())
+        else {
+            return Ok(None);
+        };
+        let Some(file) = source_file::source_with_content(self.ctx, &path, document.text.clone())?
+        else {
+            return Ok(None);
+        };
+
+        let scan = scan::scan_files(
+            self.ctx,
+            self.store,
+            &[file],
+            &self.warning_filter,
+            self.script_args,
+            Verbosity::Quiet,
+        );
+        let scan = match scan {
+            Ok(scan) => scan,
+            Err(err @ Error::UnparseableAsLanguage { .. }) => {
+                if log_enabled!(log::Level::Info) {
+                    info!("skipping {uri}: {err}");
+                }
+                return Ok(None);
+            }
+            Err(err) => return Err(err),
+        };
+        let diagnostics = scan
+            .file_irritations
+            .values()
+            .flatten()
+            .map(|irritation| self.diagnostic_for(irritation))
+            .collect();
+        Ok(Some(diagnostics))
+    }
+
+    fn diagnostic_for(&self, irritation: &Irritation) -> Diagnostic {
+        let range = irritation
+            .at()
+            .map(|(src, _)| self.location_of(src).range)
+            .unwrap_or_default();
+        let severity = match irritation.level() {
+            Severity::Note => DiagnosticSeverity::INFORMATION,
+            Severity::Warning => DiagnosticSeverity::WARNING,
+            Severity::Error => DiagnosticSeverity::ERROR,
+        };
+        let message = match irritation.info() {
+            Some(info) => format!("{}\n{info}", irritation.message()),
+            None => irritation.message().to_owned(),
+        };
+        let related_information = Some(
+            irritation
+                .show_also()
+                .iter()
+                .map(|(src, label)| DiagnosticRelatedInformation {
+                    location: self.location_of(src),
+                    message: label.clone(),
+                })
+                .collect::<Vec<_>>(),
+        )
+        .filter(|related_information| !related_information.is_empty());
+        Diagnostic {
+            range,
+            severity: Some(severity),
+            code: Some(NumberOrString::String(irritation.lint_id().to_string())),
+            source: Some(env!("CARGO_PKG_NAME").into()),
+            message,
+            related_information,
+            ..Default::default()
+        }
+    }
+
+    /// Find the location of the given source, preferring the content of open documents over
+    /// that on disk.
+    fn location_of(&self, src: &IrritationSource) -> lsp_types::Location {
+        let path = self.ctx.project_root.join(src.path().as_str());
+        let uri = Url::from_file_path(&path).expect("internal error: project path not absolute");
+        let range = match self.documents.get(&uri) {
+            Some(document) => range_in(&document.text, src.byte_range()),
+            None => match fs::read_to_string(&path) {
+                Ok(text) => range_in(&text, src.byte_range()),
+                Err(_) => {
+                    let location = src.location();
+                    Range::new(
+                        Position::new(
+                            location.start_row.saturating_sub(1) as u32,
+                            location.start_column as u32,
+                        ),
+                        Position::new(
+                            location.end_row.saturating_sub(1) as u32,
+                            location.end_column as u32,
+                        ),
+                    )
+                }
+            },
+        };
+        lsp_types::Location { uri, range }
+    }
+}
+
+fn parse_params<P: DeserializeOwned>(method: &str, params: Value) -> Result<P> {
+    serde_json::from_value(params)
+        .map_err(|err| Error::InvalidLspMessage(format!("bad {method} params: {err}")))
+}
+
+fn write_notification<N: Notification>(writer: &mut impl Write, params: N::Params) -> Result<()> {
+    let message = json!({
+        "jsonrpc": "2.0",
+        "method": N::METHOD,
+        "params": params,
+    });
+    write_message(writer, &message, STDOUT)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use camino::Utf8Path;
+    use indoc::indoc;
+
+    use crate::context::Manifest;
+
+    use super::*;
+
+    /// Send the given messages to a server in a new project and return its responses.
+    fn run_server(root: &Utf8Path, messages: &[Value]) -> Vec<Value> {
+        let ctx = Context::acquire_in(root).unwrap();
+        let script_args_heap = FrozenHeap::new();
+        let script_args = ScriptArgsValueMap::with_args(&ctx.script_args, &script_args_heap);
+        let store = crate::load_store(&ctx, &script_args, Verbosity::Quiet).unwrap();
+        let warning_filter = crate::try_make_warning_filter(&ctx.manifest).unwrap();
+
+        let mut input = vec![];
+        for message in messages {
+            write_message(&mut input, message, STDIN).unwrap();
+        }
+        let mut output = vec![];
+        Server::new(&ctx, &store, &script_args, warning_filter)
+            .serve(&mut Cursor::new(input), &mut output)
+            .unwrap();
+
+        let mut output = Cursor::new(output);
+        let mut responses = vec![];
+        while let Some(response) = read_message(&mut output, STDOUT).unwrap() {
+            responses.push(response);
+        }
+        responses
+    }
+
+    #[test]
+    fn diagnostics() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let root = Utf8PathBuf::try_from(tempdir.path().to_owned()).unwrap();
+        fs::write(root.join(Manifest::FILE_NAME), "[vex]\nversion = '1'\n").unwrap();
+        fs::create_dir(root.join("vexes")).unwrap();
+        fs::write(
+            root.join("vexes/test.star"),
+            indoc! {r#"
+                def init():
+                    vex.observe('open_project', on_open_project)
+
+                def on_open_project(event):
+                    vex.search(
+                        'rust',
+                        '(let_declaration pattern: (identifier) @name value: (integer_literal) @value)',
+                        on_match,
+                    )
+
+                def on_match(event):
+                    vex.warn(
+                        'magic-number',
+                        'magic number',
+                        at=(event.captures['value'], 'bound here'),
+                        show_also=[(event.captures['name'], 'to this')],
+                        info='use a named constant',
+                        level='error',
+                    )
+            "#},
+        )
+        .unwrap();
+
+        // The document is never written to disk, so must be checked from memory.
+        let uri = Url::from_file_path(root.join("src/main.rs")).unwrap();
+        let responses = run_server(
+            &root,
+            &[
+                json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": { "capabilities": {} } }),
+                json!({ "jsonrpc": "2.0", "method": "initialized", "params": {} }),
+                json!({
+                    "jsonrpc": "2.0",
+                    "method": "textDocument/didOpen",
+                    "params": {
+                        "textDocument": {
+                            "uri": uri,
+                            "languageId": "rust",
+                            "version": 1,
+                            "text": "fn main() {\n    let s = \"é😀\";\n    let x = 1;\n}\n",
+                        },
+                    },
+                }),
+                json!({ "jsonrpc": "2.0", "id": 2, "method": "vex/unknown" }),
+                json!({
+                    "jsonrpc": "2.0",
+                    "method": "textDocument/didChange",
+                    "params": {
+                        "textDocument": { "uri": uri, "version": 2 },
+                        "contentChanges": [{ "text": "fn main() {\n    let x = X;\n}\n" }],
+                    },
+                }),
+                json!({
+                    "jsonrpc": "2.0",
+                    "method": "textDocument/didClose",
+                    "params": { "textDocument": { "uri": uri } },
+                }),
+                json!({ "jsonrpc": "2.0", "id": 3, "method": "shutdown" }),
+                json!({ "jsonrpc": "2.0", "id": 4, "method": "vex/unknown" }),
+                json!({ "jsonrpc": "2.0", "method": "exit" }),
+                json!({ "jsonrpc": "2.0", "id": 5, "method": "shutdown" }),
+            ],
+        );
+
+        assert_eq!(responses.len(), 7, "{responses:#?}");
+        assert_eq!(responses[0]["id"], 1);
+        assert_eq!(
+            responses[0]["result"]["capabilities"]["textDocumentSync"],
+            1
+        );
+        assert_eq!(responses[0]["result"]["serverInfo"]["name"], "vex");
+        assert_eq!(
+            responses[1],
+            json!({
+                "jsonrpc": "2.0",
+                "method": "textDocument/publishDiagnostics",
+                "params": {
+                    "uri": uri,
+                    "version": 1,
+                    "diagnostics": [{
+                        "range": {
+                            "start": { "line": 2, "character": 12 },
+                            "end": { "line": 2, "character": 13 },
+                        },
+                        "severity": 1,
+                        "code": "magic-number",
+                        "source": "vex",
+                        "message": "magic number\nuse a named constant",
+                        "relatedInformation": [{
+                            "location": {
+                                "uri": uri,
+                                "range": {
+                                    "start": { "line": 2, "character": 8 },
+                                    "end": { "line": 2, "character": 9 },
+                                },
+                            },
+                            "message": "to this",
+                        }],
+                    }],
+                },
+            })
+        );
+        assert_eq!(responses[2]["id"], 2);
+        assert_eq!(responses[2]["error"]["code"], METHOD_NOT_FOUND);
+        assert_eq!(
+            responses[3]["params"],
+            json!({ "uri": uri, "version": 2, "diagnostics": [] })
+        );
+        assert_eq!(
+            responses[4]["params"],
+            json!({ "uri": uri, "diagnostics": [] })
+        );
+        assert_eq!(
+            responses[5],
+            json!({ "jsonrpc": "2.0", "id": 3, "result": null })
+        );
+        assert_eq!(responses[6]["id"], 4);
+        assert_eq!(responses[6]["error"]["code"], INVALID_REQUEST);
+    }
+}
@@ -0,0 +1,114 @@
+use std::io::{BufRead, Write};
+
+use serde_json::Value;
+
+use crate::{
+    error::{Error, IOAction},
+    result::Result,
+    source_path::PrettyPath,
+};
+
+/// Read a message from the named stream, returning `None` once it is exhausted.
+pub fn read_message(reader: &mut impl BufRead, name: &str) -> Result<Option<Value>> {
+    let io_error = |cause| Error::IO {
+        path: PrettyPath::from(name),
+        action: IOAction::Read,
+        cause,
+    };
+
+    let mut content_length = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).map_err(io_error)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end_matches(['\r', '\n']);
+        if line.is_empty() {
+            break;
+        }
+        let Some((name, value)) = line.split_once(':') else {
+            return Err(Error::InvalidLspMessage(format!(
+                "malformed header '{line}'"
+            )));
+        };
+        if name.eq_ignore_ascii_case("Content-Length") {
+            content_length = Some(value.trim().parse::<usize>()?);
+        }
+    }
+    let Some(content_length) = content_length else {
+        return Err(Error::InvalidLspMessage(
+            "missing Content-Length header".into(),
+        ));
+    };
+
+    let mut content = vec![0; content_length];
+    reader.read_exact(&mut content).map_err(io_error)?;
+    Ok(Some(serde_json::from_slice(&content)?))
+}
+
+/// Write a message to the named stream.
+pub fn write_message(writer: &mut impl Write, message: &Value, name: &str) -> Result<()> {
+    let content = serde_json::to_string(message)?;
+    write!(writer, "Content-Length: {}\r\n\r\n{content}", content.len())
+        .and_then(|_| writer.flush())
+        .map_err(|cause| Error::IO {
+            path: PrettyPath::from(name),
+            action: IOAction::Write,
+            cause,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let messages = [json!({ "id": 1 }), json!({ "method": "é" })];
+        let mut buf = vec![];
+        for message in &messages {
+            write_message(&mut buf, message, "test").unwrap();
+        }
+
+        let mut reader = Cursor::new(buf);
+        for message in &messages {
+            assert_eq!(
+                read_message(&mut reader, "test").unwrap().as_ref(),
+                Some(message)
+            );
+        }
+        assert_eq!(read_message(&mut reader, "test").unwrap(), None);
+    }
+
+    #[test]
+    fn malformed() {
+        let read = |raw: &str| read_message(&mut Cursor::new(raw.as_bytes()), "test");
+
+        assert_eq!(
+            read("Content-Length: 2\r\nContent-Type: application/json\r\n\r\n{}")
+                .unwrap()
+                .unwrap(),
+            json!({})
+        );
+        assert_eq!(
+            read("Content-Type: application/json\r\n\r\n{}")
+                .unwrap_err()
+                .to_string(),
+            "invalid language server message: missing Content-Length header"
+        );
+        assert_eq!(
+            read("garbage\r\n\r\n").unwrap_err().to_string(),
+            "invalid language server message: malformed header 'garbage'"
+        );
+        assert_eq!(
+            read("Content-Length: 10\r\n\r\n{}")
+                .unwrap_err()
+                .to_string(),
+            "cannot read test: failed to fill whole buffer"
+        );
+    }
+}
@@ -6,20 +6,31 @@ extern crate pretty_assertions;
 
 mod arena_map;
 mod associations;
+mod baseline;
+mod cache;
 mod cli;
 mod context;
 mod dump;
 mod error;
+mod expectation;
+mod fingerprint;
+mod fix;
+mod git;
 mod id;
 mod ignore_markers;
 mod irritation;
 mod language;
+mod list;
 mod logger;
+mod lsp;
+mod output_format;
 mod plural;
+mod profile;
 mod query;
 mod result;
 mod scan;
 mod scriptlets;
+mod severity;
 mod source_file;
 mod source_path;
 mod suggestion;
@@ -27,13 +38,19 @@ mod test;
 mod trigger;
 mod verbosity;
 mod warning_filter;
+mod watch;
 
 #[cfg(test)]
 mod vextest;
 
-use std::{env, process::ExitCode};
+use std::{
+    env,
+    fs::File,
+    io::{BufWriter, Write},
+    process::ExitCode,
+};
 
-use camino::Utf8PathBuf;
+use camino::{Utf8Path, Utf8PathBuf};
 use indoc::{formatdoc, printdoc};
 use log::{debug, info, log_enabled};
 use rayon::ThreadPoolBuilder;
@@ -41,14 +58,21 @@ use scriptlets::ScriptArgsValueMap;
 use starlark::values::FrozenHeap;
 
 use crate::{
+    baseline::Baseline,
+    cache::Cache,
     cli::{Args, CheckCmd, Command, InitCmd},
     context::{Context, Manifest, EXAMPLE_VEX_FILE},
     error::{Error, IOAction},
-    id::{GroupId, LintId},
+    fix::FixRunData,
+    git::{Diff, DiffBase},
+    id::{GroupId, Id, LintId},
     plural::Plural,
+    profile::Profiler,
     result::Result,
     scan::ProjectRunData,
-    scriptlets::{source, InitOptions, PreinitOptions, PreinitingStore},
+    scriptlets::{source, InitOptions, PreinitOptions, PreinitingStore, VexingStore},
+    severity::{Severity, SeverityCounts},
+    source_file::SourceFile,
     source_path::PrettyPath,
     verbosity::Verbosity,
     warning_filter::{ExclusionSet, WarningFilter},
@@ -75,7 +99,8 @@ fn run() -> Result<ExitCode> {
     };
     logger::init(verbosity)?;
 
-    if log_enabled!(log::Level::Info) {
+    // The language server communicates over stdout, so must not print anything else to it.
+    if log_enabled!(log::Level::Info) && args.command != Command::Lsp {
         print_banner();
     }
 
@@ -83,7 +108,9 @@ fn run() -> Result<ExitCode> {
         Command::Check(cmd_args) => check(cmd_args),
         Command::Dump(dump_args) => dump::dump(dump_args),
         Command::Init(init_args) => init(init_args),
-        Command::Test => test::test(),
+        Command::List => list::list(),
+        Command::Lsp => lsp::serve(),
+        Command::Test(cmd_args) => test::test(cmd_args),
     }?;
 
     Ok(logger::exit_code())
@@ -108,49 +135,95 @@ fn print_banner() {
 }
 
 fn check(cmd_args: CheckCmd) -> Result<()> {
-    let ctx = Context::acquire()?;
-    let verbosity = logger::verbosity();
-
-    let script_args_heap = FrozenHeap::new();
-    let script_args = ScriptArgsValueMap::with_args(&ctx.script_args, &script_args_heap);
-
-    let store = {
-        let preinit_opts = PreinitOptions {
-            script_args: &script_args,
-            verbosity,
-        };
-        let init_opts = InitOptions {
-            script_args: &script_args,
-            verbosity,
-        };
-        PreinitingStore::new(&source::sources_in_dir(&ctx.vex_dir())?)?
-            .preinit(&ctx, preinit_opts)?
-            .init(&ctx, init_opts)?
-    };
-
     // Configure global `rayon` thread pool.
     ThreadPoolBuilder::new()
         .num_threads(cmd_args.max_concurrent_files.into())
         .build_global()
         .expect("internal error: failed to configure global thread pool");
 
+    if cmd_args.watch {
+        return watch::watch(cmd_args);
+    }
+
+    let mut ctx = Context::acquire()?;
+    if cmd_args.profile {
+        ctx.enable_profiling(cmd_args.profile_trace.is_some());
+    }
+    let verbosity = logger::verbosity();
+
+    let script_args_heap = FrozenHeap::new();
+    let script_args = ScriptArgsValueMap::with_args(&ctx.script_args, &script_args_heap);
+
+    let store = load_store(&ctx, &script_args, verbosity)?;
+
+    let diff = match (&cmd_args.changed_since, cmd_args.staged) {
+        (Some(rev), _) => Some(Diff::new(&ctx.project_root, DiffBase::Revision(rev))?),
+        (None, true) => Some(Diff::new(&ctx.project_root, DiffBase::Index)?),
+        (None, false) => None,
+    };
+    let files = files_to_check(&ctx, &cmd_args, diff.as_ref())?;
     let warning_filter = try_make_warning_filter(&ctx.manifest)?;
+    // Files found in the cache are not scanned, so would be missing from a profile.
+    let cache = if cmd_args.no_cache || cmd_args.profile {
+        None
+    } else {
+        Some(Cache::load(&ctx, &store)?)
+    };
     let ProjectRunData {
-        irritations,
+        mut irritations,
         num_files_scanned,
         num_bytes_scanned,
     } = scan::scan_project(
         &ctx,
         &store,
+        files,
         warning_filter,
         cmd_args.max_problems,
-        cmd_args.max_concurrent_files,
         &script_args,
         verbosity,
+        cache.as_ref(),
     )?;
-    irritations
-        .iter()
-        .for_each(|irr| crate::warn!(custom=true; "{irr}"));
+    // Cached results are keyed on the content of their own file alone, so are not saved if
+    // vexes may have read others.
+    if let Some(cache) = cache.filter(|_| !ctx.project_files_read()) {
+        cache.save(&ctx)?;
+    }
+    if let Some(diff) = diff.as_ref().filter(|_| cmd_args.changed_lines_only) {
+        irritations.retain(|irr| diff.touches(irr));
+    }
+    if let Some(baseline_path) = &cmd_args.write_baseline {
+        Baseline::write(baseline_path, &irritations)?;
+        success!(
+            "wrote {} to {baseline_path}",
+            Plural::new(irritations.len(), "problem", "problems")
+        );
+        irritations.clear();
+    } else if let Some(baseline_path) = &cmd_args.baseline {
+        let num_irritations = irritations.len();
+        irritations = Baseline::load(baseline_path)?.filter(irritations);
+        info!(
+            "ignored {} found in baseline",
+            Plural::new(num_irritations - irritations.len(), "problem", "problems")
+        );
+    }
+    let mut problems = SeverityCounts::default();
+    problems.add(
+        Severity::Error,
+        *logger::NUM_ERRS.lock().expect("failed to lock NUM_ERRS") as usize,
+    );
+    problems.add(
+        Severity::Warning,
+        *logger::NUM_WARNINGS
+            .lock()
+            .expect("failed to lock NUM_WARNINGS") as usize,
+    );
+    let (irritations, num_fixed) = if cmd_args.fix || cmd_args.fix_dry_run {
+        let FixRunData { unfixed, num_fixed } = fix::fix(&ctx, irritations, cmd_args.fix_dry_run)?;
+        (unfixed, num_fixed)
+    } else {
+        (irritations, 0)
+    };
+    cmd_args.format.emit(&irritations)?;
 
     if log_enabled!(log::Level::Info) {
         info!(
@@ -173,21 +246,118 @@ fn check(cmd_args: CheckCmd) -> Result<()> {
         };
         debug!("scanned {} bytes", pretty_approx(num_bytes_scanned),);
     }
+    if let Some(profiler) = ctx.profiler() {
+        const PROFILE_TABLE_ROWS: usize = 20;
+        eprint!("{}", profiler.table(PROFILE_TABLE_ROWS));
+        if let Some(trace_path) = &cmd_args.profile_trace {
+            write_profile_trace(trace_path, profiler)?;
+            info!("wrote profile trace to {trace_path}");
+        }
+    }
 
-    let num_problems = irritations.len()
-        + *logger::NUM_ERRS.lock().expect("failed to lock NUM_ERRS") as usize
-        + *logger::NUM_WARNINGS
-            .lock()
-            .expect("failed to lock NUM_WARNINGS") as usize;
-    if num_problems != 0 {
-        crate::warn!("found {}", Plural::new(num_problems, "problem", "problems"));
-    } else {
-        success!("no problems found");
+    problems += SeverityCounts::of(&irritations);
+    if num_fixed != 0 {
+        let fixed = Plural::new(num_fixed, "problem", "problems");
+        if cmd_args.fix_dry_run {
+            info!("{fixed} can be fixed automatically");
+        } else {
+            success!("fixed {fixed}");
+        }
     }
+    print_summary(problems);
 
     Ok(())
 }
 
+fn write_profile_trace(path: &Utf8Path, profiler: &Profiler) -> Result<()> {
+    let io_error = |cause| Error::IO {
+        path: PrettyPath::new(path),
+        action: IOAction::Write,
+        cause,
+    };
+    let mut writer = BufWriter::new(File::create(path).map_err(io_error)?);
+    profiler
+        .write_trace(&mut writer)
+        .and_then(|()| writer.flush())
+        .map_err(io_error)
+}
+
+pub(crate) fn load_store(
+    ctx: &Context,
+    script_args: &ScriptArgsValueMap,
+    verbosity: Verbosity,
+) -> Result<VexingStore> {
+    let preinit_opts = PreinitOptions {
+        script_args,
+        verbosity,
+    };
+    let init_opts = InitOptions {
+        script_args,
+        verbosity,
+    };
+    PreinitingStore::new(&source::sources_in_dir(&ctx.vex_dir())?)?
+        .preinit(ctx, preinit_opts)?
+        .init(ctx, init_opts)
+}
+
+pub(crate) fn print_summary(problems: SeverityCounts) {
+    match problems.highest() {
+        Some(Severity::Error) => crate::lint_error!("found {problems}"),
+        Some(Severity::Warning) => crate::warn!("found {problems}"),
+        Some(Severity::Note) => crate::note!("found {problems}"),
+        None => success!("no problems found"),
+    }
+}
+
+pub(crate) fn files_to_check(
+    ctx: &Context,
+    cmd_args: &CheckCmd,
+    diff: Option<&Diff>,
+) -> Result<Vec<SourceFile>> {
+    if cmd_args.paths.is_empty() && diff.is_none() {
+        return source_file::sources_in_dir(ctx, cmd_args.max_concurrent_files);
+    }
+
+    let requested_paths = requested_paths(cmd_args)?;
+    let paths = match diff {
+        Some(diff) => diff
+            .changed_files()
+            .map(|path| ctx.project_root.join(path))
+            .filter(|path| {
+                requested_paths.is_empty()
+                    || requested_paths
+                        .iter()
+                        .any(|requested_path| path.starts_with(requested_path))
+            })
+            .collect(),
+        None => requested_paths,
+    };
+    let sources = source_file::sources_at(ctx, &paths, cmd_args.max_concurrent_files)?;
+    if !cmd_args.staged {
+        return Ok(sources);
+    }
+
+    // Changed lines are those of the index, so the staged content is checked rather than that in
+    // the worktree.
+    sources
+        .into_iter()
+        .map(|source| {
+            let content = git::staged_content(&ctx.project_root, &source.path().pretty_path)?;
+            Ok(source.with_content(content))
+        })
+        .collect()
+}
+
+/// The absolute paths which the user asked to be checked.
+pub(crate) fn requested_paths(cmd_args: &CheckCmd) -> Result<Vec<Utf8PathBuf>> {
+    let cwd = Utf8PathBuf::try_from(env::current_dir().map_err(|cause| Error::IO {
+        path: PrettyPath::from("."),
+        action: IOAction::Read,
+        cause,
+    })?)?;
+    Ok(cmd_args.paths.iter().map(|path| cwd.join(path)).collect())
+}
+
 pub(crate) fn try_make_warning_filter(manifest: &Manifest) -> Result<WarningFilter> {
     let inactive_lints: Vec<_> = manifest
         .lints
@@ -222,7 +392,14 @@ pub(crate) fn try_make_warning_filter(manifest: &Manifest) -> Result<WarningFilt
         .collect::<Result<_>>()?;
     let active_groups = ExclusionSet::from_excluded(inactive_groups);
 
-    Ok(WarningFilter::new(active_lints, active_groups))
+    let levels = manifest
+        .lints
+        .lint_levels_config
+        .iter()
+        .map(|(raw_id, level)| Ok((Id::try_from(raw_id.clone())?, *level)))
+        .collect::<Result<_>>()?;
+
+    Ok(WarningFilter::new(active_lints, active_groups, levels))
 }
 
 fn init(init_args: InitCmd) -> Result<()> {
@@ -0,0 +1,401 @@
+use std::{
+    borrow::Cow,
+    collections::BTreeSet,
+    fmt::Display,
+    io::{self, Write},
+};
+
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::{
+    error::{Error, IOAction},
+    id::LintId,
+    irritation::{Irritation, IrritationSource},
+    result::Result,
+    severity::Severity,
+    source_path::PrettyPath,
+};
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable snippets
+    #[default]
+    Pretty,
+
+    /// A single JSON array
+    Json,
+
+    /// One JSON object per line
+    Jsonl,
+
+    /// SARIF 2.1.0
+    Sarif,
+}
+
+impl OutputFormat {
+    pub fn emit(&self, irritations: &[Irritation]) -> Result<()> {
+        let io_error = |cause| Error::IO {
+            path: PrettyPath::from("<stdout>"),
+            action: IOAction::Write,
+            cause,
+        };
+
+        let mut stdout = io::stdout().lock();
+        match self {
+            Self::Pretty => {
+                irritations.iter().for_each(|irr| match irr.level() {
+                    // Notes are advisory so are shown without affecting the exit code.
+                    Severity::Note => log::warn!(custom=true; "{irr}"),
+                    Severity::Warning => crate::warn!(custom=true; "{irr}"),
+                    Severity::Error => crate::lint_error!(custom=true; "{irr}"),
+                });
+                return Ok(());
+            }
+            Self::Json => serde_json::to_writer_pretty(&mut stdout, irritations)?,
+            Self::Jsonl => {
+                for irritation in irritations {
+                    serde_json::to_writer(&mut stdout, irritation)?;
+                    writeln!(stdout).map_err(io_error)?;
+                }
+                return Ok(());
+            }
+            Self::Sarif => serde_json::to_writer_pretty(&mut stdout, &SarifLog::new(irritations))?,
+        }
+        writeln!(stdout).map_err(io_error)
+    }
+}
+
+impl Display for OutputFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.to_possible_value()
+            .expect("internal error: no skipped output formats")
+            .get_name()
+            .fmt(f)
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct SarifLog<'a> {
+    #[serde(rename = "$schema")]
+    schema: &'static str,
+    version: &'static str,
+    runs: [SarifRun<'a>; 1],
+}
+
+impl<'a> SarifLog<'a> {
+    const SCHEMA: &'static str = "https://json.schemastore.org/sarif-2.1.0.json";
+    const VERSION: &'static str = "2.1.0";
+
+    fn new(irritations: &'a [Irritation]) -> Self {
+        let rules = irritations
+            .iter()
+            .map(Irritation::lint_id)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .map(|id| SarifRule { id })
+            .collect();
+        let results = irritations.iter().map(SarifResult::new).collect();
+        Self {
+            schema: Self::SCHEMA,
+            version: Self::VERSION,
+            runs: [SarifRun {
+                tool: SarifTool {
+                    driver: SarifDriver {
+                        name: env!("CARGO_PKG_NAME"),
+                        version: env!("CARGO_PKG_VERSION"),
+                        information_uri: env!("CARGO_PKG_HOMEPAGE"),
+                        rules,
+                    },
+                },
+                results,
+            }],
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct SarifRun<'a> {
+    tool: SarifTool<'a>,
+    results: Vec<SarifResult<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+struct SarifTool<'a> {
+    driver: SarifDriver<'a>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifDriver<'a> {
+    name: &'static str,
+    version: &'static str,
+    information_uri: &'static str,
+    rules: Vec<SarifRule<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+struct SarifRule<'a> {
+    id: &'a LintId,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifResult<'a> {
+    rule_id: &'a LintId,
+    level: Severity,
+    message: SarifMessage<'a>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    locations: Vec<SarifLocation<'a>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    related_locations: Vec<SarifLocation<'a>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    properties: Option<SarifProperties<'a>>,
+}
+
+impl<'a> SarifResult<'a> {
+    fn new(irritation: &'a Irritation) -> Self {
+        let message = match irritation.info() {
+            Some(info) => SarifMessage::new(format!("{}\n{info}", irritation.message())),
+            None => SarifMessage::new(irritation.message().to_owned()),
+        };
+        let locations = irritation
+            .at()
+            .into_iter()
+            .map(|(src, label)| SarifLocation::new(None, src, label))
+            .collect();
+        let related_locations = irritation
+            .show_also()
+            .iter()
+            .enumerate()
+            .map(|(id, (src, label))| SarifLocation::new(Some(id), src, Some(label)))
+            .collect();
+        let properties = irritation.group_id().map(|group| SarifProperties {
+            group: group.as_str(),
+        });
+        Self {
+            rule_id: irritation.lint_id(),
+            level: irritation.level(),
+            message,
+            locations,
+            related_locations,
+            properties,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct SarifMessage<'a> {
+    text: Cow<'a, str>,
+}
+
+impl<'a> SarifMessage<'a> {
+    fn new(text: impl Into<Cow<'a, str>>) -> Self {
+        Self { text: text.into() }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifLocation<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<usize>,
+    physical_location: SarifPhysicalLocation<'a>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<SarifMessage<'a>>,
+}
+
+impl<'a> SarifLocation<'a> {
+    fn new(id: Option<usize>, src: &'a IrritationSource, label: Option<&'a str>) -> Self {
+        let region = (!src.is_whole_file()).then(|| {
+            let location = src.location();
+            let byte_range = src.byte_range();
+            SarifRegion {
+                start_line: location.start_row,
+                start_column: location.start_column + 1,
+                end_line: location.end_row,
+                end_column: location.end_column + 1,
+                byte_offset: byte_range.start,
+                byte_length: byte_range.len(),
+            }
+        });
+        Self {
+            id,
+            physical_location: SarifPhysicalLocation {
+                artifact_location: SarifArtifactLocation {
+                    uri: src.path().as_str(),
+                },
+                region,
+            },
+            message: label.map(SarifMessage::new),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifPhysicalLocation<'a> {
+    artifact_location: SarifArtifactLocation<'a>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    region: Option<SarifRegion>,
+}
+
+#[derive(Debug, Serialize)]
+struct SarifArtifactLocation<'a> {
+    uri: &'a str,
+}
+
+// SARIF columns are 1-based whereas tree-sitter columns are 0-based.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifRegion {
+    start_line: usize,
+    start_column: usize,
+    end_line: usize,
+    end_column: usize,
+    byte_offset: usize,
+    byte_length: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct SarifProperties<'a> {
+    group: &'a str,
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+    use serde_json::Value as JsonValue;
+
+    use crate::vextest::VexTest;
+
+    use super::*;
+
+    fn irritations() -> Vec<Irritation> {
+        VexTest::new("output-format")
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        vex.search(
+                            'rust',
+                            '(binary_expression left: (integer_literal) @l right: (integer_literal) @r) @bin_expr',
+                            on_match,
+                        )
+
+                    def on_match(event):
+                        captures = event.captures
+                        vex.warn(
+                            'test-id',
+                            'test message',
+                            at=(captures['bin_expr'], 'bin_expr'),
+                            show_also=[(captures['l'], 'l'), (captures['r'], 'r')],
+                            info='test info',
+                            group='test-group',
+                        )
+                "#},
+            )
+            .with_source_file(
+                "src/main.rs",
+                indoc! {r#"
+                    fn main() {
+                        let x = 1 + 2;
+                    }
+                "#},
+            )
+            .try_run()
+            .unwrap()
+            .irritations
+    }
+
+    #[test]
+    fn json() {
+        let irritations = irritations();
+        let json = serde_json::to_value(&irritations).unwrap();
+        let [irritation] = json.as_array().unwrap().as_slice() else {
+            panic!("expected exactly one irritation, got {json}");
+        };
+        assert_eq!(irritation["lint_id"], "test-id");
+        assert_eq!(irritation["group_id"], "test-group");
+        assert_eq!(irritation["message"], "test message");
+        assert_eq!(irritation["info"], "test info");
+        assert_eq!(irritation["at"][0]["path"], "src/main.rs");
+        assert_eq!(irritation["at"][0]["location"]["start_row"], 2);
+        assert_eq!(irritation["at"][1], "bin_expr");
+        assert_eq!(irritation["show_also"].as_array().unwrap().len(), 2);
+        assert!(irritation.get("rendered").is_none());
+        assert!(irritation["at"][0].get("fingerprint").is_none());
+    }
+
+    #[test]
+    fn sarif() {
+        let irritations = irritations();
+        let sarif = serde_json::to_value(SarifLog::new(&irritations)).unwrap();
+        assert_eq!(sarif["version"], "2.1.0");
+
+        let run = &sarif["runs"][0];
+        assert_eq!(run["tool"]["driver"]["name"], "vex");
+        assert_eq!(run["tool"]["driver"]["rules"][0]["id"], "test-id");
+
+        let [result] = run["results"].as_array().unwrap().as_slice() else {
+            panic!("expected exactly one result, got {run}");
+        };
+        assert_eq!(result["ruleId"], "test-id");
+        assert_eq!(result["level"], "warning");
+        assert_eq!(result["message"]["text"], "test message\ntest info");
+        assert_eq!(result["properties"]["group"], "test-group");
+
+        let location = &result["locations"][0];
+        assert_eq!(
+            location["physicalLocation"]["artifactLocation"]["uri"],
+            "src/main.rs"
+        );
+        assert_eq!(
+            location["physicalLocation"]["region"],
+            serde_json::json!({
+                "startLine": 2,
+                "startColumn": 13,
+                "endLine": 2,
+                "endColumn": 18,
+                "byteOffset": 24,
+                "byteLength": 5,
+            })
+        );
+        assert_eq!(location["message"]["text"], "bin_expr");
+
+        let related_locations = result["relatedLocations"].as_array().unwrap();
+        assert_eq!(
+            related_locations
+                .iter()
+                .map(|loc| loc["message"]["text"].clone())
+                .collect::<Vec<_>>(),
+            [JsonValue::from("l"), JsonValue::from("r")]
+        );
+    }
+
+    #[test]
+    fn sarif_whole_file() {
+        let irritations = VexTest::new("whole-file")
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.observe('open_file', on_open_file)
+
+                    def on_open_file(event):
+                        vex.warn('test-id', 'test message', at=(event.path, 'whole file'))
+                "#},
+            )
+            .with_source_file("src/main.rs", "fn main() {}")
+            .try_run()
+            .unwrap()
+            .irritations;
+        let sarif = serde_json::to_value(SarifLog::new(&irritations)).unwrap();
+        let physical_location = &sarif["runs"][0]["results"][0]["locations"][0]["physicalLocation"];
+        assert_eq!(physical_location["artifactLocation"]["uri"], "src/main.rs");
+        assert!(physical_location.get("region").is_none());
+    }
+}
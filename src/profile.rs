@@ -0,0 +1,247 @@
+use std::{
+    cell::Cell,
+    collections::BTreeMap,
+    fmt::{self, Display, Formatter},
+    io::{self, Write},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+
+/// The time spent in each scriptlet callback, query and file during a scan.
+#[derive(Debug)]
+pub struct Profiler {
+    start: Instant,
+    stats: Mutex<BTreeMap<(Subject, String), Stats>>,
+    trace: Option<Mutex<Vec<TraceEvent>>>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Subject {
+    Callback,
+    Query,
+    File,
+}
+
+impl Subject {
+    const ALL: [Self; 3] = [Self::Callback, Self::Query, Self::File];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Callback => "callback",
+            Self::Query => "query",
+            Self::File => "file",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct Stats {
+    calls: usize,
+    total: Duration,
+}
+
+impl Stats {
+    fn mean(&self) -> Duration {
+        self.total / self.calls.max(1) as u32
+    }
+}
+
+/// A complete event in the Chrome trace event format.
+#[derive(Debug, Serialize)]
+struct TraceEvent {
+    name: String,
+    cat: &'static str,
+    ph: &'static str,
+    ts: f64,
+    dur: f64,
+    pid: u32,
+    tid: usize,
+}
+
+impl Profiler {
+    /// Create a profiler, which also keeps each timed event for a trace if `trace` is set.
+    pub fn new(trace: bool) -> Self {
+        Self {
+            start: Instant::now(),
+            stats: Mutex::default(),
+            trace: trace.then(Mutex::default),
+        }
+    }
+
+    /// Run `f`, recording its wall time against the given subject.
+    pub fn time<T>(
+        &self,
+        subject: Subject,
+        name: impl FnOnce() -> String,
+        f: impl FnOnce() -> T,
+    ) -> T {
+        let start = Instant::now();
+        let ret = f();
+        self.record(subject, name(), start, start.elapsed());
+        ret
+    }
+
+    fn record(&self, subject: Subject, name: String, start: Instant, duration: Duration) {
+        if let Some(trace) = &self.trace {
+            trace.lock().expect("trace lock poisoned").push(TraceEvent {
+                name: name.clone(),
+                cat: subject.name(),
+                ph: "X",
+                ts: micros(start.saturating_duration_since(self.start)),
+                dur: micros(duration),
+                pid: 1,
+                tid: thread_id(),
+            });
+        }
+
+        let mut stats = self.stats.lock().expect("stats lock poisoned");
+        let stats = stats.entry((subject, name)).or_default();
+        stats.calls += 1;
+        stats.total += duration;
+    }
+
+    /// A table of the `limit` subjects of each kind which took the most time, slowest first.
+    /// Query times include those of the callbacks run on their matches, and file times include
+    /// those of their queries.
+    pub fn table(&self, limit: usize) -> ProfileTable<'_> {
+        ProfileTable {
+            profiler: self,
+            limit,
+        }
+    }
+
+    /// Write each timed event as a Chrome trace, viewable in `chrome://tracing` or Perfetto.
+    pub fn write_trace(&self, writer: &mut impl Write) -> io::Result<()> {
+        #[derive(Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Trace<'a> {
+            trace_events: &'a [TraceEvent],
+        }
+
+        let trace = self
+            .trace
+            .as_ref()
+            .expect("internal error: trace not recorded")
+            .lock()
+            .expect("trace lock poisoned");
+        serde_json::to_writer(
+            writer,
+            &Trace {
+                trace_events: &trace,
+            },
+        )?;
+        Ok(())
+    }
+}
+
+pub struct ProfileTable<'p> {
+    profiler: &'p Profiler,
+    limit: usize,
+}
+
+impl Display for ProfileTable<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let stats = self.profiler.stats.lock().expect("stats lock poisoned");
+        writeln!(
+            f,
+            "{:<8} {:>8} {:>10} {:>10}  name",
+            "kind", "calls", "total", "mean"
+        )?;
+        for subject in Subject::ALL {
+            let mut rows: Vec<_> = stats
+                .iter()
+                .filter(|((s, _), _)| *s == subject)
+                .map(|((_, name), stats)| (name, stats))
+                .collect();
+            rows.sort_by(|(name1, stats1), (name2, stats2)| {
+                stats2.total.cmp(&stats1.total).then(name1.cmp(name2))
+            });
+            for (name, stats) in rows.into_iter().take(self.limit) {
+                let total = format!("{:.1?}", stats.total);
+                let mean = format!("{:.1?}", stats.mean());
+                writeln!(
+                    f,
+                    "{:<8} {:>8} {total:>10} {mean:>10}  {name}",
+                    subject.name(),
+                    stats.calls,
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn micros(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1_000_000.0
+}
+
+fn thread_id() -> usize {
+    static NEXT_ID: AtomicUsize = AtomicUsize::new(1);
+    thread_local! {
+        static ID: Cell<usize> = const { Cell::new(0) };
+    }
+    ID.with(|id| {
+        if id.get() == 0 {
+            id.set(NEXT_ID.fetch_add(1, Ordering::Relaxed));
+        }
+        id.get()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use super::*;
+
+    #[test]
+    fn table() {
+        let profiler = Profiler::new(false);
+        let ms = Duration::from_millis;
+        for (subject, name, duration) in [
+            (Subject::File, "src/main.rs", ms(10)),
+            (Subject::Callback, "a.star:3 on_match", ms(1)),
+            (Subject::Callback, "b.star:5 on_match", ms(2)),
+            (Subject::Callback, "a.star:3 on_match", ms(3)),
+            (Subject::Callback, "c.star:1 on_open_file", ms(1)),
+            (Subject::Query, "rust: (integer_literal)", ms(6)),
+        ] {
+            profiler.record(subject, name.into(), Instant::now(), duration);
+        }
+
+        assert_eq!(
+            profiler.table(2).to_string(),
+            indoc! {"
+                kind        calls      total       mean  name
+                callback        2      4.0ms      2.0ms  a.star:3 on_match
+                callback        1      2.0ms      2.0ms  b.star:5 on_match
+                query           1      6.0ms      6.0ms  rust: (integer_literal)
+                file            1     10.0ms     10.0ms  src/main.rs
+            "}
+        );
+    }
+
+    #[test]
+    fn trace() {
+        let profiler = Profiler::new(true);
+        let ret = profiler.time(Subject::File, || "src/main.rs".into(), || 42);
+        assert_eq!(ret, 42);
+
+        let mut trace = Vec::new();
+        profiler.write_trace(&mut trace).unwrap();
+        let trace: serde_json::Value = serde_json::from_slice(&trace).unwrap();
+        let events = trace["traceEvents"].as_array().unwrap();
+        assert_eq!(events.len(), 1);
+        let event = &events[0];
+        assert_eq!(event["name"], "src/main.rs");
+        assert_eq!(event["cat"], "file");
+        assert_eq!(event["ph"], "X");
+        assert!(event["ts"].as_f64().unwrap() >= 0.0);
+        assert!(event["dur"].as_f64().unwrap() >= 0.0);
+    }
+}
@@ -5,7 +5,10 @@ use tree_sitter::{Language as TSLanguage, Query as TSQuery};
 use crate::{error::Error, result::Result, suggestion::suggest};
 
 #[derive(Debug)]
-pub struct Query(TSQuery);
+pub struct Query {
+    ts_query: TSQuery,
+    source: String,
+}
 
 impl Query {
     pub const KNOWN_OPERATORS: [&'static str; 8] = [
@@ -41,13 +44,13 @@ impl Query {
             return Err(Error::EmptyQuery);
         }
         let sanitised_query = format!("({query}\n)"); // TODO(kcza): remove me!
-        let query = TSQuery::new(ts_language, &sanitised_query)?;
-        if query.pattern_count() == 0 {
+        let ts_query = TSQuery::new(ts_language, &sanitised_query)?;
+        if ts_query.pattern_count() == 0 {
             return Err(Error::EmptyQuery);
         }
 
-        for pattern_index in 0..query.pattern_count() {
-            if let Some(predicate) = query.general_predicates(pattern_index).first() {
+        for pattern_index in 0..ts_query.pattern_count() {
+            if let Some(predicate) = ts_query.general_predicates(pattern_index).first() {
                 let operator = predicate.operator.to_string();
 
                 let operator_name = if operator.ends_with('?') || operator.ends_with('!') {
@@ -65,7 +68,27 @@ impl Query {
             }
         }
 
-        Ok(Self(query))
+        Ok(Self {
+            ts_query,
+            source: query.to_owned(),
+        })
+    }
+
+    /// The text from which this query was created.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// The source of this query on a single line, truncated if long.
+    pub fn summary(&self) -> String {
+        const MAX_LEN: usize = 60;
+
+        let source = self.source.split_whitespace().collect::<Vec<_>>().join(" ");
+        if source.chars().count() <= MAX_LEN {
+            return source;
+        }
+        let truncated: String = source.chars().take(MAX_LEN - 1).collect();
+        format!("{truncated}…")
     }
 }
 
@@ -73,6 +96,29 @@ impl Deref for Query {
     type Target = TSQuery;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.ts_query
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summary() {
+        let language = TSLanguage::from(tree_sitter_rust::LANGUAGE);
+        let query = Query::new(
+            &language,
+            "(binary_expression\n    left: (integer_literal))",
+        )
+        .unwrap();
+        assert_eq!(
+            query.summary(),
+            "(binary_expression left: (integer_literal))"
+        );
+
+        let long_source = "(integer_literal)".repeat(5);
+        let query = Query::new(&language, &long_source).unwrap();
+        assert_eq!(query.summary(), format!("{}…", &long_source[..59]));
     }
 }
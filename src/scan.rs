@@ -1,4 +1,5 @@
 use std::{
+    collections::BTreeMap,
     ops::Deref,
     sync::{
         atomic::{AtomicUsize, Ordering},
@@ -13,11 +14,13 @@ use starlark::values::FrozenHeap;
 use tree_sitter::QueryCursor;
 
 use crate::{
-    cli::{MaxConcurrentFileLimit, MaxProblems},
+    cache::Cache,
+    cli::MaxProblems,
     context::Context,
     error::Error,
     irritation::Irritation,
     language::Language,
+    profile::Subject,
     query::Query,
     result::Result,
     scriptlets::{
@@ -28,7 +31,8 @@ use crate::{
         query_captures::QueryCaptures,
         Observable, ObserveOptions, Observer, PrintHandler, ScriptArgsValueMap, VexingStore,
     },
-    source_file::{self, SourceFile},
+    source_file::SourceFile,
+    source_path::PrettyPath,
     verbosity::Verbosity,
     warning_filter::WarningFilter,
 };
@@ -40,57 +44,20 @@ pub struct ProjectRunData {
     pub num_bytes_scanned: u64,
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn scan_project(
     ctx: &Context,
     store: &VexingStore,
+    files: Vec<SourceFile>,
     warning_filter: WarningFilter,
     max_problems: MaxProblems,
-    max_concurrent_files: MaxConcurrentFileLimit,
     script_args: &ScriptArgsValueMap,
     verbosity: Verbosity,
+    cache: Option<&Cache>,
 ) -> Result<ProjectRunData> {
-    let files = source_file::sources_in_dir(ctx, max_concurrent_files)?;
-
     let lsp_enabled = ctx.manifest.run.lsp_enabled;
-
-    let mut irritations = vec![];
-    let frozen_heap = store.frozen_heap();
-    let project_queries = {
-        let mut project_queries = Vec::with_capacity(store.project_queries_hint());
-
-        let event = OpenProjectEvent::new(ctx.project_root.dupe());
-        let handler_module = HandlerModule::new();
-        let observe_opts = ObserveOptions {
-            action: Action::Vexing(event.kind()),
-            script_args,
-            warning_filter: Some(&warning_filter),
-            ignore_markers: None,
-            lsp_enabled,
-            print_handler: &PrintHandler::new(verbosity, event.kind().name()),
-        };
-        store.observers_for(event.kind()).observe(
-            ctx,
-            &handler_module,
-            handler_module.heap().alloc(event),
-            observe_opts,
-        )?;
-        handler_module
-            .into_intents_on(frozen_heap.deref())?
-            .into_iter()
-            .for_each(|intent| match intent {
-                Intent::Find {
-                    language,
-                    query,
-                    on_match,
-                } => project_queries.push((language, query, on_match)),
-                Intent::Observe { .. } => panic!("internal error: non-init observe"),
-                Intent::Warn(irr) => irritations.push(*irr),
-                Intent::ScanFile { .. } => {
-                    panic!("internal error: unexpected ScanFile intent declared")
-                }
-            });
-        project_queries
-    };
+    let (mut irritations, project_queries) =
+        open_project(ctx, store, &warning_filter, script_args, verbosity)?;
 
     let total_irritations = AtomicUsize::new(0);
     let runs: Vec<_> = files
@@ -114,7 +81,17 @@ pub fn scan_project(
                 script_args,
                 verbosity,
             };
-            scan_file(ctx, file, opts)
+            let scan = || {
+                ctx.profiled(
+                    Subject::File,
+                    || file.path().to_string(),
+                    || scan_file(ctx, file, opts),
+                )
+            };
+            match cache {
+                Some(cache) => cache.get_or_scan(file, language, scan),
+                None => scan(),
+            }
         })
         .take_any_while(|file_scan_result| {
             let run = match file_scan_result {
@@ -152,6 +129,101 @@ pub fn scan_project(
     })
 }
 
+/// The irritations found in a set of files, kept separate so that individual files can later be
+/// rescanned without rescanning the rest.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct FileScanData {
+    pub project_irritations: Vec<Irritation>,
+    pub file_irritations: BTreeMap<PrettyPath, Vec<Irritation>>,
+}
+
+/// Scan exactly the given files, reporting irritations per file.
+pub fn scan_files(
+    ctx: &Context,
+    store: &VexingStore,
+    files: &[SourceFile],
+    warning_filter: &WarningFilter,
+    script_args: &ScriptArgsValueMap,
+    verbosity: Verbosity,
+) -> Result<FileScanData> {
+    let lsp_enabled = ctx.manifest.run.lsp_enabled;
+    ctx.forget_project_files();
+    let (project_irritations, project_queries) =
+        open_project(ctx, store, warning_filter, script_args, verbosity)?;
+
+    let file_irritations = files
+        .par_iter()
+        .filter_map(|file| Some((file, file.language()?)))
+        .map(|(file, language)| {
+            let opts = VexFileOptions {
+                store,
+                language,
+                lsp_enabled,
+                project_queries: &project_queries,
+                warning_filter,
+                script_args,
+                verbosity,
+            };
+            let run = scan_file(ctx, file, opts)?;
+            Ok((file.path().pretty_path.dupe(), run.irritations))
+        })
+        .collect::<Result<_>>()?;
+
+    Ok(FileScanData {
+        project_irritations,
+        file_irritations,
+    })
+}
+
+pub type ProjectQuery = (Language, Arc<Query>, Observer);
+
+pub fn open_project(
+    ctx: &Context,
+    store: &VexingStore,
+    warning_filter: &WarningFilter,
+    script_args: &ScriptArgsValueMap,
+    verbosity: Verbosity,
+) -> Result<(Vec<Irritation>, Vec<ProjectQuery>)> {
+    let mut irritations = vec![];
+    let mut project_queries = Vec::with_capacity(store.project_queries_hint());
+
+    let frozen_heap = store.frozen_heap();
+    let event = OpenProjectEvent::new(ctx.project_root.dupe());
+    let handler_module = HandlerModule::new();
+    let observe_opts = ObserveOptions {
+        action: Action::Vexing(event.kind()),
+        file: None,
+        script_args,
+        warning_filter: Some(warning_filter),
+        ignore_markers: None,
+        lsp_enabled: ctx.manifest.run.lsp_enabled,
+        print_handler: &PrintHandler::new(verbosity, event.kind().name()),
+    };
+    store.observers_for(event.kind()).observe(
+        ctx,
+        &handler_module,
+        handler_module.heap().alloc(event),
+        observe_opts,
+    )?;
+    handler_module
+        .into_intents_on(frozen_heap.deref())?
+        .into_iter()
+        .for_each(|intent| match intent {
+            Intent::Find {
+                language,
+                query,
+                on_match,
+            } => project_queries.push((language, query, on_match)),
+            Intent::Observe { .. } => panic!("internal error: non-init observe"),
+            Intent::Warn(irr) => irritations.push(*irr),
+            Intent::DeclareLint(_) => panic!("internal error: non-init declare_lint"),
+            Intent::ScanFile { .. } => {
+                panic!("internal error: unexpected ScanFile intent declared")
+            }
+        });
+    Ok((irritations, project_queries))
+}
+
 #[derive(Debug, Default, PartialEq, Eq)]
 pub struct FileRunData {
     pub irritations: Vec<Irritation>,
@@ -162,7 +234,7 @@ pub struct VexFileOptions<'a> {
     store: &'a VexingStore,
     language: &'a Language,
     lsp_enabled: bool,
-    project_queries: &'a [(Language, Arc<Query>, Observer)],
+    project_queries: &'a [ProjectQuery],
     warning_filter: &'a WarningFilter,
     script_args: &'a ScriptArgsValueMap,
     verbosity: Verbosity,
@@ -190,6 +262,7 @@ fn scan_file(ctx: &Context, file: &SourceFile, opts: VexFileOptions<'_>) -> Resu
         let handler_module = HandlerModule::new();
         let observe_opts = ObserveOptions {
             action: Action::Vexing(event.kind()),
+            file: Some(&file.path().pretty_path),
             script_args,
             warning_filter: Some(warning_filter),
             ignore_markers: None,
@@ -213,6 +286,7 @@ fn scan_file(ctx: &Context, file: &SourceFile, opts: VexFileOptions<'_>) -> Resu
                 } => file_queries.push((language, query, on_match)),
                 Intent::Observe { .. } => panic!("internal error: non-init observe"),
                 Intent::Warn(irr) => irritations.push(*irr),
+                Intent::DeclareLint(_) => panic!("internal error: non-init declare_lint"),
                 Intent::ScanFile { .. } => {
                     panic!("internal error: unexpected ScanFile intent declared")
                 }
@@ -251,47 +325,58 @@ fn scan_file(ctx: &Context, file: &SourceFile, opts: VexFileOptions<'_>) -> Resu
         .chain(file_queries.iter())
         .filter(|(l, _, _)| l == language)
         .try_for_each(|(_, query, on_match)| {
-            QueryCursor::new()
-                .matches(
-                    query,
-                    parsed_file.tree.root_node(),
-                    parsed_file.content.as_bytes(),
-                )
-                .try_for_each(|qmatch| {
-                    let handler_module = HandlerModule::new();
-                    let event = {
-                        let path = parsed_file.path.pretty_path.dupe();
-                        let captures =
-                            QueryCaptures::new(query, qmatch, &parsed_file, handler_module.heap());
-                        handler_module.heap().alloc(MatchEvent::new(path, captures))
-                    };
-                    let observe_opts = ObserveOptions {
-                        action: Action::Vexing(EventKind::Match),
-                        script_args,
-                        warning_filter: Some(warning_filter),
-                        ignore_markers: Some(&ignore_markers),
-                        lsp_enabled,
-                        print_handler: &PrintHandler::new(verbosity, EventKind::Match.name()),
-                    };
-                    on_match.observe(ctx, &handler_module, event, observe_opts)?;
-                    handler_module
-                        .into_intents_on(&frozen_heap)?
-                        .into_iter()
-                        .for_each(|intent| match intent {
-                            Intent::Find { .. } => {
-                                panic!("internal error: find intended during find")
-                            }
-                            Intent::Observe { .. } => {
-                                panic!("internal error: non-init observe")
-                            }
-                            Intent::Warn(irr) => irritations.push(*irr),
-                            Intent::ScanFile { .. } => {
-                                panic!("internal error: unexpected ScanFile intent declared")
-                            }
-                        });
+            let query_name = || format!("{language}: {}", query.summary());
+            ctx.profiled(Subject::Query, query_name, || {
+                QueryCursor::new()
+                    .matches(
+                        query,
+                        parsed_file.tree.root_node(),
+                        parsed_file.content.as_bytes(),
+                    )
+                    .try_for_each(|qmatch| {
+                        let handler_module = HandlerModule::new();
+                        let event = {
+                            let path = parsed_file.path.pretty_path.dupe();
+                            let captures = QueryCaptures::new(
+                                query,
+                                qmatch,
+                                &parsed_file,
+                                handler_module.heap(),
+                            );
+                            handler_module.heap().alloc(MatchEvent::new(path, captures))
+                        };
+                        let observe_opts = ObserveOptions {
+                            action: Action::Vexing(EventKind::Match),
+                            file: Some(&parsed_file.path.pretty_path),
+                            script_args,
+                            warning_filter: Some(warning_filter),
+                            ignore_markers: Some(&ignore_markers),
+                            lsp_enabled,
+                            print_handler: &PrintHandler::new(verbosity, EventKind::Match.name()),
+                        };
+                        on_match.observe(ctx, &handler_module, event, observe_opts)?;
+                        handler_module
+                            .into_intents_on(&frozen_heap)?
+                            .into_iter()
+                            .for_each(|intent| match intent {
+                                Intent::Find { .. } => {
+                                    panic!("internal error: find intended during find")
+                                }
+                                Intent::Observe { .. } => {
+                                    panic!("internal error: non-init observe")
+                                }
+                                Intent::Warn(irr) => irritations.push(*irr),
+                                Intent::DeclareLint(_) => {
+                                    panic!("internal error: non-init declare_lint")
+                                }
+                                Intent::ScanFile { .. } => {
+                                    panic!("internal error: unexpected ScanFile intent declared")
+                                }
+                            });
 
-                    Result::Ok(())
-                })
+                        Result::Ok(())
+                    })
+            })
         })?;
     let num_bytes_scanned = parsed_file.content.len() as u64;
     Ok(FileRunData {
@@ -4,6 +4,8 @@ pub mod event;
 pub mod extra_data;
 pub mod handler_module;
 pub mod intents;
+mod limits;
+mod lint_declaration;
 mod lsp;
 pub mod main_annotation;
 mod node;
@@ -11,12 +13,15 @@ mod observers;
 mod print_handler;
 pub mod query_cache;
 pub mod query_captures;
+mod re_object;
 mod script_args_value_map;
 mod scriptlet;
 pub mod source;
 mod store;
 
 pub use self::intents::{Intent, Intents};
+pub use self::lint_declaration::LintDeclaration;
+pub use self::lsp::LspLocation;
 pub use self::node::{Location, Node, NodePrinter, WhitespaceStyle};
 pub use self::observers::{Observable, ObserveOptions, Observer, ObserverData};
 pub use self::print_handler::PrintHandler;
@@ -1,13 +1,14 @@
-use std::fmt::Display;
+use std::{fmt::Display, fs};
 
 use allocative::Allocative;
 use camino::Utf8Path;
 use derive_new::new;
-use dupe::Dupe;
+use dupe::{Dupe, OptionDupedExt};
 use starlark::{
     environment::{Methods, MethodsBuilder, MethodsStatic},
     eval::Evaluator,
     starlark_module,
+    typing::Ty,
     values::{
         list::UnpackList, none::NoneType, NoSerialize, ProvidesStaticType, StarlarkValue,
         StringValue, Value,
@@ -16,7 +17,8 @@ use starlark::{
 use starlark_derive::starlark_value;
 
 use crate::{
-    error::Error,
+    error::{Error, IOAction},
+    expectation::Expectation,
     id::{GroupId, Id, LintId},
     irritation::IrritationRenderer,
     language::Language,
@@ -28,10 +30,13 @@ use crate::{
         intents::UnfrozenIntent,
         lsp::Lsp,
         main_annotation::MainAnnotation,
-        observers::UnfrozenObserver,
-        Node,
+        observers::{ObserverOrigin, UnfrozenObserver},
+        LintDeclaration, Node,
     },
+    severity::Severity,
+    source_file::{self, SourceFile},
     source_path::PrettyPath,
+    test::TestConfig,
 };
 
 #[derive(Debug, PartialEq, Eq, new, ProvidesStaticType, NoSerialize, Allocative)]
@@ -53,7 +58,7 @@ impl AppObject {
 
             let ret_data = UnfrozenRetainedData::get_from(eval.module());
             let event_kind = event.parse()?;
-            let observer = UnfrozenObserver::new(observer);
+            let observer = UnfrozenObserver::new(observer, ObserverOrigin::of_caller(eval));
             ret_data.declare_intent(UnfrozenIntent::Observe {
                 event_kind,
                 observer,
@@ -62,6 +67,31 @@ impl AppObject {
             Ok(NoneType)
         }
 
+        fn declare_lint<'v>(
+            #[starlark(this)] _this: Value<'v>,
+            #[starlark(require=pos)] id: &'v str,
+            #[starlark(require=named)] group: Option<&'v str>,
+            #[starlark(require=named)] description: Option<&'v str>,
+            eval: &mut Evaluator<'v, '_>,
+        ) -> anyhow::Result<NoneType> {
+            AppObject::check_attr_available(eval, "vex.declare_lint", &[Action::Initing])?;
+
+            let id = LintId::try_from(id.to_owned())?;
+            let group = group
+                .map(ToOwned::to_owned)
+                .map(GroupId::try_from)
+                .transpose()?;
+            let description = description.map(ToOwned::to_owned);
+            let ret_data = UnfrozenRetainedData::get_from(eval.module());
+            ret_data.declare_intent(UnfrozenIntent::DeclareLint(LintDeclaration {
+                id,
+                group,
+                description,
+            }));
+
+            Ok(NoneType)
+        }
+
         fn active<'v>(
             #[starlark(this)] _this: Value<'v>,
             #[starlark(require=pos)] id: &'v str,
@@ -170,7 +200,7 @@ impl AppObject {
                 .language_data(&language)?
                 .ok_or_else(|| Error::NoParserForLanguage(language.dupe()))?
                 .get_or_create_query(&query)?;
-            let on_match = UnfrozenObserver::new(on_match);
+            let on_match = UnfrozenObserver::new(on_match, ObserverOrigin::of_caller(eval));
             ret_data.declare_intent(UnfrozenIntent::Find {
                 language,
                 query,
@@ -180,6 +210,116 @@ impl AppObject {
             Ok(NoneType)
         }
 
+        fn read_file<'v>(
+            #[starlark(this)] _this: Value<'v>,
+            #[starlark(require=pos)] path: &'v str,
+            eval: &mut Evaluator<'v, '_>,
+        ) -> anyhow::Result<String> {
+            AppObject::check_attr_available(
+                eval,
+                "vex.read_file",
+                &[
+                    Action::Vexing(EventKind::OpenProject),
+                    Action::Vexing(EventKind::OpenFile),
+                ],
+            )?;
+
+            let ctx = TempData::get_from(eval).ctx;
+            ctx.note_project_files_read();
+            let source_path = source_file::project_path(ctx, path)?;
+            let content =
+                fs::read_to_string(&*source_path.abs_path).map_err(|cause| Error::IO {
+                    path: source_path.pretty_path,
+                    action: IOAction::Read,
+                    cause,
+                })?;
+            Ok(content)
+        }
+
+        fn exists<'v>(
+            #[starlark(this)] _this: Value<'v>,
+            #[starlark(require=pos)] path: &'v str,
+            eval: &mut Evaluator<'v, '_>,
+        ) -> anyhow::Result<bool> {
+            AppObject::check_attr_available(
+                eval,
+                "vex.exists",
+                &[
+                    Action::Vexing(EventKind::OpenProject),
+                    Action::Vexing(EventKind::OpenFile),
+                ],
+            )?;
+
+            let ctx = TempData::get_from(eval).ctx;
+            ctx.note_project_files_read();
+            let source_path = match source_file::project_path(ctx, path) {
+                Ok(source_path) => source_path,
+                Err(Error::IgnoredPath(_)) => return Ok(false),
+                Err(err) => return Err(err.into()),
+            };
+            Ok(source_path.abs_path.exists())
+        }
+
+        fn glob<'v>(
+            #[starlark(this)] _this: Value<'v>,
+            #[starlark(require=pos)] pattern: &'v str,
+            eval: &mut Evaluator<'v, '_>,
+        ) -> anyhow::Result<Vec<PrettyPath>> {
+            AppObject::check_attr_available(
+                eval,
+                "vex.glob",
+                &[
+                    Action::Vexing(EventKind::OpenProject),
+                    Action::Vexing(EventKind::OpenFile),
+                ],
+            )?;
+
+            let ctx = TempData::get_from(eval).ctx;
+            ctx.note_project_files_read();
+            let paths = source_file::sources_matching(ctx, pattern)?
+                .into_iter()
+                .map(|source_path| source_path.pretty_path)
+                .collect();
+            Ok(paths)
+        }
+
+        fn parse<'v>(
+            #[starlark(this)] _this: Value<'v>,
+            #[starlark(require=pos)] path: &'v str,
+            #[starlark(require=named)] language: Option<&'v str>,
+            eval: &mut Evaluator<'v, '_>,
+        ) -> anyhow::Result<Node<'v>> {
+            AppObject::check_attr_available(
+                eval,
+                "vex.parse",
+                &[
+                    Action::Vexing(EventKind::OpenProject),
+                    Action::Vexing(EventKind::OpenFile),
+                ],
+            )?;
+
+            let ctx = TempData::get_from(eval).ctx;
+            ctx.note_project_files_read();
+            let source_path = source_file::project_path(ctx, path)?;
+            let language = match language {
+                Some(language) => Some(language.parse::<Language>()?),
+                None => ctx.associations()?.get_language(&source_path)?.duped(),
+            };
+            let language =
+                language.ok_or_else(|| Error::NoParserForFile(source_path.pretty_path.dupe()))?;
+            let parsed_file =
+                ctx.parsed_project_file(&source_path.pretty_path, &language, || {
+                    SourceFile::new(source_path.dupe(), Some(language.dupe())).parse(ctx)
+                })?;
+
+            // Nodes borrow their file, so a handle to it is kept for as long as the vex.
+            let parsed_file = eval
+                .frozen_heap()
+                .alloc_any_display_from_debug(parsed_file)
+                .as_ref();
+            Ok(Node::new(parsed_file.tree.root_node(), parsed_file))
+        }
+
         #[allow(clippy::too_many_arguments)]
         fn warn<'v>(
             #[starlark(this)] _this: Value<'v>,
@@ -189,6 +329,8 @@ impl AppObject {
             #[starlark(require=named)] show_also: Option<UnpackList<(Node<'v>, &'v str)>>,
             #[starlark(require=named)] info: Option<&'v str>,
             #[starlark(require=named)] group: Option<&'v str>,
+            #[starlark(require=named)] fix: Option<UnpackList<(Node<'v>, &'v str)>>,
+            #[starlark(require=named)] level: Option<&'v str>,
             eval: &mut Evaluator<'v, '_>,
         ) -> anyhow::Result<NoneType> {
             AppObject::check_attr_available(
@@ -212,11 +354,21 @@ impl AppObject {
                 )
                 .into());
             }
+            if fix.is_some() && at.as_ref().and_then(|at| at.node()).is_none() {
+                return Err(Error::InvalidWarnCall(
+                    "cannot apply `fix` without an `at` argument containing a Node",
+                )
+                .into());
+            }
 
             let lint_id = LintId::try_from(lint_id.to_owned())?;
             let group_id = group
                 .map(|group| GroupId::try_from(group.to_owned()))
                 .transpose()?;
+            let level = level
+                .map(str::parse::<Severity>)
+                .transpose()?
+                .unwrap_or_default();
 
             let temp_data = TempData::get_from(eval);
             let ignored = at.as_ref().and_then(|at| at.node()).is_some_and(|node| {
@@ -228,8 +380,13 @@ impl AppObject {
                 return Ok(NoneType);
             }
 
+            let level = temp_data.warning_filter.map_or(level, |warning_filter| {
+                warning_filter.level_of(&lint_id, group_id.as_ref(), level)
+            });
+
             let ret_data = UnfrozenRetainedData::get_from(eval.module());
             let mut irritation_renderer = IrritationRenderer::new(lint_id, message);
+            irritation_renderer.set_level(level);
             if let Some(group_id) = group_id {
                 irritation_renderer.set_group_id(group_id);
             }
@@ -242,16 +399,24 @@ impl AppObject {
             if let Some(info) = info {
                 irritation_renderer.set_info(info);
             }
+            if let Some(fix) = fix {
+                irritation_renderer.set_fix(fix.items);
+            }
             ret_data.declare_intent(UnfrozenIntent::Warn(Box::new(irritation_renderer.render())));
 
             Ok(NoneType)
         }
 
+        #[allow(clippy::too_many_arguments)]
         fn scan<'v>(
             #[starlark(this)] _this: Value<'v>,
             #[starlark(require=pos)] file_name: &'v str,
             #[starlark(require=pos)] language: &'v str,
             #[starlark(require=pos)] content: &'v str,
+            #[starlark(require=named)] expect: Option<UnpackList<Value<'v>>>,
+            #[starlark(require=named)] args: Option<Value<'v>>,
+            #[starlark(require=named)] lints: Option<Value<'v>>,
+            #[starlark(require=named)] groups: Option<Value<'v>>,
             eval: &mut Evaluator<'_, '_>,
         ) -> anyhow::Result<NoneType> {
             AppObject::check_attr_available(
@@ -263,11 +428,23 @@ impl AppObject {
             let file_name = PrettyPath::new(Utf8Path::new(file_name));
             let language = language.parse()?;
             let content = textwrap::dedent(content.strip_prefix('\n').unwrap_or(content));
+            let expectations = expect
+                .map(|expect| {
+                    expect
+                        .items
+                        .into_iter()
+                        .map(Expectation::from_value)
+                        .collect::<Result<Vec<_>>>()
+                })
+                .transpose()?;
+            let config = TestConfig::from_values(args, lints, groups)?;
             let ret_data = UnfrozenRetainedData::get_from(eval.module());
             ret_data.declare_intent(UnfrozenIntent::ScanFile {
                 file_name,
                 language,
                 content,
+                expectations,
+                config,
             });
             Ok(NoneType)
         }
@@ -296,6 +473,12 @@ impl<'v> StarlarkValue<'v> for AppObject {
         static RES: MethodsStatic = MethodsStatic::new();
         RES.methods(AppObject::methods)
     }
+
+    fn typechecker_ty(&self) -> Option<Ty> {
+        // The typechecker cannot name positional-only parameters, so leave calls to be checked
+        // at runtime, where arity errors are clearer and come with a traceback.
+        Some(Ty::any())
+    }
 }
 
 impl Display for AppObject {
@@ -309,7 +492,7 @@ mod tests {
     use indoc::{formatdoc, indoc};
     use insta::assert_yaml_snapshot;
 
-    use crate::vextest::VexTest;
+    use crate::{severity::Severity, vextest::VexTest};
 
     #[test]
     fn attrs() {
@@ -321,8 +504,13 @@ mod tests {
                         expected_attrs = [
                             'active',
                             'args_for',
+                            'declare_lint',
+                            'exists',
+                            'glob',
                             'lsp_for',
                             'observe',
+                            'parse',
+                            'read_file',
                             'scan',
                             'search',
                             'warn'
@@ -674,6 +862,69 @@ mod tests {
         assert_yaml_snapshot!(irritations);
     }
 
+    #[test]
+    fn warn_levels() {
+        let irritations = VexTest::new("levels")
+            .with_manifest(indoc! {r#"
+                [vex]
+                version = "1"
+
+                [lints.level]
+                overridden-lint = "note"
+                overridden-group = "error"
+            "#})
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        vex.warn('default-level', 'message')
+                        vex.warn('note-level', 'message', level='note')
+                        vex.warn('error-level', 'message', level='error')
+                        vex.warn('overridden-lint', 'message', level='error', group='overridden-group')
+                        vex.warn('in-group', 'message', level='note', group='overridden-group')
+                "#},
+            )
+            .try_run()
+            .unwrap()
+            .irritations;
+        let levels: Vec<_> = irritations
+            .iter()
+            .map(|irr| (irr.lint_id().as_str(), irr.level()))
+            .collect();
+        assert_eq!(
+            levels,
+            [
+                ("default-level", Severity::Warning),
+                ("error-level", Severity::Error),
+                ("in-group", Severity::Error),
+                ("note-level", Severity::Note),
+                ("overridden-lint", Severity::Note),
+            ]
+        );
+        assert!(irritations
+            .iter()
+            .find(|irr| irr.lint_id().as_str() == "note-level")
+            .unwrap()
+            .to_string()
+            .starts_with("note[note-level]: message"));
+
+        VexTest::new("unknown-level")
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        vex.warn('some-lint', 'message', level='fatal')
+                "#},
+            )
+            .returns_error("unknown level 'fatal'");
+    }
+
     #[test]
     fn args_for() {
         const ID: &str = "some-id";
@@ -779,4 +1030,206 @@ mod tests {
             )
             .returns_error("Immutable");
     }
+
+    #[test]
+    fn read_file() {
+        VexTest::new("read-file")
+            .with_scriptlet(
+                "vexes/test.star",
+                formatdoc! {r#"
+                        load('{check_path}', 'check')
+
+                        def init():
+                            vex.observe('open_project', on_open_project)
+                            vex.observe('open_file', on_open_file)
+
+                        def on_open_project(event):
+                            check['eq'](vex.read_file('data/config.txt'), 'some config\n')
+                            check['eq'](vex.read_file('./data/config.txt'), 'some config\n')
+
+                        def on_open_file(event):
+                            check['eq'](vex.read_file(str(event.path)), 'fn main() {{}}\n')
+                    "#,
+                    check_path = VexTest::CHECK_STARLARK_PATH,
+                },
+            )
+            .with_source_file("data/config.txt", "some config\n")
+            .with_source_file("src/main.rs", "fn main() {}\n")
+            .assert_irritation_free();
+        VexTest::new("missing")
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        vex.read_file('nonexistent.txt')
+                "#},
+            )
+            .returns_error("cannot read nonexistent.txt");
+        VexTest::new("ignored")
+            .with_manifest(indoc! {r#"
+                [vex]
+                version = "1"
+
+                [files]
+                ignore = ["secrets/"]
+            "#})
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        vex.read_file('secrets/key.txt')
+                "#},
+            )
+            .with_source_file("secrets/key.txt", "hunter2\n")
+            .returns_error("cannot access secrets/key.txt: path is hidden or ignored");
+    }
+
+    #[test]
+    fn exists() {
+        VexTest::new("exists")
+            .with_scriptlet(
+                "vexes/test.star",
+                formatdoc! {r#"
+                        load('{check_path}', 'check')
+
+                        def init():
+                            vex.observe('open_project', on_open_project)
+
+                        def on_open_project(event):
+                            check['true'](vex.exists('src/main.rs'))
+                            check['true'](vex.exists('src'))
+                            check['false'](vex.exists('src/lib.rs'))
+                            check['false'](vex.exists('.hidden.rs'))
+                    "#,
+                    check_path = VexTest::CHECK_STARLARK_PATH,
+                },
+            )
+            .with_source_file("src/main.rs", "fn main() {}\n")
+            .with_source_file(".hidden.rs", "fn hidden() {}\n")
+            .assert_irritation_free();
+    }
+
+    #[test]
+    fn glob() {
+        VexTest::new("glob")
+            .with_manifest(indoc! {r#"
+                [vex]
+                version = "1"
+
+                [files]
+                ignore = ["src/generated"]
+            "#})
+            .with_scriptlet(
+                "vexes/test.star",
+                formatdoc! {r#"
+                        load('{check_path}', 'check')
+
+                        def init():
+                            vex.observe('open_project', on_open_project)
+
+                        def on_open_project(event):
+                            check['eq'](
+                                [str(path) for path in vex.glob('src/*.rs')],
+                                ['src/lib.rs', 'src/main.rs'],
+                            )
+                            check['eq'](
+                                [str(path) for path in vex.glob('**/*.rs')],
+                                ['src/lib.rs', 'src/main.rs', 'src/nested/mod.rs'],
+                            )
+                            check['eq'](vex.glob('*.py'), [])
+                    "#,
+                    check_path = VexTest::CHECK_STARLARK_PATH,
+                },
+            )
+            .with_source_file("src/main.rs", "fn main() {}\n")
+            .with_source_file("src/lib.rs", "")
+            .with_source_file("src/nested/mod.rs", "")
+            .with_source_file("src/generated/mod.rs", "")
+            .with_source_file(".hidden/mod.rs", "")
+            .assert_irritation_free();
+        VexTest::new("invalid-pattern")
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        vex.glob('src/[')
+                "#},
+            )
+            .returns_error(r#"cannot compile "src/\[""#);
+    }
+
+    #[test]
+    fn parse() {
+        VexTest::new("parse")
+            .with_scriptlet(
+                "vexes/test.star",
+                formatdoc! {r#"
+                        load('{check_path}', 'check')
+
+                        def init():
+                            vex.observe('open_project', on_open_project)
+
+                        def on_open_project(event):
+                            root = vex.parse('src/lib.rs')
+                            check['eq'](root.kind, 'source_file')
+                            check['eq'](str(root), 'pub const MAX: usize = 10;\n')
+                            lits = root.descendants(kind='integer_literal')
+                            check['eq']([str(lit) for lit in lits], ['10'])
+
+                            root = vex.parse('config/settings.txt', language='rust')
+                            check['eq'](root.kind, 'source_file')
+                    "#,
+                    check_path = VexTest::CHECK_STARLARK_PATH,
+                },
+            )
+            .with_source_file("src/lib.rs", "pub const MAX: usize = 10;\n")
+            .with_source_file("config/settings.txt", "const X: u8 = 1;\n")
+            .assert_irritation_free();
+        VexTest::new("unknown-language")
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        vex.parse('notes.txt')
+                "#},
+            )
+            .with_source_file("notes.txt", "hello\n")
+            .returns_error("cannot discern language of notes.txt");
+    }
+
+    #[test]
+    fn project_confinement() {
+        for call in [
+            "vex.read_file('../secret.txt')",
+            "vex.read_file('src/../../secret.txt')",
+            "vex.read_file('/etc/passwd')",
+            "vex.exists('/etc/passwd')",
+            "vex.parse('../main.rs')",
+        ] {
+            VexTest::new(call)
+                .with_scriptlet(
+                    "vexes/test.star",
+                    formatdoc! {r#"
+                        def init():
+                            vex.observe('open_project', on_open_project)
+
+                        def on_open_project(event):
+                            {call}
+                    "#},
+                )
+                .returns_error("path is outside the project");
+        }
+    }
 }
@@ -9,10 +9,12 @@ use starlark_derive::{starlark_value, NoSerialize, ProvidesStaticType, Trace};
 
 use crate::source_path::PrettyPath;
 use crate::{
+    expectation::Expectation,
     irritation::Irritation,
     language::Language,
     query::Query,
-    scriptlets::{event::EventKind, observers::UnfrozenObserver, Observer},
+    scriptlets::{event::EventKind, observers::UnfrozenObserver, LintDeclaration, Observer},
+    test::TestConfig,
 };
 
 #[derive(Debug, Display, ProvidesStaticType, NoSerialize, Allocative, Trace)]
@@ -83,10 +85,13 @@ pub enum UnfrozenIntent<'v> {
         observer: UnfrozenObserver<'v>,
     },
     Warn(Box<Irritation>),
+    DeclareLint(LintDeclaration),
     ScanFile {
         file_name: PrettyPath,
         language: Language,
         content: String,
+        expectations: Option<Vec<Expectation>>,
+        config: TestConfig,
     },
 }
 
@@ -118,14 +123,19 @@ impl<'v> Freeze for UnfrozenIntent<'v> {
                 }
             }
             Self::Warn(irr) => Intent::Warn(irr),
+            Self::DeclareLint(declaration) => Intent::DeclareLint(declaration),
             Self::ScanFile {
                 file_name,
                 language,
                 content,
+                expectations,
+                config,
             } => Intent::ScanFile {
                 file_name,
                 language,
                 content,
+                expectations,
+                config,
             },
         })
     }
@@ -181,9 +191,12 @@ pub enum Intent {
         observer: Observer,
     },
     Warn(Box<Irritation>),
+    DeclareLint(LintDeclaration),
     ScanFile {
         file_name: PrettyPath,
         language: Language,
         content: String,
+        expectations: Option<Vec<Expectation>>,
+        config: TestConfig,
     },
 }
@@ -0,0 +1,264 @@
+use std::{
+    panic::{self, AssertUnwindSafe},
+    time::{Duration, Instant},
+};
+
+use starlark::{
+    codemap::FileSpanRef,
+    eval::{BeforeStmtFuncDyn, Evaluator},
+};
+
+use crate::{context::RunConfig, error::CallbackLimit};
+
+/// The resources which a single callback may use.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CallbackLimits {
+    max_steps: Option<u64>,
+    max_time: Option<Duration>,
+    max_heap_mib: Option<u64>,
+}
+
+impl CallbackLimits {
+    pub fn new(run_config: &RunConfig) -> Self {
+        Self {
+            max_steps: run_config.max_callback_steps,
+            max_time: run_config.max_callback_seconds.map(Duration::from_secs_f64),
+            max_heap_mib: run_config.max_callback_heap_mib,
+        }
+    }
+
+    fn is_unlimited(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Run `f`, which calls a callback using `eval`, stopping the callback if it exceeds any
+    /// limit.
+    ///
+    /// Limits are checked before each statement, which is the finest granularity at which
+    /// Starlark can be stopped. A single expression, such as a large comprehension, cannot be
+    /// interrupted, so its time and heap use are checked once it completes and steps are not
+    /// counted within it.
+    pub fn enforce<'v, 'a, T>(
+        &self,
+        eval: &mut Evaluator<'v, 'a>,
+        f: impl FnOnce(&mut Evaluator<'v, 'a>) -> T,
+    ) -> Result<T, CallbackLimit> {
+        if self.is_unlimited() {
+            return Ok(f(eval));
+        }
+
+        // Starlark cannot be interrupted with an error between statements, so the check unwinds
+        // out of the evaluator instead. Unwinding is resumed rather than started with a panic to
+        // avoid the panic hook reporting a crash.
+        let start = Instant::now();
+        let check: Box<dyn BeforeStmtFuncDyn<'a>> = Box::new(LimitCheck {
+            limits: *self,
+            steps: 0,
+            start,
+        });
+        eval.before_stmt_for_dap(check.into());
+        let ret =
+            panic::catch_unwind(AssertUnwindSafe(|| f(eval))).map_err(|payload| {
+                match payload.downcast::<CallbackLimit>() {
+                    Ok(exceeded) => *exceeded,
+                    Err(payload) => panic::resume_unwind(payload),
+                }
+            })?;
+        if let Some(exceeded) = self.resources_exceeded(start, eval) {
+            return Err(exceeded);
+        }
+        Ok(ret)
+    }
+
+    fn resources_exceeded(
+        &self,
+        start: Instant,
+        eval: &Evaluator<'_, '_>,
+    ) -> Option<CallbackLimit> {
+        if let Some(max_time) = self.max_time.filter(|max_time| start.elapsed() > *max_time) {
+            return Some(CallbackLimit::Time(max_time));
+        }
+        if let Some(max_heap_mib) = self
+            .max_heap_mib
+            .filter(|max_heap_mib| eval.heap().allocated_bytes() as u64 > max_heap_mib << 20)
+        {
+            return Some(CallbackLimit::HeapMib(max_heap_mib));
+        }
+        None
+    }
+}
+
+struct LimitCheck {
+    limits: CallbackLimits,
+    steps: u64,
+    start: Instant,
+}
+
+impl LimitCheck {
+    fn exceeded(&self, eval: &Evaluator<'_, '_>) -> Option<CallbackLimit> {
+        if let Some(max_steps) = self
+            .limits
+            .max_steps
+            .filter(|max_steps| self.steps > *max_steps)
+        {
+            return Some(CallbackLimit::Steps(max_steps));
+        }
+        self.limits.resources_exceeded(self.start, eval)
+    }
+}
+
+impl<'a> BeforeStmtFuncDyn<'a> for LimitCheck {
+    fn call<'v>(&mut self, _span: FileSpanRef, eval: &mut Evaluator<'v, 'a>) {
+        self.steps += 1;
+        if let Some(exceeded) = self.exceeded(eval) {
+            panic::resume_unwind(Box::new(exceeded));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::{formatdoc, indoc};
+
+    use crate::vextest::VexTest;
+
+    fn manifest(limit: &str) -> String {
+        formatdoc! {r#"
+            [vex]
+            version = "1"
+            {limit}
+        "#}
+    }
+
+    #[test]
+    fn steps() {
+        VexTest::new("within-limit")
+            .with_manifest(manifest("max-callback-steps = 100"))
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        for i in range(10):
+                            x = i
+                "},
+            )
+            .assert_irritation_free();
+
+        VexTest::new("exceeded")
+            .with_manifest(manifest("max-callback-steps = 100"))
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        for i in range(1000):
+                            x = i
+                "},
+            )
+            .returns_error(
+                "test.star:2 on_open_project exceeded limit of 100 steps while handling open_project event$",
+            );
+    }
+
+    #[test]
+    fn time() {
+        VexTest::new("time")
+            .with_manifest(manifest("max-callback-seconds = 0.01"))
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        for i in range(1 << 30):
+                            x = i
+                "},
+            )
+            .returns_error(
+                "on_open_project exceeded limit of 10(.0)?ms while handling open_project event",
+            );
+    }
+
+    #[test]
+    fn heap() {
+        VexTest::new("heap")
+            .with_manifest(manifest("max-callback-heap-mib = 1"))
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        parts = []
+                        for i in range(1 << 20):
+                            parts.append(str(i))
+                "},
+            )
+            .returns_error("on_open_project exceeded limit of 1 MiB of heap");
+    }
+
+    #[test]
+    fn single_expression() {
+        // Comprehensions run without any statement boundaries, so cannot be interrupted.
+        VexTest::new("time")
+            .with_manifest(manifest("max-callback-seconds = 0.01"))
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        x = [i for i in range(5000000)]
+                "},
+            )
+            .returns_error(
+                "on_open_project exceeded limit of 10(.0)?ms while handling open_project event",
+            );
+
+        VexTest::new("heap")
+            .with_manifest(manifest("max-callback-heap-mib = 1"))
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        x = [str(i) for i in range(1 << 20)]
+                "},
+            )
+            .returns_error("on_open_project exceeded limit of 1 MiB of heap");
+    }
+
+    #[test]
+    fn names_file() {
+        VexTest::new("names-file")
+            .with_manifest(manifest("max-callback-steps = 5"))
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        vex.search('rust', '(source_file) @file', on_match)
+
+                    def on_match(event):
+                        for i in range(10):
+                            x = i
+                "},
+            )
+            .with_source_file("src/main.rs", "fn main() {}")
+            .returns_error(
+                "test.star:5 on_match exceeded limit of 5 steps while handling match event on src/main.rs$",
+            );
+    }
+}
@@ -0,0 +1,11 @@
+use allocative::Allocative;
+
+use crate::id::{GroupId, LintId};
+
+/// A lint which a scriptlet declares that it may emit.
+#[derive(Clone, Debug, PartialEq, Eq, Allocative)]
+pub struct LintDeclaration {
+    pub id: LintId,
+    pub group: Option<GroupId>,
+    pub description: Option<String>,
+}
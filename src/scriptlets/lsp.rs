@@ -2,17 +2,110 @@ use std::fmt::Display;
 
 use allocative::Allocative;
 use dupe::Dupe;
-use starlark::values::{AllocValue, Heap, StarlarkValue, Value};
-use starlark_derive::{starlark_value, NoSerialize, ProvidesStaticType, Trace};
+use starlark::{
+    environment::{Methods, MethodsBuilder, MethodsStatic},
+    eval::Evaluator,
+    starlark_module, starlark_simple_value,
+    typing::Ty,
+    values::{type_repr::StarlarkTypeRepr, AllocValue, Demand, Heap, StarlarkValue, Value},
+};
+use starlark_derive::{
+    starlark_attrs, starlark_value, NoSerialize, ProvidesStaticType, StarlarkAttrs, Trace,
+};
+
+use crate::{
+    error::Error,
+    language::Language,
+    lsp::LanguageServer,
+    scriptlets::{extra_data::TempData, Location, Node},
+    source_path::PrettyPath,
+};
 
 #[derive(Debug, PartialEq, Eq, ProvidesStaticType, NoSerialize, Allocative, Trace)]
 pub struct Lsp<'v> {
     pub language: Value<'v>,
 }
 
-impl Lsp<'_> {
+impl<'v> Lsp<'v> {
     const NAME: &'static str = "Lsp";
     const LANGUAGE_ATTR_NAME: &'static str = "language";
+
+    /// Return the server for this object's language, checking that it can answer questions about
+    /// the given node.
+    fn server_for<'e>(
+        &self,
+        node: &Node<'v>,
+        eval: &Evaluator<'v, 'e>,
+    ) -> anyhow::Result<&'e LanguageServer> {
+        let language = self
+            .language
+            .unpack_str()
+            .expect("internal error: language not a string")
+            .parse::<Language>()?;
+        let node_language = node.source_file.language_data.language();
+        if *node_language != language {
+            return Err(Error::LanguageServer {
+                language,
+                reason: format!("cannot query {node_language} node"),
+            }
+            .into());
+        }
+
+        let ctx = TempData::get_from(eval).ctx;
+        let server = ctx
+            .language_server(&language)?
+            .ok_or(Error::NoLanguageServer(language))?;
+
+        // Answers draw on the whole project, not just the file being scanned.
+        ctx.note_project_files_read();
+        Ok(server)
+    }
+
+    #[starlark_module]
+    fn methods(builder: &mut MethodsBuilder) {
+        fn hover<'v>(
+            this: Value<'v>,
+            #[starlark(require=pos)] node: Node<'v>,
+            eval: &mut Evaluator<'v, '_>,
+        ) -> anyhow::Result<Option<String>> {
+            let server = Lsp::from_value(this).server_for(&node, eval)?;
+            Ok(server.hover(node.source_file, node.start_byte())?)
+        }
+
+        fn type_of<'v>(
+            this: Value<'v>,
+            #[starlark(require=pos)] node: Node<'v>,
+            eval: &mut Evaluator<'v, '_>,
+        ) -> anyhow::Result<Option<String>> {
+            let server = Lsp::from_value(this).server_for(&node, eval)?;
+            Ok(server.type_of(node.source_file, node.start_byte())?)
+        }
+
+        fn definition<'v>(
+            this: Value<'v>,
+            #[starlark(require=pos)] node: Node<'v>,
+            eval: &mut Evaluator<'v, '_>,
+        ) -> anyhow::Result<Vec<LspLocation>> {
+            let server = Lsp::from_value(this).server_for(&node, eval)?;
+            Ok(server.definition(node.source_file, node.start_byte())?)
+        }
+
+        fn references<'v>(
+            this: Value<'v>,
+            #[starlark(require=pos)] node: Node<'v>,
+            #[starlark(require=named, default = true)] include_declaration: bool,
+            eval: &mut Evaluator<'v, '_>,
+        ) -> anyhow::Result<Vec<LspLocation>> {
+            let server = Lsp::from_value(this).server_for(&node, eval)?;
+            Ok(server.references(node.source_file, node.start_byte(), include_declaration)?)
+        }
+    }
+
+    fn from_value(value: Value<'v>) -> &'v Self {
+        value
+            .request_value::<&Lsp<'_>>()
+            .expect("internal error: incorrect receiver")
+    }
 }
 
 impl Display for Lsp<'_> {
@@ -23,6 +116,15 @@ impl Display for Lsp<'_> {
 
 #[starlark_value(type = "Lsp")]
 impl<'v> StarlarkValue<'v> for Lsp<'v> {
+    fn provide(&'v self, demand: &mut Demand<'_, 'v>) {
+        demand.provide_value(self)
+    }
+
+    fn get_methods() -> Option<&'static Methods> {
+        static METHODS: MethodsStatic = MethodsStatic::new();
+        METHODS.methods(Self::methods)
+    }
+
     fn dir_attr(&self) -> Vec<String> {
         vec![Self::LANGUAGE_ATTR_NAME.to_owned()]
     }
@@ -45,9 +147,39 @@ impl<'v> AllocValue<'v> for Lsp<'v> {
     }
 }
 
+/// A location reported by a language server, possibly in a file outside of the project.
+#[derive(
+    Clone, Debug, PartialEq, Eq, Allocative, ProvidesStaticType, NoSerialize, StarlarkAttrs,
+)]
+pub struct LspLocation {
+    pub path: PrettyPath,
+    pub location: Location,
+}
+starlark_simple_value!(LspLocation);
+
+#[starlark_value(type = "LspLocation")]
+impl<'v> StarlarkValue<'v> for LspLocation {
+    starlark_attrs!();
+
+    fn attr_ty(attr: &str) -> Option<Ty> {
+        match attr {
+            "path" => Some(PrettyPath::starlark_type_repr()),
+            "location" => Some(Location::starlark_type_repr()),
+            _ => None,
+        }
+    }
+}
+
+impl Display for LspLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.path, self.location)
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use indoc::indoc;
+    use indoc::{formatdoc, indoc};
+    use serde_json::json;
     use starlark::{
         environment::{Globals, Module},
         eval::Evaluator,
@@ -129,4 +261,114 @@ mod tests {
             )
             .assert_irritation_free();
     }
+
+    #[test]
+    fn queries() {
+        VexTest::new("queries")
+            .with_manifest(indoc! {r#"
+                [vex]
+                version = "1"
+                enable-lsp = true
+            "#})
+            .with_language_server(Language::Rust, |method, params| {
+                let uri = &params["textDocument"]["uri"];
+                let position = &params["position"];
+                let range = |start, end| {
+                    json!({
+                        "start": { "line": 1, "character": start },
+                        "end": { "line": 1, "character": end },
+                    })
+                };
+                match method {
+                    "textDocument/hover" => json!({
+                        "contents": {
+                            "kind": "markdown",
+                            "value": format!(
+                                "```rust\nResult<Row, DbError>\n```\n\nAt {}:{}",
+                                position["line"], position["character"],
+                            ),
+                        },
+                    }),
+                    "textDocument/definition" => json!({ "uri": uri, "range": range(14, 23) }),
+                    "textDocument/references" => {
+                        let mut references = vec![json!({ "uri": uri, "range": range(14, 16) })];
+                        if params["context"]["includeDeclaration"] == true {
+                            references.push(json!({ "uri": uri, "range": range(4, 7) }));
+                        }
+                        json!(references)
+                    }
+                    "textDocument/didOpen" => serde_json::Value::Null,
+                    _ => panic!("unexpected method {method}"),
+                }
+            })
+            .with_scriptlet(
+                "vexes/test.star",
+                formatdoc! {r#"
+                    load('{check_path}', 'check')
+
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        vex.search(
+                            'rust',
+                            '(call_expression function: (field_expression value: (_) @receiver))',
+                            on_match,
+                        )
+
+                    def on_match(event):
+                        lsp = vex.lsp_for('rust')
+                        receiver = event.captures['receiver']
+
+                        check['eq'](lsp.type_of(receiver), 'Result<Row, DbError>')
+                        check['in']('At 1:14', lsp.hover(receiver))
+
+                        definitions = lsp.definition(receiver)
+                        check['eq'](len(definitions), 1)
+                        check['eq'](type(definitions[0]), 'LspLocation')
+                        check['eq'](str(definitions[0].path), 'src/main.rs')
+                        check['eq'](str(definitions[0].location), '2:14-23')
+
+                        check['eq'](len(lsp.references(receiver)), 2)
+                        references = lsp.references(receiver, include_declaration=False)
+                        check['eq'](str(references[0]), 'src/main.rs:2:14-16')
+                "#,
+                    check_path = VexTest::CHECK_STARLARK_PATH,
+                },
+            )
+            .with_source_file(
+                "src/main.rs",
+                indoc! {"
+                    fn main() {
+                        let row = db::get().unwrap();
+                    }
+                "},
+            )
+            .assert_irritation_free();
+    }
+
+    #[test]
+    fn unconfigured() {
+        VexTest::new("unconfigured")
+            .with_manifest(indoc! {r#"
+                [vex]
+                version = "1"
+                enable-lsp = true
+            "#})
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        vex.search('rust', '(integer_literal) @lit', on_match)
+
+                    def on_match(event):
+                        vex.lsp_for('rust').hover(event.captures['lit'])
+                "},
+            )
+            .with_source_file("src/main.rs", "fn main() { let x = 1; }\n")
+            .returns_error("no language server configured for rust");
+    }
 }
@@ -1,12 +1,14 @@
 use std::fmt::Display;
 
 use allocative::Allocative;
-use starlark::values::{StarlarkValue, UnpackValue, Value};
-use starlark_derive::{starlark_value, NoSerialize, ProvidesStaticType};
+use starlark::{
+    typing::Ty,
+    values::{type_repr::StarlarkTypeRepr, UnpackValue, Value},
+};
 
 use crate::{scriptlets::Node, source_path::PrettyPath};
 
-#[derive(Debug, PartialEq, Eq, ProvidesStaticType, NoSerialize, Allocative)]
+#[derive(Debug, PartialEq, Eq, Allocative)]
 pub enum MainAnnotation<'v> {
     Path {
         path: PrettyPath,
@@ -44,8 +46,16 @@ impl<'v> Display for MainAnnotation<'v> {
     }
 }
 
-#[starlark_value(type = "Path|(Node, str)")]
-impl<'v> StarlarkValue<'v> for MainAnnotation<'v> {}
+impl<'v> StarlarkTypeRepr for MainAnnotation<'v> {
+    fn starlark_type_repr() -> Ty {
+        Ty::unions(vec![
+            PrettyPath::starlark_type_repr(),
+            <(PrettyPath, &str)>::starlark_type_repr(),
+            Node::starlark_type_repr(),
+            <(Node<'v>, &str)>::starlark_type_repr(),
+        ])
+    }
+}
 
 impl<'v> UnpackValue<'v> for MainAnnotation<'v> {
     fn unpack_value(value: Value<'v>) -> Option<Self> {
@@ -10,21 +10,24 @@ use derive_more::Display;
 use derive_new::new;
 use dupe::{Dupe, OptionDupedExt};
 use paste::paste;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use starlark::{
     collections::StarlarkHasher,
     environment::{Methods, MethodsBuilder, MethodsStatic},
     starlark_simple_value,
+    typing::Ty,
     values::{
-        AllocValue, Demand, Heap, NoSerialize, ProvidesStaticType, StarlarkValue, Trace,
-        UnpackValue, Value, ValueError,
+        AllocValue, Demand, Heap, NoSerialize, ProvidesStaticType, StarlarkValue, StringValue,
+        Trace, UnpackValue, Value, ValueError,
     },
 };
 use starlark_derive::{starlark_attrs, starlark_module, starlark_value, StarlarkAttrs};
 use strum::EnumIs;
-use tree_sitter::{Node as TSNode, Point, TreeCursor};
+use tree_sitter::{Node as TSNode, Point, QueryCursor, TreeCursor};
 
-use crate::{result::Result, source_file::ParsedSourceFile};
+use crate::{
+    result::Result, scriptlets::query_captures::QueryCaptures, source_file::ParsedSourceFile,
+};
 
 #[derive(new, Clone, Debug, PartialEq, Eq, ProvidesStaticType, NoSerialize, Allocative)]
 pub struct Node<'v> {
@@ -42,6 +45,18 @@ unsafe impl<'v> Trace<'v> for Node<'v> {
 impl<'v> Node<'v> {
     const KIND_ATTR_NAME: &'static str = "kind";
     const LOCATION_ATTR_NAME: &'static str = "location";
+    const TEXT_ATTR_NAME: &'static str = "text";
+    const FIELD_NAME_ATTR_NAME: &'static str = "field_name";
+    const START_BYTE_ATTR_NAME: &'static str = "start_byte";
+    const END_BYTE_ATTR_NAME: &'static str = "end_byte";
+    const ATTR_NAMES: [&'static str; 6] = [
+        Self::KIND_ATTR_NAME,
+        Self::LOCATION_ATTR_NAME,
+        Self::TEXT_ATTR_NAME,
+        Self::FIELD_NAME_ATTR_NAME,
+        Self::START_BYTE_ATTR_NAME,
+        Self::END_BYTE_ATTR_NAME,
+    ];
 
     #[inline]
     fn parent(&self) -> Option<Self> {
@@ -88,6 +103,74 @@ impl<'v> Node<'v> {
             .map(|ts_node| Self::new(ts_node, self.source_file))
     }
 
+    #[inline]
+    fn named_children<'cursor>(
+        &self,
+        cursor: &'cursor mut TreeCursor<'v>,
+    ) -> impl ExactSizeIterator<Item = Self> + 'cursor {
+        self.ts_node
+            .named_children(cursor)
+            .map(|ts_node| Self::new(ts_node, self.source_file))
+    }
+
+    /// The name of the field of this node's parent which holds this node, if any.
+    fn field_name(&self) -> Option<&'static str> {
+        let parent = self.parent()?;
+        let index = parent
+            .children(&mut parent.walk())
+            .position(|child| child == *self)
+            .expect("internal error: node not a child of its parent");
+        parent.field_name_for_child(index as u32)
+    }
+
+    /// The descendants of this node in pre-order, excluding the node itself.
+    fn descendants(&self, kind: Option<&str>) -> Vec<Self> {
+        let mut descendants = Vec::new();
+        let mut cursor = self.walk();
+        if !cursor.goto_first_child() {
+            return descendants;
+        }
+        'walk: loop {
+            let node = cursor.node();
+            if kind.map_or(true, |kind| node.grammar_name() == kind) {
+                descendants.push(Self::new(node, self.source_file));
+            }
+            if cursor.goto_first_child() {
+                continue;
+            }
+            while !cursor.goto_next_sibling() {
+                if !cursor.goto_parent() || cursor.node() == self.ts_node {
+                    break 'walk;
+                }
+            }
+        }
+        descendants
+    }
+
+    /// The smallest descendant of this node which spans the given byte range.
+    fn descendant_for_byte_range(&self, start: usize, end: usize) -> Option<Self> {
+        if start > end || start < self.start_byte() || self.end_byte() < end {
+            return None;
+        }
+        self.ts_node
+            .descendant_for_byte_range(start, end)
+            .map(|ts_node| Self::new(ts_node, self.source_file))
+    }
+
+    fn text(&self) -> &'v str {
+        &self.source_file.content[self.byte_range()]
+    }
+
+    /// Run the given query over this node and its descendants.
+    fn search(&self, query: &StringValue<'_>, heap: &'v Heap) -> Result<Vec<QueryCaptures<'v>>> {
+        let query = self.source_file.language_data.get_or_create_query(query)?;
+        let matches = QueryCursor::new()
+            .matches(&query, self.ts_node, self.source_file.content.as_bytes())
+            .map(|qmatch| QueryCaptures::new(&query, qmatch, self.source_file, heap))
+            .collect();
+        Ok(matches)
+    }
+
     pub fn to_complete_sexp(&self) -> Result<String> {
         let mut expr = String::new();
         NodePrinter::new(&mut expr, WhitespaceStyle::Compact).write_node(self, None)?;
@@ -136,9 +219,48 @@ impl<'v> Node<'v> {
             Ok(this.child_count())
         }
 
+        fn named_children<'v>(this: Node<'v>) -> starlark::Result<Vec<Node<'v>>> {
+            Ok(this.named_children(&mut this.walk()).collect())
+        }
+
+        fn num_named_children<'v>(this: Node<'v>) -> starlark::Result<usize> {
+            Ok(this.named_child_count())
+        }
+
+        fn field<'v>(
+            this: Node<'v>,
+            #[starlark(require=pos)] name: &str,
+        ) -> starlark::Result<Option<Node<'v>>> {
+            Ok(this.child_by_field_name(name))
+        }
+
+        fn descendants<'v>(
+            this: Node<'v>,
+            #[starlark(require=named)] kind: Option<&str>,
+        ) -> starlark::Result<Vec<Node<'v>>> {
+            Ok(this.descendants(kind))
+        }
+
+        fn descendant_for_range<'v>(
+            this: Node<'v>,
+            #[starlark(require=pos)] start_byte: u32,
+            #[starlark(require=pos)] end_byte: u32,
+        ) -> starlark::Result<Option<Node<'v>>> {
+            Ok(this.descendant_for_byte_range(start_byte as usize, end_byte as usize))
+        }
+
         fn expr<'v>(this: Node<'v>) -> starlark::Result<String> {
             this.to_complete_sexp().map_err(starlark::Error::new_other)
         }
+
+        fn search<'v>(
+            this: Node<'v>,
+            #[starlark(require=pos)] query: StringValue<'v>,
+            heap: &'v Heap,
+        ) -> starlark::Result<Vec<QueryCaptures<'v>>> {
+            this.search(&query, heap)
+                .map_err(starlark::Error::new_other)
+        }
     }
 }
 
@@ -206,22 +328,23 @@ impl<'v> StarlarkValue<'v> for Node<'v> {
     }
 
     fn dir_attr(&self) -> Vec<String> {
-        [Self::KIND_ATTR_NAME, Self::LOCATION_ATTR_NAME]
-            .into_iter()
-            .map(Into::into)
-            .collect()
+        Self::ATTR_NAMES.into_iter().map(Into::into).collect()
     }
 
     fn get_attr(&self, attr: &str, heap: &'v Heap) -> Option<Value<'v>> {
         match attr {
             Self::KIND_ATTR_NAME => Some(heap.alloc(heap.alloc_str(self.grammar_name()))),
             Self::LOCATION_ATTR_NAME => Some(heap.alloc(Location::of(self))),
+            Self::TEXT_ATTR_NAME => Some(heap.alloc(self.text())),
+            Self::FIELD_NAME_ATTR_NAME => Some(heap.alloc(self.field_name())),
+            Self::START_BYTE_ATTR_NAME => Some(heap.alloc(self.start_byte())),
+            Self::END_BYTE_ATTR_NAME => Some(heap.alloc(self.end_byte())),
             _ => None,
         }
     }
 
     fn has_attr(&self, attr: &str, _heap: &'v Heap) -> bool {
-        [Self::KIND_ATTR_NAME, Self::LOCATION_ATTR_NAME].contains(&attr)
+        Self::ATTR_NAMES.contains(&attr)
     }
 
     fn get_methods() -> Option<&'static Methods> {
@@ -476,6 +599,7 @@ impl WhitespaceStyle {
     Hash,
     Allocative,
     Serialize,
+    Deserialize,
     ProvidesStaticType,
     StarlarkAttrs,
 )]
@@ -518,6 +642,13 @@ impl Location {
 #[starlark_value(type = "Location")]
 impl<'v> StarlarkValue<'v> for Location {
     starlark_attrs!();
+
+    fn attr_ty(attr: &str) -> Option<Ty> {
+        match attr {
+            "start_row" | "start_column" | "end_row" | "end_column" => Some(Ty::int()),
+            _ => None,
+        }
+    }
 }
 
 impl Display for Location {
@@ -679,18 +810,28 @@ mod tests {
                         def on_match(event):
                             expected_attrs = [
                                 'children',
+                                'descendant_for_range',
+                                'descendants',
+                                'end_byte',
+                                'field',
+                                'field_name',
                                 'is_extra',
                                 'is_named',
                                 'kind',
                                 'location',
+                                'named_children',
                                 'next_sibling',
                                 'next_siblings',
                                 'num_children',
+                                'num_named_children',
                                 'parent',
                                 'parents',
                                 'previous_sibling',
                                 'previous_siblings',
                                 'expr',
+                                'search',
+                                'start_byte',
+                                'text',
                             ]
                             check['attrs'](event.captures['bin_expr'], expected_attrs)
                     "#,
@@ -954,6 +1095,138 @@ mod tests {
             .assert_irritation_free();
     }
 
+    #[test]
+    fn search() {
+        VexTest::new("search")
+            .with_scriptlet(
+                "vexes/test.star",
+                formatdoc! {r#"
+                        load('{check_path}', 'check')
+
+                        def init():
+                            vex.observe('open_project', on_open_project)
+
+                        def on_open_project(event):
+                            vex.search(
+                                'rust',
+                                '(function_item name: (identifier) @name body: (block) @body)',
+                                on_match,
+                            )
+
+                        def on_match(event):
+                            if str(event.captures['name']) != 'main':
+                                return
+                            body = event.captures['body']
+
+                            results = body.search('(integer_literal) @lit')
+                            check['type'](results, 'list')
+                            check['eq']([str(result['lit']) for result in results], ['1', '2'])
+                            for result in results:
+                                check['type'](result, 'QueryCaptures')
+
+                            check['eq'](body.search('(function_item) @func'), [])
+                    "#,
+                    check_path = VexTest::CHECK_STARLARK_PATH,
+                },
+            )
+            .with_source_file(
+                "src/main.rs",
+                indoc! {r#"
+                    fn main() {
+                        let x = 1 + 2;
+                    }
+
+                    fn other() {
+                        let y = 3;
+                    }
+                "#},
+            )
+            .assert_irritation_free();
+
+        VexTest::new("invalid-query")
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        vex.search('rust', '(integer_literal) @lit', on_match)
+
+                    def on_match(event):
+                        event.captures['lit'].search('(no_such_node) @x')
+                "#},
+            )
+            .with_source_file("src/main.rs", "fn main() { 1; }")
+            .returns_error("Invalid node type no_such_node");
+    }
+
+    #[test]
+    fn introspection() {
+        VexTest::new("introspection")
+            .with_scriptlet(
+                "vexes/test.star",
+                formatdoc! {r#"
+                        load('{check_path}', 'check')
+
+                        def init():
+                            vex.observe('open_project', on_open_project)
+
+                        def on_open_project(event):
+                            vex.search('rust', '(function_item) @func', on_match)
+
+                        def on_match(event):
+                            func = event.captures['func']
+                            check['eq'](func.text, 'fn main() {{\n    // comment\n    let x = f(1) + g(2);\n}}')
+                            check['eq'](func.start_byte, 0)
+                            check['eq'](func.end_byte, len(func.text))
+                            check['eq'](func.field_name, None)
+
+                            name = func.field('name')
+                            check['eq'](name.text, 'main')
+                            check['eq'](name.field_name, 'name')
+                            check['eq'](name, func['name'])
+                            check['eq'](func.field('no_such_field'), None)
+
+                            body = func.field('body')
+                            check['eq'](body.num_children(), 4)
+                            check['eq'](body.num_named_children(), 2)
+                            check['eq'](
+                                [child.kind for child in body.named_children()],
+                                ['line_comment', 'let_declaration'],
+                            )
+
+                            calls = func.descendants(kind='call_expression')
+                            check['eq']([call.text for call in calls], ['f(1)', 'g(2)'])
+                            descendants = func.descendants()
+                            check['eq'](descendants[0], name.previous_sibling())
+                            check['not in'](func, descendants)
+                            check['true'](all([
+                                func.start_byte <= d.start_byte and d.end_byte <= func.end_byte
+                                for d in descendants
+                            ]))
+                            check['eq'](name.descendants(), [])
+
+                            g = calls[1]
+                            check['eq'](func.descendant_for_range(g.start_byte, g.end_byte), g)
+                            check['eq'](func.descendant_for_range(g.start_byte, g.start_byte + 1).kind, 'identifier')
+                            check['eq'](name.descendant_for_range(g.start_byte, g.end_byte), None)
+                    "#,
+                    check_path = VexTest::CHECK_STARLARK_PATH,
+                },
+            )
+            .with_source_file(
+                "src/main.rs",
+                indoc! {r#"
+                    fn main() {
+                        // comment
+                        let x = f(1) + g(2);
+                    }
+                "#},
+            )
+            .assert_irritation_free();
+    }
+
     #[test]
     fn location() {
         VexTest::new("location")
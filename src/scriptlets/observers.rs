@@ -1,4 +1,7 @@
+use std::fmt::Display;
+
 use allocative::Allocative;
+use camino::Utf8Path;
 use derive_new::new;
 use dupe::Dupe;
 use starlark::{
@@ -9,12 +12,15 @@ use starlark_derive::{starlark_value, NoSerialize, ProvidesStaticType, Trace};
 
 use crate::{
     context::Context,
+    error::Error,
     ignore_markers::IgnoreMarkers,
+    profile::Subject,
     result::Result,
     scriptlets::{
         action::Action, event::EventKind, extra_data::TempData, handler_module::HandlerModule,
-        print_handler::PrintHandler, ScriptArgsValueMap,
+        limits::CallbackLimits, print_handler::PrintHandler, ScriptArgsValueMap,
     },
+    source_path::PrettyPath,
     warning_filter::WarningFilter,
 };
 
@@ -72,6 +78,16 @@ impl ObserverData {
         self.on_post_test_run.push(observer)
     }
 
+    /// Move the observers of test events into a separate set, as tests are run separately for
+    /// each scriptlet.
+    pub fn take_test_observers(&mut self) -> Self {
+        Self {
+            on_pre_test_run: std::mem::take(&mut self.on_pre_test_run),
+            on_post_test_run: std::mem::take(&mut self.on_post_test_run),
+            ..Self::empty()
+        }
+    }
+
     pub fn extend(&mut self, other: Self) {
         let Self {
             on_open_project,
@@ -102,21 +118,69 @@ impl<'v> StarlarkValue<'v> for ObserverData {}
 #[derive(new, Debug, Trace, Allocative)]
 pub struct UnfrozenObserver<'v> {
     callback: Value<'v>,
+    origin: ObserverOrigin,
 }
 
 impl<'v> Freeze for UnfrozenObserver<'v> {
     type Frozen = Observer;
 
     fn freeze(self, freezer: &Freezer) -> anyhow::Result<Self::Frozen> {
-        let Self { callback } = self;
+        let Self { callback, origin } = self;
         let callback = callback.freeze(freezer)?;
-        Ok(Observer { callback })
+        Ok(Observer { callback, origin })
     }
 }
 
 #[derive(new, Debug, Clone, Dupe, Allocative)]
 pub struct Observer {
     callback: FrozenValue,
+    origin: ObserverOrigin,
+}
+
+impl Observer {
+    /// The scriptlet which declared this observer.
+    pub fn scriptlet(&self) -> &PrettyPath {
+        &self.origin.scriptlet
+    }
+
+    /// A name for this observer, which identifies the scriptlet line which declared it and its
+    /// callback.
+    pub fn name(&self) -> String {
+        // Callbacks display as their module-qualified signature, e.g. `main.star.on_match(event)`.
+        let callback = self.callback.to_value().to_string();
+        let qualified_name = callback.split('(').next().unwrap_or_default();
+        let callback_name = qualified_name.rsplit('.').next().unwrap_or_default();
+        format!("{} {callback_name}", self.origin)
+    }
+}
+
+/// The place in a scriptlet where an observer was declared.
+#[derive(Clone, Debug, Dupe, Allocative)]
+pub struct ObserverOrigin {
+    scriptlet: PrettyPath,
+    line: usize,
+}
+
+impl ObserverOrigin {
+    /// The location of the call to the native function currently being evaluated.
+    pub fn of_caller(eval: &Evaluator<'_, '_>) -> Self {
+        match eval.call_stack_top_location() {
+            Some(location) => Self {
+                scriptlet: PrettyPath::new(Utf8Path::new(location.filename())),
+                line: location.resolve_span().begin.line + 1,
+            },
+            None => Self {
+                scriptlet: PrettyPath::new(Utf8Path::new("<unknown>")),
+                line: 0,
+            },
+        }
+    }
+}
+
+impl Display for ObserverOrigin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.scriptlet, self.line)
+    }
 }
 
 pub trait Observable {
@@ -132,6 +196,7 @@ pub trait Observable {
 #[derive(Clone, Debug, Dupe)]
 pub struct ObserveOptions<'v> {
     pub action: Action,
+    pub file: Option<&'v PrettyPath>,
     pub script_args: &'v ScriptArgsValueMap,
     pub ignore_markers: Option<&'v IgnoreMarkers>,
     pub lsp_enabled: bool,
@@ -149,6 +214,7 @@ impl Observable for Observer {
     ) -> Result<()> {
         let ObserveOptions {
             action,
+            file,
             script_args,
             ignore_markers,
             lsp_enabled,
@@ -166,10 +232,30 @@ impl Observable for Observer {
         let mut eval = Evaluator::new(handler_module);
         eval.extra = Some(&temp_data);
         eval.set_print_handler(print_handler);
+        if let Some(coverage) = ctx.coverage() {
+            coverage.instrument(&mut eval)?;
+        }
 
         let func = self.callback.dupe().to_value(); // TODO(kcza): check thread safety! Can this unfrozen
                                                     // function mutate upvalues if it is a closure?
-        eval.eval_function(func, &[event], &[])?;
+        let limits = CallbackLimits::new(&ctx.manifest.run);
+        let result = ctx
+            .profiled(
+                Subject::Callback,
+                || self.name(),
+                || limits.enforce(&mut eval, |eval| eval.eval_function(func, &[event], &[])),
+            )
+            .map_err(|limit| Error::CallbackLimitExceeded {
+                observer: self.name(),
+                event: action.name(),
+                file: file.cloned(),
+                limit,
+            });
+        // Lines run before a failure are still covered.
+        if let Some(coverage) = ctx.coverage() {
+            coverage.record(&eval)?;
+        }
+        result??;
 
         Ok(())
     }
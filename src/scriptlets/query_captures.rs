@@ -24,7 +24,7 @@ pub struct QueryCaptures<'v> {
 impl<'v> QueryCaptures<'v> {
     pub fn new(
         query: &Query,
-        qmatch: QueryMatch<'v, '_>,
+        qmatch: QueryMatch<'_, 'v>,
         source_file: &'v ParsedSourceFile,
         heap: &'v Heap,
     ) -> Self {
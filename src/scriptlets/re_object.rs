@@ -0,0 +1,550 @@
+use std::{
+    collections::HashMap,
+    fmt::{self, Display},
+    sync::RwLock,
+};
+
+use allocative::Allocative;
+use regex::{Captures, Regex};
+use starlark::{
+    environment::{Methods, MethodsBuilder, MethodsStatic},
+    starlark_module,
+    values::{
+        dict::AllocDict, tuple::AllocTuple, Heap, NoSerialize, ProvidesStaticType, StarlarkValue,
+        Value, ValueError, ValueLike,
+    },
+};
+use starlark_derive::starlark_value;
+
+use crate::result::Result;
+
+/// The `re` module. Each scriptlet gets its own, so patterns compiled by one are never shared
+/// with another.
+#[derive(Debug, Default, ProvidesStaticType, NoSerialize, Allocative)]
+pub struct ReObject {
+    #[allocative(skip)]
+    cache: RwLock<HashMap<String, Regex>>,
+}
+
+impl ReObject {
+    pub const NAME: &'static str = "re";
+
+    fn compile(&self, pattern: &str) -> Result<Regex> {
+        if let Some(regex) = self.cache.read().unwrap().get(pattern) {
+            return Ok(regex.clone());
+        }
+
+        let regex = Regex::new(pattern)?;
+        self.cache
+            .write()
+            .unwrap()
+            .insert(pattern.to_owned(), regex.clone());
+        Ok(regex)
+    }
+
+    fn get_from(this: Value<'_>) -> &Self {
+        this.downcast_ref()
+            .expect("internal error: incorrect receiver")
+    }
+
+    #[allow(clippy::type_complexity)]
+    #[starlark_module]
+    fn methods(builder: &mut MethodsBuilder) {
+        fn compile<'v>(
+            this: Value<'v>,
+            #[starlark(require=pos)] pattern: &str,
+        ) -> anyhow::Result<Pattern> {
+            let regex = ReObject::get_from(this).compile(pattern)?;
+            Ok(Pattern { regex })
+        }
+
+        fn escape<'v>(
+            #[starlark(this)] _this: Value<'v>,
+            #[starlark(require=pos)] string: &str,
+        ) -> anyhow::Result<String> {
+            Ok(regex::escape(string))
+        }
+
+        fn r#match<'v>(
+            this: Value<'v>,
+            #[starlark(require=pos)] pattern: &str,
+            #[starlark(require=pos)] string: &str,
+        ) -> anyhow::Result<Option<Match>> {
+            let regex = ReObject::get_from(this).compile(pattern)?;
+            Ok(match_start(&regex, string))
+        }
+
+        fn search<'v>(
+            this: Value<'v>,
+            #[starlark(require=pos)] pattern: &str,
+            #[starlark(require=pos)] string: &str,
+        ) -> anyhow::Result<Option<Match>> {
+            let regex = ReObject::get_from(this).compile(pattern)?;
+            Ok(search(&regex, string))
+        }
+
+        fn findall<'v>(
+            this: Value<'v>,
+            #[starlark(require=pos)] pattern: &str,
+            #[starlark(require=pos)] string: &str,
+            heap: &'v Heap,
+        ) -> anyhow::Result<Vec<Value<'v>>> {
+            let regex = ReObject::get_from(this).compile(pattern)?;
+            Ok(findall(&regex, string, heap))
+        }
+
+        fn sub<'v>(
+            this: Value<'v>,
+            #[starlark(require=pos)] pattern: &str,
+            #[starlark(require=pos)] repl: &str,
+            #[starlark(require=pos)] string: &str,
+            #[starlark(require=named, default=0)] count: u32,
+        ) -> anyhow::Result<String> {
+            let regex = ReObject::get_from(this).compile(pattern)?;
+            Ok(sub(&regex, repl, string, count))
+        }
+    }
+}
+
+starlark::starlark_simple_value!(ReObject);
+#[starlark_value(type = "Re")]
+impl<'v> StarlarkValue<'v> for ReObject {
+    fn get_methods() -> Option<&'static Methods> {
+        static RES: MethodsStatic = MethodsStatic::new();
+        RES.methods(ReObject::methods)
+    }
+}
+
+impl Display for ReObject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Self::NAME.fmt(f)
+    }
+}
+
+#[derive(Debug, ProvidesStaticType, NoSerialize, Allocative)]
+pub struct Pattern {
+    #[allocative(skip)]
+    regex: Regex,
+}
+
+impl Pattern {
+    const PATTERN_ATTR_NAME: &'static str = "pattern";
+
+    fn get_from(this: Value<'_>) -> &Self {
+        this.downcast_ref()
+            .expect("internal error: incorrect receiver")
+    }
+
+    #[starlark_module]
+    fn methods(builder: &mut MethodsBuilder) {
+        fn r#match<'v>(
+            this: Value<'v>,
+            #[starlark(require=pos)] string: &str,
+        ) -> anyhow::Result<Option<Match>> {
+            Ok(match_start(&Pattern::get_from(this).regex, string))
+        }
+
+        fn search<'v>(
+            this: Value<'v>,
+            #[starlark(require=pos)] string: &str,
+        ) -> anyhow::Result<Option<Match>> {
+            Ok(search(&Pattern::get_from(this).regex, string))
+        }
+
+        fn findall<'v>(
+            this: Value<'v>,
+            #[starlark(require=pos)] string: &str,
+            heap: &'v Heap,
+        ) -> anyhow::Result<Vec<Value<'v>>> {
+            Ok(findall(&Pattern::get_from(this).regex, string, heap))
+        }
+
+        fn sub<'v>(
+            this: Value<'v>,
+            #[starlark(require=pos)] repl: &str,
+            #[starlark(require=pos)] string: &str,
+            #[starlark(require=named, default=0)] count: u32,
+        ) -> anyhow::Result<String> {
+            Ok(sub(&Pattern::get_from(this).regex, repl, string, count))
+        }
+    }
+}
+
+starlark::starlark_simple_value!(Pattern);
+#[starlark_value(type = "Pattern")]
+impl<'v> StarlarkValue<'v> for Pattern {
+    fn dir_attr(&self) -> Vec<String> {
+        vec![Self::PATTERN_ATTR_NAME.into()]
+    }
+
+    fn get_attr(&self, attr: &str, heap: &'v Heap) -> Option<Value<'v>> {
+        match attr {
+            Self::PATTERN_ATTR_NAME => Some(heap.alloc(self.regex.as_str())),
+            _ => None,
+        }
+    }
+
+    fn has_attr(&self, attr: &str, _heap: &'v Heap) -> bool {
+        attr == Self::PATTERN_ATTR_NAME
+    }
+
+    fn get_methods() -> Option<&'static Methods> {
+        static RES: MethodsStatic = MethodsStatic::new();
+        RES.methods(Pattern::methods)
+    }
+}
+
+impl Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "re.compile({:?})", self.regex.as_str())
+    }
+}
+
+/// A successful match, with the text and byte offsets of each of its groups. Group 0 is the
+/// whole match.
+#[derive(Debug, ProvidesStaticType, NoSerialize, Allocative)]
+pub struct Match {
+    groups: Vec<Option<Group>>,
+    names: Vec<Option<String>>,
+}
+
+#[derive(Debug, Allocative)]
+struct Group {
+    text: String,
+    start: usize,
+    end: usize,
+}
+
+impl Match {
+    fn new(regex: &Regex, captures: &Captures<'_>) -> Self {
+        let groups = captures
+            .iter()
+            .map(|group| {
+                group.map(|group| Group {
+                    text: group.as_str().to_owned(),
+                    start: group.start(),
+                    end: group.end(),
+                })
+            })
+            .collect();
+        let names = regex
+            .capture_names()
+            .map(|name| name.map(ToOwned::to_owned))
+            .collect();
+        Self { groups, names }
+    }
+
+    fn get_from(this: Value<'_>) -> &Self {
+        this.downcast_ref()
+            .expect("internal error: incorrect receiver")
+    }
+
+    /// The group with the given index or name. Unmatched groups are `None`.
+    fn group(&self, group: Value<'_>) -> starlark::Result<Option<&Group>> {
+        if let Some(index) = group.unpack_i32() {
+            return usize::try_from(index)
+                .ok()
+                .and_then(|index| self.groups.get(index))
+                .map(Option::as_ref)
+                .ok_or_else(|| ValueError::IndexOutOfBound(index).into());
+        }
+        if let Some(name) = group.unpack_str() {
+            return self
+                .names
+                .iter()
+                .position(|n| n.as_deref() == Some(name))
+                .map(|index| self.groups[index].as_ref())
+                .ok_or_else(|| ValueError::KeyNotFound(name.to_owned()).into());
+        }
+        ValueError::unsupported_with(self, "group", group)
+    }
+
+    #[starlark_module]
+    fn methods(builder: &mut MethodsBuilder) {
+        fn group<'v>(
+            this: Value<'v>,
+            #[starlark(require=pos, default=0)] group: Value<'v>,
+        ) -> starlark::Result<Option<String>> {
+            let group = Match::get_from(this).group(group)?;
+            Ok(group.map(|group| group.text.clone()))
+        }
+
+        fn groups<'v>(this: Value<'v>) -> starlark::Result<Vec<Option<String>>> {
+            Ok(Match::get_from(this)
+                .groups
+                .iter()
+                .skip(1)
+                .map(|group| group.as_ref().map(|group| group.text.clone()))
+                .collect())
+        }
+
+        fn groupdict<'v>(this: Value<'v>, heap: &'v Heap) -> starlark::Result<Value<'v>> {
+            let this = Match::get_from(this);
+            Ok(
+                heap.alloc(AllocDict(this.names.iter().zip(&this.groups).filter_map(
+                    |(name, group)| {
+                        let name = name.as_deref()?;
+                        let text = group.as_ref().map(|group| group.text.as_str());
+                        Some((name, text))
+                    },
+                ))),
+            )
+        }
+
+        fn start<'v>(
+            this: Value<'v>,
+            #[starlark(require=pos, default=0)] group: Value<'v>,
+        ) -> starlark::Result<i32> {
+            let group = Match::get_from(this).group(group)?;
+            Ok(group.map_or(-1, |group| group.start as i32))
+        }
+
+        fn end<'v>(
+            this: Value<'v>,
+            #[starlark(require=pos, default=0)] group: Value<'v>,
+        ) -> starlark::Result<i32> {
+            let group = Match::get_from(this).group(group)?;
+            Ok(group.map_or(-1, |group| group.end as i32))
+        }
+    }
+}
+
+starlark::starlark_simple_value!(Match);
+#[starlark_value(type = "Match")]
+impl<'v> StarlarkValue<'v> for Match {
+    fn at(&self, index: Value<'v>, heap: &'v Heap) -> starlark::Result<Value<'v>> {
+        let group = self.group(index)?;
+        Ok(heap.alloc(group.map(|group| group.text.as_str())))
+    }
+
+    fn get_methods() -> Option<&'static Methods> {
+        static RES: MethodsStatic = MethodsStatic::new();
+        RES.methods(Match::methods)
+    }
+}
+
+impl Display for Match {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Some(whole) = &self.groups[0] else {
+            panic!("internal error: match has no text");
+        };
+        write!(
+            f,
+            "<Match {:?} at {}..{}>",
+            whole.text, whole.start, whole.end
+        )
+    }
+}
+
+fn match_start(regex: &Regex, string: &str) -> Option<Match> {
+    regex
+        .captures(string)
+        .filter(|captures| captures.get(0).is_some_and(|whole| whole.start() == 0))
+        .map(|captures| Match::new(regex, &captures))
+}
+
+fn search(regex: &Regex, string: &str) -> Option<Match> {
+    regex
+        .captures(string)
+        .map(|captures| Match::new(regex, &captures))
+}
+
+/// Each non-overlapping match in `string`: the whole match if the pattern has no groups, the
+/// text of the only group if it has one, or a tuple of the text of each group otherwise.
+/// Unmatched groups are empty.
+fn findall<'v>(regex: &Regex, string: &str, heap: &'v Heap) -> Vec<Value<'v>> {
+    regex
+        .captures_iter(string)
+        .map(|captures| {
+            let mut groups = captures
+                .iter()
+                .map(|group| group.map_or("", |group| group.as_str()));
+            match captures.len() {
+                1 => heap.alloc(groups.next().unwrap()),
+                2 => heap.alloc(groups.nth(1).unwrap()),
+                _ => heap.alloc(AllocTuple(groups.skip(1))),
+            }
+        })
+        .collect()
+}
+
+fn sub(regex: &Regex, repl: &str, string: &str, count: u32) -> String {
+    regex.replacen(string, count as usize, repl).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::{formatdoc, indoc};
+
+    use crate::vextest::VexTest;
+
+    use super::*;
+
+    #[test]
+    fn cache() {
+        let re = ReObject::default();
+        re.compile("a+").unwrap();
+        re.compile("a+").unwrap();
+        re.compile("b+").unwrap();
+        assert_eq!(re.cache.read().unwrap().len(), 2);
+
+        re.compile("(").unwrap_err();
+        assert_eq!(re.cache.read().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn compile() {
+        VexTest::new("compile")
+            .with_scriptlet(
+                "vexes/test.star",
+                formatdoc! {r#"
+                    load('{check_path}', 'check')
+
+                    pattern = re.compile(r'(?P<word>[a-z]+)(\d+)')
+                    check['type'](pattern, 'Pattern')
+                    check['eq'](pattern.pattern, r'(?P<word>[a-z]+)(\d+)')
+                    check['eq'](str(pattern), 're.compile("(?P<word>[a-z]+)(\\\\d+)")')
+
+                    check['eq'](pattern.match('x abc123'), None)
+                    m = pattern.search('x abc123')
+                    check['type'](m, 'Match')
+                    check['eq'](m.group(), 'abc123')
+                    check['eq'](pattern.findall('a1 b2'), [('a', '1'), ('b', '2')])
+                    check['eq'](pattern.sub('$2$word', 'a1 b2', count=1), '1a b2')
+                "#,
+                    check_path = VexTest::CHECK_STARLARK_PATH,
+                },
+            )
+            .assert_irritation_free();
+
+        VexTest::new("invalid")
+            .with_scriptlet("vexes/test.star", "re.compile('(')")
+            .returns_error("regex parse error");
+    }
+
+    #[test]
+    fn r#match() {
+        VexTest::new("match")
+            .with_scriptlet(
+                "vexes/test.star",
+                formatdoc! {r#"
+                    load('{check_path}', 'check')
+
+                    check['eq'](re.match('b', 'abc'), None)
+
+                    m = re.match(r'(?P<first>\w+) (?P<second>\w+)?(x)?', 'hello world!')
+                    check['eq'](str(m), '<Match "hello world" at 0..11>')
+                    check['eq'](m.group(), 'hello world')
+                    check['eq'](m.group(0), 'hello world')
+                    check['eq'](m.group(1), 'hello')
+                    check['eq'](m.group('second'), 'world')
+                    check['eq'](m.group(3), None)
+                    check['eq'](m[1], 'hello')
+                    check['eq'](m['second'], 'world')
+                    check['eq'](m.groups(), ['hello', 'world', None])
+                    check['eq'](m.groupdict(), {{'first': 'hello', 'second': 'world'}})
+                    check['eq'](m.start(), 0)
+                    check['eq'](m.end('first'), 5)
+                    check['eq'](m.start('second'), 6)
+                    check['eq'](m.start(3), -1)
+                "#,
+                    check_path = VexTest::CHECK_STARLARK_PATH,
+                },
+            )
+            .assert_irritation_free();
+
+        VexTest::new("no-such-group")
+            .with_scriptlet("vexes/test.star", "re.match('(a)', 'a').group('b')")
+            .returns_error("Key `b` was not found");
+    }
+
+    #[test]
+    fn search() {
+        VexTest::new("search")
+            .with_scriptlet(
+                "vexes/test.star",
+                formatdoc! {r#"
+                    load('{check_path}', 'check')
+
+                    check['eq'](re.search('d', 'abc'), None)
+                    m = re.search('b+', 'abbc')
+                    check['eq'](m.group(), 'bb')
+                    check['eq'](m.start(), 1)
+                    check['eq'](m.end(), 3)
+                "#,
+                    check_path = VexTest::CHECK_STARLARK_PATH,
+                },
+            )
+            .assert_irritation_free();
+    }
+
+    #[test]
+    fn findall() {
+        VexTest::new("findall")
+            .with_scriptlet(
+                "vexes/test.star",
+                formatdoc! {r#"
+                    load('{check_path}', 'check')
+
+                    check['eq'](re.findall(r'\d+', 'a1 b22 c'), ['1', '22'])
+                    check['eq'](re.findall(r'[a-z](\d+)', 'a1 b22 c'), ['1', '22'])
+                    check['eq'](re.findall(r'([a-z])(\d+)?', 'a1 c'), [('a', '1'), ('c', '')])
+                    check['eq'](re.findall('x', 'abc'), [])
+                "#,
+                    check_path = VexTest::CHECK_STARLARK_PATH,
+                },
+            )
+            .assert_irritation_free();
+    }
+
+    #[test]
+    fn sub() {
+        VexTest::new("sub")
+            .with_scriptlet(
+                "vexes/test.star",
+                formatdoc! {r#"
+                    load('{check_path}', 'check')
+
+                    check['eq'](re.sub(r'\d', '#', 'a1b2c3'), 'a#b#c#')
+                    check['eq'](re.sub(r'\d', '#', 'a1b2c3', count=2), 'a#b#c3')
+                    check['eq'](re.sub(r'(?P<l>[a-z])(?P<d>\d)', '${{d}}${{l}}', 'a1b2'), '1a2b')
+                    check['eq'](re.escape('a.b*'), r'a\.b\*')
+                "#,
+                    check_path = VexTest::CHECK_STARLARK_PATH,
+                },
+            )
+            .assert_irritation_free();
+    }
+
+    #[test]
+    fn in_callbacks() {
+        let irritations = VexTest::new("in-callbacks")
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        vex.search('rust', '(line_comment) @comment', on_match)
+
+                    def on_match(event):
+                        comment = event.captures['comment']
+                        if re.search(r'\bTODO\b', str(comment)):
+                            vex.warn('todo', 'todo found', at=comment)
+                "#},
+            )
+            .with_source_file(
+                "src/main.rs",
+                indoc! {r#"
+                    // TODO: write main
+                    // TODOS are not matched
+                    fn main() {}
+                "#},
+            )
+            .try_run()
+            .unwrap()
+            .irritations;
+        assert_eq!(irritations.len(), 1);
+    }
+}
@@ -1,4 +1,7 @@
-use std::collections::{BTreeMap, HashSet};
+use std::{
+    collections::{BTreeMap, HashSet},
+    sync::OnceLock,
+};
 
 use camino::{Utf8Component, Utf8Path, Utf8PathBuf};
 use const_format::formatcp;
@@ -10,7 +13,7 @@ use starlark::{
     environment::{FrozenModule, Globals, GlobalsBuilder, LibraryExtension, Module},
     errors::Lint,
     eval::{Evaluator, FileLoader},
-    syntax::{AstModule, Dialect},
+    syntax::{AstModule, Dialect, DialectTypes},
     values::FrozenHeap,
 };
 
@@ -25,8 +28,9 @@ use crate::{
         extra_data::{RetainedData, TempData, UnfrozenRetainedData},
         handler_module::HandlerModule,
         print_handler::PrintHandler,
+        re_object::ReObject,
         store::{InitOptions, PreinitedModuleStore},
-        Intent, ObserverData, PreinitOptions,
+        Intent, LintDeclaration, ObserverData, PreinitOptions,
     },
     source_path::PrettyPath,
 };
@@ -39,8 +43,23 @@ pub struct PreinitingScriptlet {
 }
 
 impl PreinitingScriptlet {
+    const DIALECT: Dialect = Dialect {
+        enable_types: DialectTypes::Enable,
+        ..Dialect::Standard
+    };
+
+    const EXTENSIONS: [LibraryExtension; 7] = [
+        LibraryExtension::Print,
+        LibraryExtension::StructType,
+        LibraryExtension::RecordType,
+        LibraryExtension::EnumType,
+        LibraryExtension::Typing,
+        LibraryExtension::Json,
+        LibraryExtension::Partial,
+    ];
+
     pub fn new(path: Utf8PathBuf, code: String) -> Result<Self> {
-        let ast = AstModule::parse(path.as_str(), code, &Dialect::Standard)?;
+        let ast = AstModule::parse(path.as_str(), code, &Self::DIALECT)?;
         let loads = ast
             .loads()
             .into_iter()
@@ -55,14 +74,9 @@ impl PreinitingScriptlet {
 
     #[allow(unused)]
     pub fn lint(&self) -> Vec<Lint> {
-        self.ast.lint(Some(&self.global_names()))
+        self.ast.lint(Some(Self::global_names()))
     }
 
-    // #[allow(unused)]
-    // pub fn typecheck(&self, globals: &Globals, ...) -> Result<()> {
-    // // TODO(kcza): typecheck starlark before executing it!
-    // }
-
     pub fn preinit(
         self,
         ctx: &Context,
@@ -95,7 +109,16 @@ impl PreinitingScriptlet {
                 eval.set_loader(&loader);
                 eval.set_print_handler(&print_handler);
                 eval.extra = Some(&temp_data);
-                eval.eval_module(ast, &Self::globals())?;
+                eval.enable_static_typechecking(true);
+                if let Some(coverage) = ctx.coverage() {
+                    coverage.add_statements(&ast);
+                    coverage.instrument(&mut eval)?;
+                }
+                let result = eval.eval_module(ast, &Self::globals());
+                if let Some(coverage) = ctx.coverage() {
+                    coverage.record(&eval)?;
+                }
+                result?;
             };
             preinited_module.freeze()?
         };
@@ -108,14 +131,21 @@ impl PreinitingScriptlet {
     }
 
     fn globals() -> Globals {
-        let mut builder = GlobalsBuilder::extended_by(&[LibraryExtension::Print]);
+        let mut builder = GlobalsBuilder::extended_by(&Self::EXTENSIONS);
         let app = AppObject::new();
         builder.set(AppObject::NAME, builder.alloc(app));
+        builder.set(ReObject::NAME, builder.alloc(ReObject::default()));
         builder.build()
     }
 
-    fn global_names(&self) -> HashSet<String> {
-        HashSet::from_iter(["vex".to_string()])
+    fn global_names() -> &'static HashSet<String> {
+        static GLOBAL_NAMES: OnceLock<HashSet<String>> = OnceLock::new();
+        GLOBAL_NAMES.get_or_init(|| {
+            Self::globals()
+                .names()
+                .map(|name| name.as_str().to_owned())
+                .collect()
+        })
     }
 
     pub fn loads(&self) -> &BTreeMap<String, LoadPath> {
@@ -351,7 +381,7 @@ impl InitingScriptlet {
         ctx: &Context,
         opts: &InitOptions,
         frozen_heap: &FrozenHeap,
-    ) -> Result<ObserverData> {
+    ) -> Result<(ObserverData, Vec<LintDeclaration>)> {
         let Self {
             path,
             preinited_module,
@@ -362,7 +392,7 @@ impl InitingScriptlet {
         } = opts;
 
         let Some(init) = preinited_module.get_option("init")? else {
-            return Ok(ObserverData::empty());
+            return Ok((ObserverData::empty(), vec![]));
         };
 
         let module = {
@@ -380,40 +410,44 @@ impl InitingScriptlet {
                 let mut eval = Evaluator::new(&module);
                 eval.extra = Some(&temp_data);
                 eval.set_print_handler(&print_handler);
-                eval.eval_function(init.value(), &[], &[])?;
+                if let Some(coverage) = ctx.coverage() {
+                    coverage.instrument(&mut eval)?;
+                }
+                let result = eval.eval_function(init.value(), &[], &[]);
+                if let Some(coverage) = ctx.coverage() {
+                    coverage.record(&eval)?;
+                }
+                result?;
             }
             module.into_module().freeze()?
         };
         frozen_heap.add_reference(module.frozen_heap());
 
-        let observer_data = {
-            let invocation_data = RetainedData::get_from(&module);
-            let intents = invocation_data.intents();
-            let mut observer_data = ObserverData::with_capacity(intents.len());
-            intents.iter().for_each(|intent| {
-                if let Intent::Observe {
-                    event_kind,
-                    observer,
-                } = intent
-                {
-                    let observer = observer.dupe();
-                    match event_kind {
-                        EventKind::OpenProject => observer_data.add_open_project_observer(observer),
-                        EventKind::OpenFile => observer_data.add_open_file_observer(observer),
-                        EventKind::Match => panic!("internal error: query_match not observable"),
-                        EventKind::PreTestRun => observer_data.add_pre_test_run_observer(observer),
-                        EventKind::PostTestRun => {
-                            observer_data.add_post_test_run_observer(observer)
-                        }
-                    }
+        let invocation_data = RetainedData::get_from(&module);
+        let intents = invocation_data.intents();
+        let mut observer_data = ObserverData::with_capacity(intents.len());
+        let mut lint_declarations = vec![];
+        intents.iter().for_each(|intent| match intent {
+            Intent::Observe {
+                event_kind,
+                observer,
+            } => {
+                let observer = observer.dupe();
+                match event_kind {
+                    EventKind::OpenProject => observer_data.add_open_project_observer(observer),
+                    EventKind::OpenFile => observer_data.add_open_file_observer(observer),
+                    EventKind::Match => panic!("internal error: query_match not observable"),
+                    EventKind::PreTestRun => observer_data.add_pre_test_run_observer(observer),
+                    EventKind::PostTestRun => observer_data.add_post_test_run_observer(observer),
                 }
-            });
-            observer_data
-        };
+            }
+            Intent::DeclareLint(declaration) => lint_declarations.push(declaration.clone()),
+            _ => {}
+        });
         if observer_data.len() == 0 {
             crate::warn!("{} observes no events", path);
         }
-        Ok(observer_data)
+        Ok((observer_data, lint_declarations))
     }
 }
 
@@ -572,6 +606,15 @@ mod tests {
         );
         test_preiniting_availability("vex.active", Unavailable, "vex.active('some-id')");
         test_preiniting_availability("vex.warn", Unavailable, "vex.warn('test', 'oh no!')");
+        test_preiniting_availability("vex.declare_lint", Unavailable, "vex.declare_lint('test')");
+        test_preiniting_availability("vex.read_file", Unavailable, "vex.read_file('vex.toml')");
+        test_preiniting_availability("vex.exists", Unavailable, "vex.exists('vex.toml')");
+        test_preiniting_availability("vex.glob", Unavailable, "vex.glob('*')");
+        test_preiniting_availability(
+            "vex.parse",
+            Unavailable,
+            "vex.parse('vex.toml', language='python')",
+        );
 
         let assert_available_initing = |name, call| {
             VexTest::new(format!("initing-{name}"))
@@ -603,6 +646,7 @@ mod tests {
                             {call}
                     "#},
                 )
+                .with_source_file("data.txt", "data\n")
                 .try_run();
             match availability {
                 Available => drop(result.unwrap()),
@@ -628,6 +672,15 @@ mod tests {
         );
         test_vexing_open_availability("vex.active", Available, "vex.active('some-id')");
         test_vexing_open_availability("vex.warn", Available, "vex.warn('test', 'oh no!')");
+        test_vexing_open_availability("vex.declare_lint", Unavailable, "vex.declare_lint('test')");
+        test_vexing_open_availability("vex.read_file", Available, "vex.read_file('data.txt')");
+        test_vexing_open_availability("vex.exists", Available, "vex.exists('data.txt')");
+        test_vexing_open_availability("vex.glob", Available, "vex.glob('*')");
+        test_vexing_open_availability(
+            "vex.parse",
+            Available,
+            "vex.parse('data.txt', language='python')",
+        );
 
         let test_vexing_match_availability = |name, availability, call| {
             let result = VexTest::new(format!("vexing-{name}"))
@@ -681,6 +734,15 @@ mod tests {
         );
         test_vexing_match_availability("vex.active", Unavailable, "vex.active('some-id')");
         test_vexing_match_availability("vex.warn", Available, "vex.warn('test', 'oh no!')");
+        test_vexing_match_availability("vex.declare_lint", Unavailable, "vex.declare_lint('test')");
+        test_vexing_match_availability(
+            "vex.read_file",
+            Unavailable,
+            "vex.read_file('src/main.rs')",
+        );
+        test_vexing_match_availability("vex.exists", Unavailable, "vex.exists('src/main.rs')");
+        test_vexing_match_availability("vex.glob", Unavailable, "vex.glob('src/*.rs')");
+        test_vexing_match_availability("vex.parse", Unavailable, "vex.parse('src/main.rs')");
     }
 
     #[test]
@@ -690,6 +752,97 @@ mod tests {
             .returns_error("not found")
     }
 
+    #[test]
+    fn library_extensions() {
+        VexTest::new("library-extensions")
+            .with_scriptlet(
+                "vexes/test.star",
+                formatdoc! {r#"
+                    load('{check_path}', 'check')
+
+                    Point = record(x=int, y=field(int, 0))
+                    Colour = enum('red', 'green')
+
+                    def add(a, b):
+                        return a + b
+
+                    def check_extensions():
+                        check['eq'](json.encode({{'a': [1, None]}}), '{{"a":[1,null]}}')
+                        check['eq'](json.decode('{{"b": true}}'), {{'b': True}})
+                        check['eq'](struct(a=1).a, 1)
+                        check['eq'](Point(x=1).y, 0)
+                        check['eq'](Colour('green').index, 1)
+                        check['eq'](partial(add, 1)(2), 3)
+                        check['true'](isinstance(add, typing.Callable))
+
+                    check_extensions()
+                "#,
+                    check_path = VexTest::CHECK_STARLARK_PATH,
+                },
+            )
+            .assert_irritation_free();
+    }
+
+    #[test]
+    fn typecheck() {
+        VexTest::new("typed")
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+
+                    def on_open_project(event):
+                        vex.search('rust', '(integer_literal) @lit', on_match)
+
+                    def on_match(event):
+                        lit = event.captures['lit']
+                        if count_digits(str(lit)) > 5:
+                            vex.warn('big-number', 'big number', at=(lit, 'here'))
+
+                    def count_digits(text: str) -> int:
+                        return len(text)
+                "#},
+            )
+            .with_source_file("src/main.rs", "fn main() { 1; }")
+            .assert_irritation_free();
+
+        VexTest::new("never-called")
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        pass
+
+                    def unused() -> int:
+                        return 'not an int'
+                "#},
+            )
+            .returns_error("Expected type `int` but got `str`");
+
+        VexTest::new("wrong-argument")
+            .with_scriptlet(
+                "vexes/lib.star",
+                indoc! {r#"
+                    def double(x: int) -> int:
+                        return 2 * x
+                "#},
+            )
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    load('lib.star', 'double')
+
+                    def init():
+                        pass
+
+                    def unused():
+                        return double('2')
+                "#},
+            )
+            .returns_error("Expected type `int` but got `str`");
+    }
+
     #[test]
     fn loads() {
         VexTest::new("valid-absolute")
@@ -57,6 +57,16 @@ impl ScriptSource for FileSource {
     }
 }
 
+impl<S: ScriptSource> ScriptSource for &S {
+    fn path(&self) -> &Utf8Path {
+        (*self).path()
+    }
+
+    fn content(&self) -> Result<String> {
+        (*self).content()
+    }
+}
+
 pub fn sources_in_dir(dir_path: &Utf8Path) -> Result<Vec<FileSource>> {
     if !dir_path.is_dir() {
         return Err(Error::NoVexesDir(PrettyPath::new(dir_path)));
@@ -1,21 +1,23 @@
 use std::{
-    collections::BTreeMap,
+    collections::{BTreeMap, BTreeSet},
     ops::Deref,
     sync::{Mutex, MutexGuard},
 };
 
 use camino::{Utf8Path, Utf8PathBuf};
+use dupe::Dupe;
 use log::{info, log_enabled};
 use starlark::values::FrozenHeap;
 
 use crate::{
     context::Context,
     error::Error,
+    fingerprint::Fingerprint,
     result::Result,
     scriptlets::{
         scriptlet::{InitingScriptlet, PreinitingScriptlet},
         source::ScriptSource,
-        ObserverData, ScriptArgsValueMap,
+        LintDeclaration, ObserverData, ScriptArgsValueMap,
     },
     source_path::PrettyPath,
     verbosity::Verbosity,
@@ -24,6 +26,7 @@ use crate::{
 #[derive(Debug)]
 pub struct PreinitingStore {
     store: Vec<PreinitingScriptlet>,
+    fingerprint: Fingerprint,
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
@@ -31,7 +34,7 @@ struct StoreIndex(usize);
 
 impl PreinitingStore {
     pub fn new<S: ScriptSource>(scripts: &[S]) -> Result<Self> {
-        let store: Vec<_> = scripts
+        let mut sources: Vec<_> = scripts
             .iter()
             .map(|source| Result::Ok((source.path(), source.content()?)))
             .inspect(|content_result| {
@@ -42,15 +45,24 @@ impl PreinitingStore {
                 }
             })
             .flatten()
+            .collect();
+        sources.sort_by(|(path1, _), (path2, _)| path1.cmp(path2));
+        let fingerprint = Fingerprint::of_parts(
+            sources
+                .iter()
+                .flat_map(|(path, content)| [path.as_str().as_bytes(), content.as_bytes()]),
+        );
+        let store = sources
+            .into_iter()
             .map(|(path, content)| PreinitingScriptlet::new(path.to_owned(), content))
             .collect::<Result<_>>()?;
-        Ok(Self { store })
+        Ok(Self { store, fingerprint })
     }
 
     pub fn preinit(mut self, ctx: &Context, opts: PreinitOptions<'_>) -> Result<InitingStore> {
         self.store.sort_by(|sc1, sc2| sc1.path.cmp(&sc2.path));
         self.topographic_sort()?;
-        let Self { store } = self;
+        let Self { store, fingerprint } = self;
 
         let frozen_heap = FrozenHeap::new();
         let mut partial_store = PreinitedModuleStore::new();
@@ -61,7 +73,38 @@ impl PreinitingStore {
         }
 
         let store = partial_store.into_entry_modules().collect();
-        Ok(InitingStore { store, frozen_heap })
+        Ok(InitingStore {
+            store,
+            fingerprint,
+            frozen_heap,
+        })
+    }
+
+    /// The paths of the scriptlets which each scriptlet loads, directly or transitively, including
+    /// its own.
+    pub fn dependencies(&self) -> BTreeMap<PrettyPath, BTreeSet<Utf8PathBuf>> {
+        let load_edges = self.get_load_edges();
+        self.store
+            .iter()
+            .enumerate()
+            .map(|(idx, scriptlet)| {
+                let mut explored = vec![false; self.store.len()];
+                let mut to_explore = vec![StoreIndex(idx)];
+                while let Some(node) = to_explore.pop() {
+                    if !explored[node.0] {
+                        explored[node.0] = true;
+                        to_explore.extend(&load_edges[node.0]);
+                    }
+                }
+                let dependencies = explored
+                    .into_iter()
+                    .zip(&self.store)
+                    .filter(|(explored, _)| *explored)
+                    .map(|(_, dependency)| dependency.path.clone())
+                    .collect();
+                (PrettyPath::new(&scriptlet.path), dependencies)
+            })
+            .collect()
     }
 
     /// Topographically order the store
@@ -241,26 +284,41 @@ impl PreinitedModuleStore {
 #[derive(Debug)]
 pub struct InitingStore {
     store: Vec<InitingScriptlet>,
+    fingerprint: Fingerprint,
     frozen_heap: FrozenHeap,
 }
 
 impl InitingStore {
     pub fn init(self, ctx: &Context, opts: InitOptions<'_>) -> Result<VexingStore> {
-        let Self { store, frozen_heap } = self;
+        let Self {
+            store,
+            fingerprint,
+            frozen_heap,
+        } = self;
         let num_scripts = store.len();
 
-        let observer_data = store.into_iter().try_fold(
-            ObserverData::with_capacity(4 * num_scripts),
-            |mut data, scriptlet| {
-                data.extend(scriptlet.init(ctx, &opts, &frozen_heap)?);
-                Result::Ok(data)
-            },
-        )?;
+        let mut observer_data = ObserverData::with_capacity(4 * num_scripts);
+        let mut test_observer_data = BTreeMap::new();
+        let mut lint_declarations = BTreeMap::new();
+        for scriptlet in store {
+            let path = PrettyPath::new(&scriptlet.path);
+            let (mut scriptlet_observer_data, scriptlet_lint_declarations) =
+                scriptlet.init(ctx, &opts, &frozen_heap)?;
+            let scriptlet_test_observer_data = scriptlet_observer_data.take_test_observers();
+            if scriptlet_test_observer_data.len() != 0 {
+                test_observer_data.insert(path.dupe(), scriptlet_test_observer_data);
+            }
+            observer_data.extend(scriptlet_observer_data);
+            lint_declarations.insert(path, scriptlet_lint_declarations);
+        }
 
         let frozen_heap = Mutex::new(frozen_heap);
         Ok(VexingStore {
             num_scripts,
+            fingerprint,
             observer_data,
+            test_observer_data,
+            lint_declarations,
             frozen_heap,
         })
     }
@@ -275,7 +333,10 @@ pub struct InitOptions<'args> {
 #[derive(Debug)]
 pub struct VexingStore {
     num_scripts: usize,
+    fingerprint: Fingerprint,
     observer_data: ObserverData,
+    test_observer_data: BTreeMap<PrettyPath, ObserverData>,
+    lint_declarations: BTreeMap<PrettyPath, Vec<LintDeclaration>>,
     frozen_heap: Mutex<FrozenHeap>,
 }
 
@@ -284,6 +345,22 @@ impl VexingStore {
         self.frozen_heap.lock().expect("frozen heap lock poisoned")
     }
 
+    /// A fingerprint of the paths and sources of all scriptlets in this store.
+    pub fn fingerprint(&self) -> Fingerprint {
+        self.fingerprint
+    }
+
+    /// The observers of test events declared by each scriptlet which has any.
+    pub fn test_observer_data(&self) -> &BTreeMap<PrettyPath, ObserverData> {
+        &self.test_observer_data
+    }
+
+    /// The lints declared by each scriptlet, including an empty entry for each which declares
+    /// none.
+    pub fn lint_declarations(&self) -> &BTreeMap<PrettyPath, Vec<LintDeclaration>> {
+        &self.lint_declarations
+    }
+
     pub fn project_queries_hint(&self) -> usize {
         // Heuristic: expect scriptlets to declare on average at most this many queries during the
         // `open_project` event.
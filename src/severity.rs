@@ -0,0 +1,181 @@
+use std::{fmt::Display, ops::AddAssign, str::FromStr};
+
+use allocative::Allocative;
+use annotate_snippets::AnnotationType;
+use dupe::Dupe;
+use serde::{Deserialize, Serialize};
+use strum::{EnumIter, IntoEnumIterator};
+
+use crate::{
+    error::Error, irritation::Irritation, plural::Plural, result::Result, suggestion::suggest,
+};
+
+/// How seriously a problem should be taken. Problems at `Note` level are advisory and do not
+/// cause `vex check` to fail.
+#[derive(
+    Copy,
+    Clone,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    EnumIter,
+    Allocative,
+    Dupe,
+    Serialize,
+    Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Note,
+
+    #[default]
+    Warning,
+
+    Error,
+}
+
+impl Severity {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Note => "note",
+            Self::Warning => "warning",
+            Self::Error => "error",
+        }
+    }
+
+    pub fn annotation_type(&self) -> AnnotationType {
+        match self {
+            Self::Note => AnnotationType::Note,
+            Self::Warning => AnnotationType::Warning,
+            Self::Error => AnnotationType::Error,
+        }
+    }
+}
+
+impl FromStr for Severity {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::iter()
+            .find(|severity| severity.name() == s)
+            .ok_or_else(|| Error::UnknownSeverity {
+                name: s.to_owned(),
+                suggestion: suggest(s, Self::iter().map(|severity| severity.name())),
+            })
+    }
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.name().fmt(f)
+    }
+}
+
+/// The number of problems found at each severity.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct SeverityCounts {
+    notes: usize,
+    warnings: usize,
+    errors: usize,
+}
+
+impl SeverityCounts {
+    pub fn of(irritations: &[Irritation]) -> Self {
+        let mut ret = Self::default();
+        irritations
+            .iter()
+            .for_each(|irritation| ret.add(irritation.level(), 1));
+        ret
+    }
+
+    pub fn add(&mut self, severity: Severity, num: usize) {
+        match severity {
+            Severity::Note => self.notes += num,
+            Severity::Warning => self.warnings += num,
+            Severity::Error => self.errors += num,
+        }
+    }
+
+    pub fn highest(&self) -> Option<Severity> {
+        [
+            (Severity::Error, self.errors),
+            (Severity::Warning, self.warnings),
+            (Severity::Note, self.notes),
+        ]
+        .into_iter()
+        .find(|(_, num)| *num != 0)
+        .map(|(severity, _)| severity)
+    }
+}
+
+impl AddAssign for SeverityCounts {
+    fn add_assign(&mut self, rhs: Self) {
+        self.notes += rhs.notes;
+        self.warnings += rhs.warnings;
+        self.errors += rhs.errors;
+    }
+}
+
+impl Display for SeverityCounts {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let parts: Vec<_> = [
+            (self.errors, "error", "errors"),
+            (self.warnings, "warning", "warnings"),
+            (self.notes, "note", "notes"),
+        ]
+        .into_iter()
+        .filter(|(num, _, _)| *num != 0)
+        .map(|(num, singular, plural)| Plural::new(num, singular, plural).to_string())
+        .collect();
+        match parts.as_slice() {
+            [] => write!(f, "no problems"),
+            [part] => write!(f, "{part}"),
+            [init @ .., last] => write!(f, "{} and {last}", init.join(", ")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        for severity in Severity::iter() {
+            assert_eq!(severity.name().parse::<Severity>().unwrap(), severity);
+        }
+        assert_eq!(
+            "eror".parse::<Severity>().unwrap_err().to_string(),
+            "unknown level 'eror' (did you mean 'error'?), expected one of: note, warning, error"
+        );
+    }
+
+    #[test]
+    fn counts() {
+        let mut counts = SeverityCounts::default();
+        assert_eq!(counts.highest(), None);
+        assert_eq!(counts.to_string(), "no problems");
+
+        counts.add(Severity::Note, 2);
+        assert_eq!(counts.highest(), Some(Severity::Note));
+        assert_eq!(counts.to_string(), "2 notes");
+
+        counts.add(Severity::Error, 1);
+        assert_eq!(counts.highest(), Some(Severity::Error));
+        assert_eq!(counts.to_string(), "1 error and 2 notes");
+
+        counts.add(Severity::Warning, 3);
+        assert_eq!(counts.highest(), Some(Severity::Error));
+        assert_eq!(counts.to_string(), "1 error, 3 warnings and 2 notes");
+    }
+
+    #[test]
+    fn order() {
+        assert!(Severity::Note < Severity::Warning);
+        assert!(Severity::Warning < Severity::Error);
+    }
+}
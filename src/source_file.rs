@@ -1,8 +1,9 @@
-use std::{fs, ops::Range};
+use std::{collections::BTreeMap, fs, ops::Range};
 
 use allocative::Allocative;
-use camino::{Utf8Path, Utf8PathBuf};
+use camino::{Utf8Component, Utf8Path, Utf8PathBuf};
 use dupe::{Dupe, OptionDupedExt};
+use glob::{MatchOptions, Pattern};
 use log::{info, log_enabled};
 use tree_sitter::{Node as TSNode, Parser, QueryCursor, Tree};
 use walkdir::WalkDir;
@@ -15,7 +16,7 @@ use crate::{
     language::Language,
     result::{RecoverableResult, Result},
     scriptlets::{Location, Node},
-    source_path::SourcePath,
+    source_path::{PrettyPath, SourcePath},
     trigger::FilePattern,
 };
 
@@ -23,26 +24,164 @@ pub fn sources_in_dir(
     ctx: &Context,
     max_concurrent_files: MaxConcurrentFileLimit,
 ) -> Result<Vec<SourceFile>> {
-    let ignores: Vec<_> = ctx
-        .files
-        .ignores
-        .clone()
-        .into_inner()
-        .into_iter()
-        .map(|ignore| ignore.compile())
-        .collect::<Result<_>>()?;
-    let allows: Vec<_> = ctx
-        .files
-        .allows
-        .clone()
-        .into_iter()
-        .map(|allow| allow.compile())
-        .collect::<Result<_>>()?;
+    let filter = PathFilter::new(ctx)?;
+    let associations = ctx.associations()?;
+    walk(ctx, &ctx.project_root, &filter, max_concurrent_files)
+        .map(|source_path| {
+            let language = associations.get_language(&source_path)?;
+            Ok(SourceFile::new(source_path, language.duped()))
+        })
+        .collect()
+}
+
+/// Find the source files at or under the given absolute paths, which must be in the project.
+pub fn sources_at(
+    ctx: &Context,
+    paths: &[Utf8PathBuf],
+    max_concurrent_files: MaxConcurrentFileLimit,
+) -> Result<Vec<SourceFile>> {
+    let filter = PathFilter::new(ctx)?;
     let associations = ctx.associations()?;
+    let canonical_root = ctx
+        .project_root
+        .canonicalize_utf8()
+        .map_err(|cause| Error::IO {
+            path: ctx.project_root.dupe(),
+            action: IOAction::Read,
+            cause,
+        })?;
+
+    let mut source_paths = BTreeMap::new();
+    for path in paths {
+        let canonical_path = path.canonicalize_utf8().map_err(|cause| Error::IO {
+            path: PrettyPath::new(path),
+            action: IOAction::Read,
+            cause,
+        })?;
+        let rel_path = canonical_path
+            .strip_prefix(&canonical_root)
+            .map_err(|_| Error::NotACheckPath(PrettyPath::new(path)))?;
+        let path = ctx.project_root.join(rel_path);
+        if !filter.admits_with_ancestors(&path, canonical_path.is_dir()) {
+            continue;
+        }
+
+        walk(ctx, &path, &filter, max_concurrent_files).for_each(|source_path| {
+            source_paths.insert(source_path.pretty_path.dupe(), source_path);
+        });
+    }
+
+    source_paths
+        .into_values()
+        .map(|source_path| {
+            let language = associations.get_language(&source_path)?;
+            Ok(SourceFile::new(source_path, language.duped()))
+        })
+        .collect()
+}
+
+/// Create a source file for the given absolute path whose content is held in memory rather than
+/// read from disk. Returns `None` if the path is outside of the project or ignored.
+pub fn source_with_content(
+    ctx: &Context,
+    path: &Utf8Path,
+    content: String,
+) -> Result<Option<SourceFile>> {
+    if !path.starts_with(ctx.project_root.as_str()) {
+        return Ok(None);
+    }
+    if !PathFilter::new(ctx)?.admits_with_ancestors(path, false) {
+        return Ok(None);
+    }
+    let source_path = SourcePath::new(path, &ctx.project_root);
+    let language = ctx.associations()?.get_language(&source_path)?.duped();
+    Ok(Some(SourceFile {
+        content: Some(content),
+        ..SourceFile::new(source_path, language)
+    }))
+}
+
+/// Resolve a path given relative to the project root, refusing any which would leave the project
+/// or which would not be checked, such as hidden or ignored files.
+pub fn project_path(ctx: &Context, path: &str) -> Result<SourcePath> {
+    let outside_project = || Error::OutsideProject(PrettyPath::from(path));
+
+    let mut rel_path = Utf8PathBuf::new();
+    for component in Utf8Path::new(path).components() {
+        match component {
+            Utf8Component::Normal(component) => rel_path.push(component),
+            Utf8Component::CurDir => {}
+            Utf8Component::ParentDir | Utf8Component::RootDir | Utf8Component::Prefix(_) => {
+                return Err(outside_project())
+            }
+        }
+    }
+    let source_path = SourcePath::new_in(&rel_path, &ctx.project_root);
+
+    // Symlinks within the project may still point outside of it.
+    if let Ok(canonical_path) = source_path.abs_path.canonicalize_utf8() {
+        let canonical_root = ctx
+            .project_root
+            .canonicalize_utf8()
+            .map_err(|cause| Error::IO {
+                path: ctx.project_root.dupe(),
+                action: IOAction::Read,
+                cause,
+            })?;
+        if !canonical_path.starts_with(canonical_root) {
+            return Err(outside_project());
+        }
+    }
+
+    // Vexes only see the files which `sources_matching` could return.
+    let is_dir = source_path.abs_path.is_dir();
+    if !PathFilter::new(ctx)?.admits_with_ancestors(&source_path.abs_path, is_dir) {
+        return Err(Error::IgnoredPath(source_path.pretty_path));
+    }
+    Ok(source_path)
+}
 
-    let root = ctx.project_root.as_str();
+/// Find the files which would be checked whose paths relative to the project root match the
+/// given glob, in order.
+pub fn sources_matching(ctx: &Context, pattern: &str) -> Result<Vec<SourcePath>> {
+    let compiled_pattern = Pattern::new(pattern).map_err(|cause| Error::Pattern {
+        pattern: pattern.into(),
+        cause_pos_offset: 0,
+        cause,
+    })?;
+    let match_options = MatchOptions {
+        require_literal_separator: true,
+        ..MatchOptions::new()
+    };
+    let project_sources = ctx.project_sources(|| {
+        let filter = PathFilter::new(ctx)?;
+        let mut source_paths: Vec<_> = walk(
+            ctx,
+            &ctx.project_root,
+            &filter,
+            MaxConcurrentFileLimit::default(),
+        )
+        .collect();
+        source_paths.sort_by(|path1, path2| path1.pretty_path.cmp(&path2.pretty_path));
+        Ok(source_paths)
+    })?;
+    let source_paths = project_sources
+        .iter()
+        .filter(|source_path| {
+            compiled_pattern.matches_with(source_path.pretty_path.as_str(), match_options)
+        })
+        .cloned()
+        .collect();
+    Ok(source_paths)
+}
 
-    WalkDir::new(root)
+fn walk<'a>(
+    ctx: &'a Context,
+    from: &Utf8Path,
+    filter: &'a PathFilter<'a>,
+    max_concurrent_files: MaxConcurrentFileLimit,
+) -> impl Iterator<Item = SourcePath> + 'a {
+    WalkDir::new(from)
         .follow_links(false)
         .follow_root_links(false)
         .max_open(max_concurrent_files.into())
@@ -52,70 +191,120 @@ pub fn sources_in_dir(
                 Some(p) => p,
                 _ => return false,
             };
+            filter.admits(entry_path, entry.file_type().is_dir())
+        })
+        .flatten()
+        .filter(|entry| entry.file_type().is_file())
+        .flat_map(|entry| Utf8PathBuf::from_path_buf(entry.path().to_owned()))
+        .map(|entry_path| SourcePath::new(&entry_path, &ctx.project_root))
+}
 
-            let is_root = entry_path == root;
+struct PathFilter<'a> {
+    root: &'a str,
+    ignores: Vec<FilePattern>,
+    allows: Vec<FilePattern>,
+}
 
-            let is_hidden = entry_path
-                .file_name()
-                .is_some_and(|file_name| file_name.starts_with('.'));
-            if is_hidden && !is_root {
-                if log_enabled!(log::Level::Info) {
-                    let dir_marker = if entry.file_type().is_dir() { "/" } else { "" };
-                    info!("ignoring {entry_path}{dir_marker}: hidden");
-                }
-                return false;
+impl<'a> PathFilter<'a> {
+    fn new(ctx: &'a Context) -> Result<Self> {
+        let ignores = ctx
+            .files
+            .ignores
+            .clone()
+            .into_inner()
+            .into_iter()
+            .map(|ignore| ignore.compile())
+            .collect::<Result<_>>()?;
+        let allows = ctx
+            .files
+            .allows
+            .clone()
+            .into_iter()
+            .map(|allow| allow.compile())
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            root: ctx.project_root.as_str(),
+            ignores,
+            allows,
+        })
+    }
+
+    fn admits_with_ancestors(&self, path: &Utf8Path, is_dir: bool) -> bool {
+        path.ancestors()
+            .take_while(|ancestor| ancestor.starts_with(self.root))
+            .skip(1)
+            .all(|ancestor| self.admits(ancestor, true))
+            && self.admits(path, is_dir)
+    }
+
+    fn admits(&self, path: &Utf8Path, is_dir: bool) -> bool {
+        let Self {
+            root,
+            ignores,
+            allows,
+        } = self;
+        let is_root = path == *root;
+
+        let is_hidden = path
+            .file_name()
+            .is_some_and(|file_name| file_name.starts_with('.'));
+        if is_hidden && !is_root {
+            if log_enabled!(log::Level::Info) {
+                let dir_marker = if is_dir { "/" } else { "" };
+                info!("ignoring {path}{dir_marker}: hidden");
             }
+            return false;
+        }
 
-            let matches_any = |path, patterns: &[FilePattern]| {
-                patterns.iter().any(|pattern| pattern.matches(path))
-            };
-            if matches_any(entry_path, &ignores) && !matches_any(entry_path, &allows) {
-                if log_enabled!(log::Level::Info) {
-                    let dir_marker = if entry.file_type().is_dir() { "/" } else { "" };
-                    info!(
-                        "ignoring {}{dir_marker}: matches ignore pattern",
-                        entry_path.strip_prefix(root).unwrap_or(entry_path),
-                    );
-                }
-                return false;
+        let matches_any =
+            |path, patterns: &[FilePattern]| patterns.iter().any(|pattern| pattern.matches(path));
+        if matches_any(path, ignores) && !matches_any(path, allows) {
+            if log_enabled!(log::Level::Info) {
+                let dir_marker = if is_dir { "/" } else { "" };
+                info!(
+                    "ignoring {}{dir_marker}: matches ignore pattern",
+                    path.strip_prefix(root).unwrap_or(path),
+                );
             }
+            return false;
+        }
 
-            if !is_root
-                && entry.file_type().is_dir()
-                && entry_path.join(Manifest::FILE_NAME).exists()
-            {
-                if log_enabled!(log::Level::Info) {
-                    let dir_marker = if entry.file_type().is_dir() { "/" } else { "" };
-                    info!(
-                        "ignoring {}{dir_marker}: contains vex project",
-                        entry_path.strip_prefix(root).unwrap_or(entry_path),
-                    );
-                }
-                return false;
+        if !is_root && is_dir && path.join(Manifest::FILE_NAME).exists() {
+            if log_enabled!(log::Level::Info) {
+                info!(
+                    "ignoring {}/: contains vex project",
+                    path.strip_prefix(root).unwrap_or(path),
+                );
             }
-            true
-        })
-        .flatten()
-        .filter(|entry| entry.file_type().is_file())
-        .flat_map(|entry| Utf8PathBuf::from_path_buf(entry.path().to_owned()))
-        .map(|entry_path| SourcePath::new(&entry_path, &ctx.project_root))
-        .map(|source_path| {
-            let language = associations.get_language(&source_path)?;
-            Ok(SourceFile::new(source_path, language.duped()))
-        })
-        .collect()
+            return false;
+        }
+        true
+    }
 }
 
 #[derive(Debug)]
 pub struct SourceFile {
     path: SourcePath,
     language: Option<Language>,
+    content: Option<String>,
 }
 
 impl SourceFile {
     pub fn new(path: SourcePath, language: Option<Language>) -> Self {
         let path = path.dupe();
-        Self { path, language }
+        Self {
+            path,
+            language,
+            content: None,
+        }
+    }
+
+    /// Check the given content in place of that on disk.
+    pub fn with_content(self, content: String) -> Self {
+        Self {
+            content: Some(content),
+            ..self
+        }
     }
 
     pub fn path(&self) -> &SourcePath {
@@ -126,6 +315,11 @@ impl SourceFile {
         self.language.as_ref()
     }
 
+    /// The content to check in place of that on disk, if any.
+    pub fn content(&self) -> Option<&str> {
+        self.content.as_deref()
+    }
+
     pub fn parse(&self, ctx: &Context) -> Result<ParsedSourceFile> {
         if log_enabled!(log::Level::Info) {
             info!("parsing {}", self.path);
@@ -134,12 +328,14 @@ impl SourceFile {
             .language
             .as_ref()
             .ok_or_else(|| Error::NoParserForFile(self.path.pretty_path.dupe()))?;
-        let content =
-            fs::read_to_string(self.path.abs_path.as_str()).map_err(|cause| Error::IO {
+        let content = match &self.content {
+            Some(content) => content.clone(),
+            None => fs::read_to_string(self.path.abs_path.as_str()).map_err(|cause| Error::IO {
                 path: self.path.pretty_path.dupe(),
                 action: IOAction::Read,
                 cause,
-            })?;
+            })?,
+        };
 
         let language_data = match ctx.language_data(language)? {
             Some(language_data) => language_data,
@@ -339,7 +535,7 @@ impl Eq for ParsedSourceFile {}
 
 #[cfg(test)]
 mod tests {
-    use std::{fs::File, io::Write};
+    use std::{cell::Cell, fs::File, io::Write};
 
     use indoc::indoc;
 
@@ -391,6 +587,208 @@ mod tests {
         assert_eq!(returned_paths, expected_paths);
     }
 
+    #[test]
+    fn matching_sources_found_once() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let tempdir_path = Utf8PathBuf::try_from(tempdir.path().to_owned()).unwrap();
+        let write_file = |path: &str, content: &str| {
+            let abs_path = tempdir_path.join(path);
+            fs::create_dir_all(abs_path.parent().unwrap()).unwrap();
+            File::create(abs_path)
+                .unwrap()
+                .write_all(content.as_bytes())
+                .unwrap();
+        };
+        write_file("vex.toml", "[vex]\nversion = \"1\"");
+        write_file("src/main.rs", "fn main() {}");
+
+        let ctx = Context::acquire_in(&tempdir_path).unwrap();
+        let matching_paths = |pattern| -> Vec<_> {
+            sources_matching(&ctx, pattern)
+                .unwrap()
+                .into_iter()
+                .map(|source_path| source_path.pretty_path.to_string())
+                .collect()
+        };
+        assert_eq!(matching_paths("**/*.rs"), ["src/main.rs"]);
+
+        write_file("src/lib.rs", "fn lib() {}");
+        assert_eq!(matching_paths("**/*.rs"), ["src/main.rs"]);
+        assert_eq!(matching_paths("src/*"), ["src/main.rs"]);
+
+        ctx.forget_project_files();
+        assert_eq!(matching_paths("**/*.rs"), ["src/lib.rs", "src/main.rs"]);
+    }
+
+    #[test]
+    fn project_files_parsed_once() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let tempdir_path = Utf8PathBuf::try_from(tempdir.path().to_owned()).unwrap();
+        fs::write(tempdir_path.join("vex.toml"), "[vex]\nversion = \"1\"").unwrap();
+        fs::write(tempdir_path.join("main.rs"), "fn main() {}").unwrap();
+
+        let ctx = Context::acquire_in(&tempdir_path).unwrap();
+        let num_parses = Cell::new(0);
+        let parsed_content = || {
+            let source_path = project_path(&ctx, "main.rs").unwrap();
+            ctx.parsed_project_file(&source_path.pretty_path, &Language::Rust, || {
+                num_parses.set(num_parses.get() + 1);
+                SourceFile::new(source_path.dupe(), Some(Language::Rust)).parse(&ctx)
+            })
+            .unwrap()
+            .content
+            .clone()
+        };
+        assert_eq!(parsed_content(), "fn main() {}");
+        assert_eq!(parsed_content(), "fn main() {}");
+        assert_eq!(num_parses.get(), 1);
+
+        fs::write(tempdir_path.join("main.rs"), "fn main() { changed(); }").unwrap();
+        assert_eq!(parsed_content(), "fn main() {}");
+
+        ctx.forget_project_files();
+        assert_eq!(parsed_content(), "fn main() { changed(); }");
+        assert_eq!(num_parses.get(), 2);
+    }
+
+    #[test]
+    fn specific_paths() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let tempdir_path = Utf8PathBuf::try_from(tempdir.path().to_owned()).unwrap();
+
+        let manifest_content: &str = indoc! {r#"
+            [vex]
+            version = "1"
+
+            [files]
+            ignore = [ "to-ignore/" ]
+        "#};
+        let files = [
+            ("vex.toml", manifest_content),
+            ("src/main.rs", "fn main() {}"),
+            ("src/lib.rs", "fn lib() {}"),
+            ("tests/test.rs", "fn test() {}"),
+            ("tests/data/data.py", "data = None"),
+            ("to-ignore/ignored.rs", "fn ignored() {}"),
+            (".hidden/hidden.rs", "fn hidden() {}"),
+            ("sub-project/vex.toml", manifest_content),
+            ("sub-project/sub-project-file.rs", "fn sub_project() {}"),
+        ];
+        for (path, content) in files {
+            let abs_path = tempdir_path.join(path);
+            fs::create_dir_all(abs_path.parent().unwrap()).unwrap();
+            File::create(abs_path)
+                .unwrap()
+                .write_all(content.as_bytes())
+                .unwrap();
+        }
+
+        let ctx = Context::acquire_in(&tempdir_path).unwrap();
+        let paths: Vec<_> = [
+            "src/main.rs",
+            "tests",
+            "tests/test.rs",
+            "to-ignore/ignored.rs",
+            ".hidden/hidden.rs",
+            "sub-project/sub-project-file.rs",
+        ]
+        .into_iter()
+        .map(|path| tempdir_path.join(path))
+        .collect();
+        let sources = sources_at(&ctx, &paths, MaxConcurrentFileLimit::new(1)).unwrap();
+        let returned = sources
+            .iter()
+            .map(|source_file| {
+                (
+                    source_file.path().pretty_path.as_str(),
+                    source_file.language().cloned(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            returned,
+            [
+                ("src/main.rs", Some(Language::Rust)),
+                ("tests/data/data.py", Some(Language::Python)),
+                ("tests/test.rs", Some(Language::Rust)),
+            ]
+        );
+
+        let outside_dir = tempfile::tempdir().unwrap();
+        let outside_path = Utf8PathBuf::try_from(outside_dir.path().to_owned()).unwrap();
+        let err = sources_at(&ctx, &[outside_path], MaxConcurrentFileLimit::new(1)).unwrap_err();
+        assert!(
+            matches!(err, Error::NotACheckPath(_)),
+            "unexpected error: {err}"
+        );
+
+        let err = sources_at(
+            &ctx,
+            &[tempdir_path.join("i-do-not-exist")],
+            MaxConcurrentFileLimit::new(1),
+        )
+        .unwrap_err();
+        assert!(matches!(err, Error::IO { .. }), "unexpected error: {err}");
+    }
+
+    #[test]
+    fn project_paths() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let tempdir_path = Utf8PathBuf::try_from(tempdir.path().to_owned()).unwrap();
+        fs::write(
+            tempdir_path.join("vex.toml"),
+            "[vex]\nversion = '1'\n[files]\nignore = ['to-ignore/']",
+        )
+        .unwrap();
+        fs::create_dir(tempdir_path.join("src")).unwrap();
+        fs::write(tempdir_path.join("src/main.rs"), "fn main() {}").unwrap();
+        fs::create_dir(tempdir_path.join("sub-project")).unwrap();
+        fs::write(tempdir_path.join("sub-project/vex.toml"), "").unwrap();
+
+        let ctx = Context::acquire_in(&tempdir_path).unwrap();
+        for (path, expected) in [
+            ("src/main.rs", "src/main.rs"),
+            ("./src//main.rs", "src/main.rs"),
+            ("src/lib.rs", "src/lib.rs"),
+        ] {
+            let source_path = project_path(&ctx, path).unwrap();
+            assert_eq!(source_path.pretty_path.as_str(), expected);
+            assert_eq!(*source_path.abs_path, tempdir_path.join(expected));
+        }
+
+        for path in ["..", "src/../../main.rs", "/etc/passwd"] {
+            let err = project_path(&ctx, path).unwrap_err();
+            assert!(
+                matches!(err, Error::OutsideProject(_)),
+                "unexpected error for {path}: {err}"
+            );
+        }
+
+        for path in [
+            ".git/config",
+            ".hidden",
+            "to-ignore/file.rs",
+            "sub-project/vex.toml",
+        ] {
+            let err = project_path(&ctx, path).unwrap_err();
+            assert!(
+                matches!(err, Error::IgnoredPath(_)),
+                "unexpected error for {path}: {err}"
+            );
+        }
+
+        #[cfg(unix)]
+        {
+            let outside_dir = tempfile::tempdir().unwrap();
+            std::os::unix::fs::symlink(outside_dir.path(), tempdir_path.join("escape")).unwrap();
+            let err = project_path(&ctx, "escape").unwrap_err();
+            assert!(
+                matches!(err, Error::OutsideProject(_)),
+                "unexpected error: {err}"
+            );
+        }
+    }
+
     #[test]
     fn general_ignore_markers() {
         let ctx = Context::new_with_manifest("test-path".into(), Manifest::default());
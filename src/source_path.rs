@@ -6,7 +6,7 @@ use std::path;
 use allocative::Allocative;
 use camino::Utf8Path;
 use dupe::{Dupe, OptionDupedExt};
-use serde::Serialize;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use starlark::{
     environment::{Methods, MethodsBuilder, MethodsStatic},
     starlark_module, starlark_simple_value,
@@ -52,17 +52,7 @@ impl Display for SourcePath {
 }
 
 #[derive(
-    Clone,
-    Debug,
-    Dupe,
-    PartialEq,
-    Eq,
-    PartialOrd,
-    Ord,
-    Hash,
-    Allocative,
-    Serialize,
-    ProvidesStaticType,
+    Clone, Debug, Dupe, PartialEq, Eq, PartialOrd, Ord, Hash, Allocative, ProvidesStaticType,
 )]
 pub struct PrettyPath {
     #[allocative(skip)]
@@ -132,6 +122,25 @@ impl Deref for PrettyPath {
     }
 }
 
+impl Serialize for PrettyPath {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for PrettyPath {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let raw = String::deserialize(deserializer)?;
+        Ok(Self::new(Utf8Path::new(&raw)))
+    }
+}
+
 impl Display for PrettyPath {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         #[cfg(not(target_os = "windows"))]
@@ -1,47 +1,96 @@
 use std::{
     collections::BTreeMap,
     fs::{self, File},
-    io::Write,
+    io::{BufWriter, Write},
+    time::{Duration, Instant},
 };
 
 use camino::{Utf8Component, Utf8Path, Utf8PathBuf};
 use dupe::Dupe;
-use log::{error, log_enabled};
 use starlark::values::FrozenHeap;
 
+mod config;
+mod coverage;
+mod snapshot;
+
 use crate::{
-    associations::Associations,
-    cli::{MaxConcurrentFileLimit, MaxProblems},
-    context::{Context, Manifest},
+    cli::{MaxProblems, TestCmd},
+    context::Context,
     error::{Error, IOAction},
+    expectation::{self, Expectation},
+    irritation::Irritation,
+    language::Language,
     logger,
+    plural::Plural,
     result::Result,
     scan,
     scriptlets::{
         action::Action,
-        event::{PostTestRunEvent, PreTestRunEvent},
+        event::{EventKind, PostTestRunEvent, PreTestRunEvent},
         handler_module::HandlerModule,
         source::{self, ScriptSource},
-        InitOptions, Intent, Observable, ObserveOptions, PreinitOptions, PreinitingStore,
-        PrintHandler, ScriptArgsValueMap,
+        InitOptions, Intent, Observable, ObserveOptions, ObserverData, PreinitOptions,
+        PreinitingStore, PrintHandler, ScriptArgsValueMap,
     },
+    source_file::SourceFile,
     source_path::{PrettyPath, SourcePath},
     verbosity::Verbosity,
     warning_filter::WarningFilter,
 };
 
-pub fn test() -> Result<()> {
-    let ctx = Context::acquire()?;
+use self::snapshot::Snapshot;
+
+pub use self::{config::TestConfig, coverage::Coverage};
+
+pub fn test(cmd_args: TestCmd) -> Result<()> {
+    let mut ctx = Context::acquire()?;
+    if cmd_args.coverage.is_some() {
+        ctx.enable_coverage();
+    }
     let script_args_heap = FrozenHeap::new();
     let script_args = ScriptArgsValueMap::with_args(&ctx.script_args, &script_args_heap);
-    run_tests(
+    let results = run_tests(
         &ctx,
         RunTestOptions {
             lsp_enabled: ctx.manifest.run.lsp_enabled,
             script_args: &script_args,
             script_sources: &source::sources_in_dir(&ctx.vex_dir())?,
+            pattern: cmd_args.pattern.as_deref(),
+            update_snapshots: cmd_args.update_snapshots,
         },
     )?;
+
+    for result in &results {
+        println!(
+            "{:<4}  {:>8.3}s  {}",
+            if result.outcome.is_ok() { "ok" } else { "FAIL" },
+            result.duration.as_secs_f64(),
+            result.vex,
+        );
+    }
+    if let Some(junit_path) = &cmd_args.junit {
+        write_junit(junit_path, &results)?;
+    }
+    if let (Some(coverage_path), Some(coverage)) = (&cmd_args.coverage, ctx.coverage()) {
+        write_coverage(coverage_path, &ctx, coverage)?;
+    }
+
+    let num_failed = results
+        .iter()
+        .filter(|result| result.outcome.is_err())
+        .count();
+    for result in &results {
+        if let Err(err) = &result.outcome {
+            crate::error!("{} failed: {err}", result.vex);
+        }
+    }
+    if results.is_empty() {
+        if let Some(pattern) = &cmd_args.pattern {
+            crate::warn!("no tests match '{pattern}'");
+        }
+    } else if num_failed == 0 {
+        crate::success!("{} passed", Plural::new(results.len(), "test", "tests"));
+    }
     Ok(())
 }
 
@@ -49,17 +98,34 @@ pub(crate) struct RunTestOptions<'a, S> {
     pub(crate) lsp_enabled: bool,
     pub(crate) script_args: &'a ScriptArgsValueMap,
     pub(crate) script_sources: &'a [S],
+    pub(crate) pattern: Option<&'a str>,
+    pub(crate) update_snapshots: bool,
+}
+
+/// The outcome of running the test events of one vex.
+#[derive(Debug)]
+pub(crate) struct TestResult {
+    pub(crate) vex: PrettyPath,
+    pub(crate) duration: Duration,
+    pub(crate) outcome: Result<()>,
 }
 
+/// Run the tests declared by each vex which observes a test event. Each vex scans its files in a
+/// fresh directory with only itself and the vexes it loads active, so that one vex cannot affect
+/// the results of another.
 pub(crate) fn run_tests<S: ScriptSource>(
     ctx: &Context,
     run_test_opts: RunTestOptions<'_, S>,
-) -> Result<()> {
+) -> Result<Vec<TestResult>> {
     let RunTestOptions {
         lsp_enabled,
         script_args,
         script_sources,
+        pattern,
+        update_snapshots,
     } = run_test_opts;
+    let preiniting_store = PreinitingStore::new(script_sources)?;
+    let dependencies = preiniting_store.dependencies();
     let store = {
         let preinit_opts = PreinitOptions {
             script_args,
@@ -69,82 +135,144 @@ pub(crate) fn run_tests<S: ScriptSource>(
             script_args,
             verbosity: Verbosity::Quiet,
         };
-        PreinitingStore::new(script_sources)?
+        preiniting_store
             .preinit(ctx, preinit_opts)?
             .init(ctx, init_opts)?
     };
+    let warning_filter = WarningFilter::all();
+    let pre_test_run_print_handler =
+        PrintHandler::new(logger::verbosity(), EventKind::PreTestRun.name());
+    let post_test_run_print_handler =
+        PrintHandler::new(logger::verbosity(), EventKind::PostTestRun.name());
+    let observe_opts = |event_kind: EventKind| ObserveOptions {
+        action: Action::Vexing(event_kind),
+        file: None,
+        script_args,
+        ignore_markers: None,
+        lsp_enabled,
+        print_handler: match event_kind {
+            EventKind::PreTestRun => &pre_test_run_print_handler,
+            _ => &post_test_run_print_handler,
+        },
+        warning_filter: Some(&warning_filter),
+    };
 
-    let files_to_scan = {
-        let frozen_heap = FrozenHeap::new();
-        let event = PreTestRunEvent;
-        let handler_module = HandlerModule::new();
-        let warning_filter = WarningFilter::all();
-        let observe_opts = ObserveOptions {
-            action: Action::Vexing(event.kind()),
-            script_args,
-            ignore_markers: None,
-            lsp_enabled,
-            print_handler: &PrintHandler::new(logger::verbosity(), event.kind().name()),
-            warning_filter: Some(&warning_filter),
-        };
-        store.observers_for(event.kind()).observe(
-            ctx,
-            &handler_module,
-            handler_module.heap().alloc(event),
-            observe_opts,
-        )?;
-
-        let mut files_to_scan = Vec::with_capacity(handler_module.intent_count());
-        let mut seen_file_names = BTreeMap::new();
-        handler_module
-            .into_intents_on(&frozen_heap)?
-            .into_iter()
-            .for_each(|intent| match intent {
-                Intent::ScanFile {
-                    file_name,
-                    language,
-                    content,
-                } => {
-                    seen_file_names
-                        .entry(file_name.dupe())
-                        .and_modify(|count| *count += 1)
-                        .or_insert(1);
-                    files_to_scan.push((file_name, language, content));
+    let test_cases: Vec<_> = store
+        .test_observer_data()
+        .iter()
+        .map(|(vex, observer_data)| {
+            let start = Instant::now();
+            let files =
+                declared_test_files(ctx, observer_data, observe_opts(EventKind::PreTestRun));
+            TestCase {
+                vex: vex.dupe(),
+                observer_data,
+                duration: start.elapsed(),
+                files,
+            }
+        })
+        .filter(|test_case| pattern.map_or(true, |pattern| test_case.matches(pattern)))
+        .collect();
+
+    Ok(test_cases
+        .into_iter()
+        .map(|test_case| {
+            let TestCase {
+                vex,
+                observer_data,
+                mut duration,
+                files,
+            } = test_case;
+            let start = Instant::now();
+            let outcome = files.and_then(|files| {
+                let vex_sources: Vec<_> = script_sources
+                    .iter()
+                    .filter(|source| dependencies[&vex].contains(source.path()))
+                    .collect();
+                let mut configs: Vec<(&TestConfig, Vec<&TestFile>)> = vec![];
+                for file in &files {
+                    match configs
+                        .iter_mut()
+                        .find(|(config, _)| **config == file.config)
+                    {
+                        Some((_, config_files)) => config_files.push(file),
+                        None => configs.push((&file.config, vec![file])),
+                    }
                 }
-                _ => panic!("internal error: unexpected intent: {intent:?}"),
-            });
-        let mut test_run_invalid = false;
-        seen_file_names
-            .into_iter()
-            .filter(|(_, count)| *count > 1)
-            .for_each(|(file_name, count)| {
-                test_run_invalid = true;
-                if log_enabled!(log::Level::Error) {
-                    error!("test file '{file_name}' declared {count} times");
+                let mut irritations = vec![];
+                for (config, config_files) in configs {
+                    irritations.extend(scan_test_files(
+                        ctx,
+                        &vex_sources,
+                        script_args,
+                        config,
+                        &config_files,
+                    )?);
+                }
+                irritations.sort();
+                let mismatches: Vec<_> = files
+                    .iter()
+                    .flat_map(|file| file.mismatches(&irritations))
+                    .collect();
+                let snapshot = Snapshot::of(&irritations);
+
+                let handler_module = HandlerModule::new();
+                let event = PostTestRunEvent::new(irritations, handler_module.heap());
+                observer_data.observers_for(event.kind()).observe(
+                    ctx,
+                    &handler_module,
+                    handler_module.heap().alloc(event),
+                    observe_opts(EventKind::PostTestRun),
+                )?;
+
+                let snapshot_path = Snapshot::path_for(&ctx.vex_dir().join(vex.as_str()));
+                if update_snapshots {
+                    snapshot.save(&snapshot_path)?;
+                } else if let Some(recorded) = Snapshot::load(&snapshot_path)? {
+                    if recorded != snapshot {
+                        return Err(Error::SnapshotMismatch {
+                            path: PrettyPath::new(
+                                snapshot_path
+                                    .strip_prefix(ctx.project_root.as_str())
+                                    .unwrap_or(&snapshot_path),
+                            ),
+                            diff: recorded.diff(&snapshot),
+                        });
+                    }
                 }
+                if !mismatches.is_empty() {
+                    return Err(Error::UnexpectedIrritations(mismatches));
+                }
+                Ok(())
             });
-        if test_run_invalid {
-            return Err(Error::TestRunInvalid);
-        }
-        files_to_scan
-    };
+            duration += start.elapsed();
+            TestResult {
+                vex,
+                duration,
+                outcome,
+            }
+        })
+        .collect())
+}
 
-    // TODO(kzca): Remove this constraint once language can be specified.
-    let base_associations = Associations::base();
-    files_to_scan.iter().try_for_each(|(path, language, _)| {
-        let src_path = SourcePath::new_in(Utf8Path::new(path.as_str()), Utf8Path::new(""));
-        let Some(associated_language) = base_associations.get_language(&src_path)? else {
-            return Err(Error::InvalidTest(format!(
-                "file {path} has no language associated with it by default"
-            )));
-        };
-        if language != associated_language {
-            return Err(Error::InvalidTest(format!(
-                "file {path} declared as {language} but default language association is {associated_language}"
-            )));
+/// Write the given test files into a temporary project and scan them as their declared languages
+/// with the given scripts and configuration.
+fn scan_test_files<S: ScriptSource>(
+    ctx: &Context,
+    script_sources: &[S],
+    script_args: &ScriptArgsValueMap,
+    config: &TestConfig,
+    files: &[&TestFile],
+) -> Result<Vec<Irritation>> {
+    let config_script_args_heap = FrozenHeap::new();
+    let config_script_args;
+    let script_args = match config.script_args() {
+        Some(args) => {
+            config_script_args = ScriptArgsValueMap::with_args(args, &config_script_args_heap);
+            &config_script_args
         }
-        Ok(())
-    })?;
+        None => script_args,
+    };
 
     let temp_dir = tempfile::tempdir().map_err(|cause| Error::IO {
         path: "(temp file)".into(),
@@ -152,8 +280,15 @@ pub(crate) fn run_tests<S: ScriptSource>(
         cause,
     })?;
     let temp_dir_path = Utf8PathBuf::try_from(temp_dir.path().to_path_buf()).unwrap();
-    for (file_name, _language, content) in files_to_scan {
-        // TODO(kcza): make use of declared language
+    let sub_ctx = ctx.with_project_root(&temp_dir_path);
+    let mut source_files = Vec::with_capacity(files.len());
+    for TestFile {
+        file_name,
+        language,
+        content,
+        ..
+    } in files
+    {
         if file_name
             .components()
             .any(|component| !matches!(component, Utf8Component::Normal(_)))
@@ -162,7 +297,8 @@ pub(crate) fn run_tests<S: ScriptSource>(
                 "cannot use path operators in test path: got {file_name}"
             )));
         }
-        let abs_path = temp_dir_path.join(&file_name);
+        sub_ctx.language_data(language)?;
+        let abs_path = temp_dir_path.join(file_name);
 
         if let Some(parent) = abs_path.parent() {
             fs::create_dir_all(parent).map_err(|cause| Error::IO {
@@ -183,65 +319,313 @@ pub(crate) fn run_tests<S: ScriptSource>(
                 action: IOAction::Write,
                 cause,
             })?;
+        source_files.push(SourceFile::new(
+            SourcePath::new(&abs_path, &sub_ctx.project_root),
+            Some(language.dupe()),
+        ));
     }
 
-    let run_data = {
-        let sub_ctx = Context::new_with_manifest(&temp_dir_path, Manifest::default());
-        let sub_store = {
-            let verbosity = Verbosity::Quiet;
-            let preinit_opts = PreinitOptions {
-                script_args,
-                verbosity,
-            };
-            let init_opts = InitOptions {
-                script_args,
-                verbosity,
-            };
-            PreinitingStore::new(script_sources)?
-                .preinit(ctx, preinit_opts)?
-                .init(ctx, init_opts)?
+    let sub_store = {
+        let verbosity = Verbosity::Quiet;
+        let preinit_opts = PreinitOptions {
+            script_args,
+            verbosity,
         };
-        scan::scan_project(
-            &sub_ctx,
-            &sub_store,
-            WarningFilter::all(),
-            MaxProblems::Unlimited,
-            MaxConcurrentFileLimit::new(1),
+        let init_opts = InitOptions {
             script_args,
-            Verbosity::Quiet,
-        )?
+            verbosity,
+        };
+        PreinitingStore::new(script_sources)?
+            .preinit(ctx, preinit_opts)?
+            .init(ctx, init_opts)?
     };
+    let run_data = scan::scan_project(
+        &sub_ctx,
+        &sub_store,
+        source_files,
+        config.warning_filter(),
+        MaxProblems::Unlimited,
+        script_args,
+        Verbosity::Quiet,
+        None,
+    )?;
+    Ok(run_data.irritations)
+}
 
-    {
-        let handler_module = HandlerModule::new();
-        let event = PostTestRunEvent::new(run_data.irritations, handler_module.heap());
+/// The tests declared by one vex.
+struct TestCase<'a> {
+    vex: PrettyPath,
+    observer_data: &'a ObserverData,
+    duration: Duration,
+    files: Result<Vec<TestFile>>,
+}
 
-        let warning_filter = WarningFilter::all();
-        let observer_opts = ObserveOptions {
-            action: Action::Vexing(event.kind()),
-            script_args,
-            ignore_markers: None,
-            lsp_enabled,
-            print_handler: &PrintHandler::new(logger::verbosity(), event.kind().name()),
-            warning_filter: Some(&warning_filter),
+impl TestCase<'_> {
+    /// Returns whether this vex's path or any lint ID it expects contains the given pattern.
+    fn matches(&self, pattern: &str) -> bool {
+        if self.vex.as_str().contains(pattern) {
+            return true;
+        }
+        self.files.as_ref().is_ok_and(|files| {
+            files
+                .iter()
+                .flat_map(|file| file.expectations.iter().flatten())
+                .any(|expectation| expectation.lint_id.as_str().contains(pattern))
+        })
+    }
+}
+
+/// Fire the `pre_test_run` event for one vex and collect the files it declares.
+fn declared_test_files(
+    ctx: &Context,
+    observer_data: &ObserverData,
+    observe_opts: ObserveOptions<'_>,
+) -> Result<Vec<TestFile>> {
+    let frozen_heap = FrozenHeap::new();
+    let event = PreTestRunEvent;
+    let handler_module = HandlerModule::new();
+    observer_data.observers_for(event.kind()).observe(
+        ctx,
+        &handler_module,
+        handler_module.heap().alloc(event),
+        observe_opts,
+    )?;
+
+    let mut files = Vec::with_capacity(handler_module.intent_count());
+    for intent in handler_module.into_intents_on(&frozen_heap)? {
+        let Intent::ScanFile {
+            file_name,
+            language,
+            content,
+            expectations,
+            config,
+        } = intent
+        else {
+            panic!("internal error: unexpected intent: {intent:?}");
         };
-        store.observers_for(event.kind()).observe(
-            ctx,
-            &handler_module,
-            handler_module.heap().alloc(event),
-            observer_opts,
-        )?;
+        let annotations = Expectation::from_annotations(&content)?;
+        let expectations = match expectations {
+            Some(expectations) => Some(expectations.into_iter().chain(annotations).collect()),
+            None if !annotations.is_empty() => Some(annotations),
+            None => None,
+        };
+        files.push(TestFile {
+            file_name,
+            language,
+            content,
+            expectations,
+            config,
+        });
     }
 
-    Ok(())
+    let mut seen_file_names = BTreeMap::new();
+    files.iter().for_each(|test_file| {
+        seen_file_names
+            .entry(&test_file.file_name)
+            .and_modify(|count| *count += 1)
+            .or_insert(1);
+    });
+    if let Some((file_name, count)) = seen_file_names.into_iter().find(|(_, count)| *count > 1) {
+        return Err(Error::InvalidTest(format!(
+            "test file '{file_name}' declared {count} times"
+        )));
+    }
+
+    Ok(files)
+}
+
+/// A file declared by `vex.scan`.
+struct TestFile {
+    file_name: PrettyPath,
+    language: Language,
+    content: String,
+
+    /// The irritations expected of this file, either through the `expect` argument of `vex.scan`
+    /// or through annotations in its content. Files without expectations are not checked.
+    expectations: Option<Vec<Expectation>>,
+
+    config: TestConfig,
+}
+
+impl TestFile {
+    /// Describe each difference between the irritations found in this file and those expected of
+    /// it.
+    fn mismatches(&self, irritations: &[Irritation]) -> Vec<String> {
+        let Some(expectations) = &self.expectations else {
+            return vec![];
+        };
+        let irritations: Vec<_> = irritations
+            .iter()
+            .filter(|irritation| irritation.path() == Some(&self.file_name))
+            .collect();
+        expectation::diff(expectations, &irritations, &self.content)
+            .iter()
+            .map(|mismatch| format!("{}: {mismatch}", self.file_name))
+            .collect()
+    }
+}
+
+fn write_junit(path: &Utf8Path, results: &[TestResult]) -> Result<()> {
+    let io_error = |cause| Error::IO {
+        path: PrettyPath::new(path),
+        action: IOAction::Write,
+        cause,
+    };
+    let mut writer = BufWriter::new(File::create(path).map_err(io_error)?);
+    render_junit(&mut writer, results)
+        .and_then(|()| writer.flush())
+        .map_err(io_error)
+}
+
+fn write_coverage(path: &Utf8Path, ctx: &Context, coverage: &Coverage) -> Result<()> {
+    let io_error = |cause| Error::IO {
+        path: PrettyPath::new(path),
+        action: IOAction::Write,
+        cause,
+    };
+    let vexes_dir = Utf8Path::new(ctx.manifest.run.vexes_dir.as_str());
+    let mut writer = BufWriter::new(File::create(path).map_err(io_error)?);
+    coverage
+        .write_lcov(&mut writer, |file| vexes_dir.join(file).to_string())
+        .and_then(|()| writer.flush())
+        .map_err(io_error)
+}
+
+fn render_junit(writer: &mut impl Write, results: &[TestResult]) -> std::io::Result<()> {
+    let num_failures = results
+        .iter()
+        .filter(|result| result.outcome.is_err())
+        .count();
+    let total_time: f64 = results
+        .iter()
+        .map(|result| result.duration.as_secs_f64())
+        .sum();
+    writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        writer,
+        r#"<testsuites name="vex" tests="{}" failures="{num_failures}" time="{total_time:.3}">"#,
+        results.len(),
+    )?;
+    writeln!(
+        writer,
+        r#"  <testsuite name="vex" tests="{}" failures="{num_failures}" time="{total_time:.3}">"#,
+        results.len(),
+    )?;
+    for result in results {
+        let name = xml_escape(result.vex.as_str());
+        let time = result.duration.as_secs_f64();
+        match &result.outcome {
+            Ok(()) => writeln!(
+                writer,
+                r#"    <testcase name="{name}" classname="vex" time="{time:.3}"/>"#
+            )?,
+            Err(err) => {
+                let err = xml_escape(&err.to_string());
+                writeln!(
+                    writer,
+                    r#"    <testcase name="{name}" classname="vex" time="{time:.3}">"#
+                )?;
+                writeln!(writer, r#"      <failure message="{err}">{err}</failure>"#)?;
+                writeln!(writer, "    </testcase>")?;
+            }
+        }
+    }
+    writeln!(writer, "  </testsuite>")?;
+    writeln!(writer, "</testsuites>")
+}
+
+fn xml_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\n' => escaped.push_str("&#10;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
 }
 
 #[allow(clippy::module_inception)]
 #[cfg(test)]
 mod tests {
-    use indoc::formatdoc;
+    use indoc::{formatdoc, indoc};
+
+    use crate::{context::Manifest, vextest::VexTest};
 
-    use crate::vextest::VexTest;
+    use super::*;
+
+    #[test]
+    fn pattern() {
+        let observer_data = ObserverData::empty();
+        let test_case = TestCase {
+            vex: PrettyPath::from("lints/no_unwrap.star"),
+            observer_data: &observer_data,
+            duration: Duration::ZERO,
+            files: Ok(vec![TestFile {
+                file_name: PrettyPath::from("main.rs"),
+                language: Language::Rust,
+                content: "".into(),
+                expectations: Some(vec![Expectation::from_annotations(
+                    "x // vex:expect unwrap-on-db-result",
+                )
+                .unwrap()
+                .remove(0)]),
+                config: TestConfig::default(),
+            }]),
+        };
+        assert!(test_case.matches("no_unwrap"));
+        assert!(test_case.matches("lints/"));
+        assert!(test_case.matches("db-result"));
+        assert!(!test_case.matches("no-unwrap"));
+
+        let failed_test_case = TestCase {
+            files: Err(Error::InvalidTest(
+                "test file 'main.rs' declared 2 times".into(),
+            )),
+            ..test_case
+        };
+        assert!(failed_test_case.matches("no_unwrap"));
+        assert!(!failed_test_case.matches("db-result"));
+    }
+
+    #[test]
+    fn junit() {
+        let results = [
+            TestResult {
+                vex: PrettyPath::from("a.star"),
+                duration: Duration::from_millis(1500),
+                outcome: Ok(()),
+            },
+            TestResult {
+                vex: PrettyPath::from("b.star"),
+                duration: Duration::from_millis(250),
+                outcome: Err(Error::UnexpectedIrritations(vec![
+                    "main.rs: missing <lint> & more".into(),
+                ])),
+            },
+        ];
+        let mut output = vec![];
+        render_junit(&mut output, &results).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            indoc! {r#"
+                <?xml version="1.0" encoding="UTF-8"?>
+                <testsuites name="vex" tests="2" failures="1" time="1.750">
+                  <testsuite name="vex" tests="2" failures="1" time="1.750">
+                    <testcase name="a.star" classname="vex" time="1.500"/>
+                    <testcase name="b.star" classname="vex" time="0.250">
+                      <failure message="irritations differ from expectations:&#10;  main.rs: missing &lt;lint&gt; &amp; more">irritations differ from expectations:&#10;  main.rs: missing &lt;lint&gt; &amp; more</failure>
+                    </testcase>
+                  </testsuite>
+                </testsuites>
+            "#}
+        );
+    }
 
     #[test]
     fn standard_flow() {
@@ -691,4 +1075,483 @@ mod tests {
             )
             .assert_irritation_free();
     }
+
+    #[test]
+    fn expectations() {
+        let test = |name, on_pre_test_run: &str| {
+            VexTest::new(name).with_test_events(true).with_scriptlet(
+                "vexes/test.star",
+                formatdoc! {
+                    r#"
+                        def init():
+                            vex.observe('open_project', on_open_project)
+                            vex.observe('pre_test_run', on_pre_test_run)
+
+                        def on_open_project(event):
+                            vex.search('rust', '(integer_literal) @lit', on_match)
+
+                        def on_match(event):
+                            lit = event.captures['lit']
+                            vex.warn('literal', 'found %s' % lit, at=lit)
+
+                        {on_pre_test_run}
+                    "#,
+                },
+            )
+        };
+
+        test(
+            "expect-arg",
+            indoc! {r#"
+                def on_pre_test_run(event):
+                    vex.scan(
+                        'main.rs',
+                        'rust',
+                        '''
+                            fn main() {
+                                let x = 1 + 2;
+                            }
+                        ''',
+                        expect=[
+                            'literal',
+                            {'id': 'literal', 'line': 2, 'column': 12, 'text': '1'},
+                        ],
+                    )
+                    vex.scan('none.rs', 'rust', 'struct S;', expect=[])
+                    vex.scan('unchecked.rs', 'rust', 'const X: i32 = 1;')
+            "#},
+        )
+        .assert_irritation_free();
+
+        test(
+            "annotations",
+            indoc! {r#"
+                def on_pre_test_run(event):
+                    vex.scan(
+                        'main.rs',
+                        'rust',
+                        '''
+                            fn main() {
+                                let x = 1; // vex:expect literal
+                                // vex:expect literal
+                                let y = 2;
+                            }
+                        ''',
+                    )
+            "#},
+        )
+        .assert_irritation_free();
+
+        test(
+            "mismatched",
+            indoc! {r#"
+                def on_pre_test_run(event):
+                    vex.scan(
+                        'main.rs',
+                        'rust',
+                        '''
+                            fn main() {
+                                let x = 1;
+                                let y = 2; // vex:expect literal
+                            }
+                        ''',
+                        expect=[{'id': 'literal', 'message': 'found 3'}],
+                    )
+                    vex.scan('ok.rs', 'rust', 'const X: i32 = 1;', expect=['literal'])
+            "#},
+        )
+        .returns_error("irritations differ from expectations");
+
+        test(
+            "invalid",
+            indoc! {r#"
+                def on_pre_test_run(event):
+                    vex.scan('main.rs', 'rust', 'struct S;', expect=[{'id': 'literal', 'row': 1}])
+            "#},
+        )
+        .returns_error("unknown expectation field 'row'");
+    }
+
+    #[test]
+    fn isolation() {
+        let vex = |lint_id: &str| {
+            formatdoc! {
+                r#"
+                    load('lib/literals.star', 'warn_literals')
+
+                    def init():
+                        vex.observe('open_project', lambda event: warn_literals('{lint_id}'))
+                        vex.observe('pre_test_run', on_pre_test_run)
+
+                    def on_pre_test_run(event):
+                        vex.scan(
+                            'main.rs',
+                            'rust',
+                            'const X: i32 = 1; // vex:expect {lint_id}',
+                        )
+                "#,
+            }
+        };
+        VexTest::new("isolation")
+            .with_test_events(true)
+            .with_scriptlet(
+                "vexes/lib/literals.star",
+                indoc! {r#"
+                    def warn_literals(lint_id):
+                        vex.search(
+                            'rust',
+                            '(integer_literal) @lit',
+                            lambda event: vex.warn(lint_id, 'literal', at=event.captures['lit']),
+                        )
+                "#},
+            )
+            .with_scriptlet("vexes/a.star", vex("a-literal"))
+            .with_scriptlet("vexes/b.star", vex("b-literal"))
+            .assert_irritation_free();
+    }
+
+    #[test]
+    fn config() {
+        let test = |name, on_pre_test_run: &str| {
+            VexTest::new(name).with_test_events(true).with_scriptlet(
+                "vexes/test.star",
+                formatdoc! {
+                    r#"
+                        def init():
+                            vex.observe('open_project', on_open_project)
+                            vex.observe('pre_test_run', on_pre_test_run)
+
+                        def on_open_project(event):
+                            if vex.active('big-number', group='numbers'):
+                                vex.search('rust', '(integer_literal) @lit', on_match)
+
+                        def on_match(event):
+                            args = vex.args_for('big-number') or {{}}
+                            lit = event.captures['lit']
+                            if int(str(lit)) >= args.get('threshold', 10):
+                                vex.warn('big-number', 'big', at=lit, group='numbers')
+
+                        {on_pre_test_run}
+                    "#,
+                },
+            )
+        };
+
+        test(
+            "valid",
+            indoc! {r#"
+                def on_pre_test_run(event):
+                    vex.scan('default.rs', 'rust', 'const X: i32 = 20;', expect=['big-number'])
+                    vex.scan(
+                        'strict.rs',
+                        'rust',
+                        'const X: i32 = 20;',
+                        args={'big-number': {'threshold': 100}},
+                        expect=[],
+                    )
+                    vex.scan(
+                        'lenient.rs',
+                        'rust',
+                        'const X: i32 = 2;',
+                        args={'big-number': {'threshold': 1}},
+                        expect=['big-number'],
+                    )
+                    vex.scan(
+                        'inactive-lint.rs',
+                        'rust',
+                        'const X: i32 = 20;',
+                        lints={'big-number': False},
+                        expect=[],
+                    )
+                    vex.scan(
+                        'inactive-group.rs',
+                        'rust',
+                        'const X: i32 = 20;',
+                        groups={'numbers': False},
+                        expect=[],
+                    )
+            "#},
+        )
+        .assert_irritation_free();
+
+        test(
+            "invalid-args",
+            indoc! {r#"
+                def on_pre_test_run(event):
+                    vex.scan('main.rs', 'rust', 'struct S;', args={'big-number': 100})
+            "#},
+        )
+        .returns_error("invalid args");
+
+        test(
+            "invalid-lints",
+            indoc! {r#"
+                def on_pre_test_run(event):
+                    vex.scan('main.rs', 'rust', 'struct S;', lints={'Big Number': False})
+            "#},
+        )
+        .returns_error("invalid lints");
+    }
+
+    #[test]
+    fn declared_languages() {
+        VexTest::new("declared-language")
+            .with_test_events(true)
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+                        vex.observe('pre_test_run', on_pre_test_run)
+
+                    def on_open_project(event):
+                        vex.search('rust', '(integer_literal) @lit', on_match)
+
+                    def on_match(event):
+                        vex.warn('literal', 'found a literal', at=event.captures['lit'])
+
+                    def on_pre_test_run(event):
+                        vex.scan('main.rs.in', 'rust', 'const X: i32 = 1; // vex:expect literal')
+                "#},
+            )
+            .assert_irritation_free();
+
+        VexTest::new("unconfigured-language")
+            .with_test_events(true)
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.observe('pre_test_run', on_pre_test_run)
+
+                    def on_pre_test_run(event):
+                        vex.scan('main.hcl', 'hcl', 'x = 1')
+                "#},
+            )
+            .returns_error("cannot load language hcl");
+    }
+
+    #[test]
+    fn external_language() {
+        const PARSER_LINK: &str = "vexes/tree-sitter-lua";
+        VexTest::new("external-language")
+            .with_test_events(true)
+            .with_manifest(formatdoc! {r#"
+                [vex]
+                version = "1"
+
+                [languages.lua]
+                parser-dir = '{PARSER_LINK}'
+            "#})
+            .with_parser_dir_link("test-data/tree-sitter-lua", PARSER_LINK)
+            .with_scriptlet(
+                "vexes/test.star",
+                indoc! {r#"
+                    def init():
+                        vex.observe('open_project', on_open_project)
+                        vex.observe('pre_test_run', on_pre_test_run)
+
+                    def on_open_project(event):
+                        vex.search('lua', '(function_call) @call', on_match)
+
+                    def on_match(event):
+                        vex.warn('call', 'found a call', at=event.captures['call'])
+
+                    def on_pre_test_run(event):
+                        vex.scan('main.lua', 'lua', "print('hello') -- vex:expect call")
+                "#},
+            )
+            .assert_irritation_free();
+    }
+
+    #[test]
+    fn snapshots() {
+        let root_dir = tempfile::tempdir().unwrap();
+        let root_path = Utf8PathBuf::try_from(root_dir.path().to_path_buf()).unwrap();
+        fs::write(root_path.join(Manifest::FILE_NAME), "[vex]\nversion = '1'").unwrap();
+        fs::create_dir(root_path.join("vexes")).unwrap();
+        fs::write(
+            root_path.join("vexes/test.star"),
+            indoc! {r#"
+                def init():
+                    vex.observe('open_project', on_open_project)
+                    vex.observe('pre_test_run', on_pre_test_run)
+
+                def on_open_project(event):
+                    vex.search('rust', '(integer_literal) @lit', on_match)
+
+                def on_match(event):
+                    vex.warn('literal', 'found a literal', at=event.captures['lit'])
+
+                def on_pre_test_run(event):
+                    vex.scan('main.rs', 'rust', 'const X: i32 = 1;')
+            "#},
+        )
+        .unwrap();
+
+        let ctx = Context::acquire_in(&root_path).unwrap();
+        let script_args_heap = FrozenHeap::new();
+        let script_args = ScriptArgsValueMap::with_args(&ctx.script_args, &script_args_heap);
+        let script_sources = source::sources_in_dir(&ctx.vex_dir()).unwrap();
+        let run = |update_snapshots| {
+            let mut results = run_tests(
+                &ctx,
+                RunTestOptions {
+                    lsp_enabled: false,
+                    script_args: &script_args,
+                    script_sources: &script_sources,
+                    pattern: None,
+                    update_snapshots,
+                },
+            )
+            .unwrap();
+            assert_eq!(results.len(), 1);
+            results.remove(0).outcome
+        };
+
+        // Without a recorded snapshot, nothing is compared.
+        run(false).unwrap();
+
+        run(true).unwrap();
+        let snapshot_path = root_path.join("vexes/test.snap");
+        let snapshot = fs::read_to_string(&snapshot_path).unwrap();
+        assert!(snapshot.contains("found a literal"), "{snapshot}");
+        assert!(snapshot.contains("main.rs:1:16"), "{snapshot}");
+        run(false).unwrap();
+
+        fs::write(
+            &snapshot_path,
+            snapshot.replace("found a literal", "old message"),
+        )
+        .unwrap();
+        let err = run(false).unwrap_err().to_string();
+        assert!(
+            err.starts_with("irritations differ from snapshot vexes/test.snap"),
+            "{err}"
+        );
+        assert!(err.contains("-warning[literal]: old message"), "{err}");
+        assert!(err.contains("+warning[literal]: found a literal"), "{err}");
+
+        run(true).unwrap();
+        assert_eq!(fs::read_to_string(&snapshot_path).unwrap(), snapshot);
+    }
+
+    /// Run the tests of the given vex with coverage enabled, returning the outcome of each test
+    /// and the resulting lcov tracefile.
+    fn run_with_coverage(vex_src: &str) -> (Vec<Result<()>>, String) {
+        let root_dir = tempfile::tempdir().unwrap();
+        let root_path = Utf8PathBuf::try_from(root_dir.path().to_path_buf()).unwrap();
+        fs::write(root_path.join(Manifest::FILE_NAME), "[vex]\nversion = '1'").unwrap();
+        fs::create_dir(root_path.join("vexes")).unwrap();
+        fs::write(root_path.join("vexes/test.star"), vex_src).unwrap();
+
+        let mut ctx = Context::acquire_in(&root_path).unwrap();
+        ctx.enable_coverage();
+        let script_args_heap = FrozenHeap::new();
+        let script_args = ScriptArgsValueMap::with_args(&ctx.script_args, &script_args_heap);
+        let outcomes = run_tests(
+            &ctx,
+            RunTestOptions {
+                lsp_enabled: false,
+                script_args: &script_args,
+                script_sources: &source::sources_in_dir(&ctx.vex_dir()).unwrap(),
+                pattern: None,
+                update_snapshots: false,
+            },
+        )
+        .unwrap()
+        .into_iter()
+        .map(|result| result.outcome)
+        .collect();
+
+        let mut output = vec![];
+        ctx.coverage()
+            .unwrap()
+            .write_lcov(&mut output, |file| format!("vexes/{file}"))
+            .unwrap();
+        (outcomes, String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn coverage() {
+        let (outcomes, lcov) = run_with_coverage(indoc! {r#"
+            def init():
+                vex.observe('open_project', on_open_project)
+                vex.observe('pre_test_run', on_pre_test_run)
+
+            def on_open_project(event):
+                vex.search('rust', '(integer_literal) @lit', on_match)
+
+            def on_match(event):
+                lit = event.captures['lit']
+                if str(lit) == '0':
+                    vex.warn('zero', 'found zero', at=lit)
+
+            def on_pre_test_run(event):
+                vex.scan('main.rs', 'rust', 'const X: i32 = 1 + 2;')
+        "#});
+        outcomes
+            .into_iter()
+            .try_for_each(|outcome| outcome)
+            .unwrap();
+        assert_eq!(
+            lcov,
+            indoc! {"
+                SF:vexes/test.star
+                DA:1,2
+                DA:2,2
+                DA:3,2
+                DA:5,2
+                DA:6,1
+                DA:8,2
+                DA:9,2
+                DA:10,2
+                DA:11,0
+                DA:13,2
+                DA:14,1
+                LH:10
+                LF:11
+                end_of_record
+            "}
+        );
+    }
+
+    #[test]
+    fn failing_coverage() {
+        let (outcomes, lcov) = run_with_coverage(indoc! {r#"
+            def init():
+                vex.observe('open_project', on_open_project)
+                vex.observe('pre_test_run', on_pre_test_run)
+
+            def on_open_project(event):
+                vex.search('rust', '(integer_literal) @lit', on_match)
+
+            def on_match(event):
+                lit = event.captures['lit']
+                fail('oh no')
+
+            def on_pre_test_run(event):
+                vex.scan('main.rs', 'rust', 'const X: i32 = 1;')
+        "#});
+        assert!(outcomes.iter().any(Result::is_err), "{outcomes:?}");
+        assert_eq!(
+            lcov,
+            indoc! {"
+                SF:vexes/test.star
+                DA:1,2
+                DA:2,2
+                DA:3,2
+                DA:5,2
+                DA:6,1
+                DA:8,2
+                DA:9,1
+                DA:10,1
+                DA:12,2
+                DA:13,1
+                LH:10
+                LF:10
+                end_of_record
+            "}
+        );
+    }
 }
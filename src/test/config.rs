@@ -0,0 +1,78 @@
+use std::collections::{BTreeMap, HashMap};
+
+use allocative::Allocative;
+use serde::de::DeserializeOwned;
+use starlark::values::Value;
+
+use crate::{
+    context::ScriptArgs,
+    error::Error,
+    id::{GroupId, LintId},
+    result::Result,
+    warning_filter::{ExclusionSet, WarningFilter},
+};
+
+/// Settings which replace those of the project while scanning a test file.
+#[derive(Clone, Debug, Default, PartialEq, Allocative)]
+pub struct TestConfig {
+    /// Replaces the `[args]` table of the manifest.
+    #[allocative(skip)]
+    script_args: Option<ScriptArgs>,
+
+    /// As in `[lints.active]`, lints not mentioned are active.
+    active_lints: BTreeMap<LintId, bool>,
+
+    /// As in `[groups.active]`, groups not mentioned are active.
+    active_groups: BTreeMap<GroupId, bool>,
+}
+
+impl TestConfig {
+    /// Parse the `args`, `lints` and `groups` arguments passed to `vex.scan`.
+    pub fn from_values(
+        args: Option<Value<'_>>,
+        lints: Option<Value<'_>>,
+        groups: Option<Value<'_>>,
+    ) -> Result<Self> {
+        Ok(Self {
+            script_args: args.map(|args| from_value("args", args)).transpose()?,
+            active_lints: lints
+                .map(|lints| from_value("lints", lints))
+                .transpose()?
+                .unwrap_or_default(),
+            active_groups: groups
+                .map(|groups| from_value("groups", groups))
+                .transpose()?
+                .unwrap_or_default(),
+        })
+    }
+
+    pub fn script_args(&self) -> Option<&ScriptArgs> {
+        self.script_args.as_ref()
+    }
+
+    pub fn warning_filter(&self) -> WarningFilter {
+        fn inactive<T: Clone + Eq + std::hash::Hash>(
+            active: &BTreeMap<T, bool>,
+        ) -> ExclusionSet<T> {
+            ExclusionSet::from_excluded(
+                active
+                    .iter()
+                    .filter(|(_, active)| !**active)
+                    .map(|(id, _)| id.clone()),
+            )
+        }
+        WarningFilter::new(
+            inactive(&self.active_lints),
+            inactive(&self.active_groups),
+            HashMap::new(),
+        )
+    }
+}
+
+fn from_value<T: DeserializeOwned>(field: &str, value: Value<'_>) -> Result<T> {
+    let json = value
+        .to_json_value()
+        .map_err(|err| Error::InvalidTest(format!("cannot read {field}: {err}")))?;
+    serde_json::from_value(json)
+        .map_err(|err| Error::InvalidTest(format!("invalid {field}: {err}")))
+}
@@ -0,0 +1,82 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::{self, Write},
+    sync::{Mutex, MutexGuard},
+};
+
+use starlark::{
+    eval::{Evaluator, ProfileMode},
+    syntax::AstModule,
+};
+
+use crate::result::Result;
+
+/// For each statement-bearing line of each scriptlet, the number of evaluations which reached it.
+#[derive(Debug, Default)]
+pub struct Coverage(Mutex<BTreeMap<String, BTreeMap<usize, usize>>>);
+
+impl Coverage {
+    fn files(&self) -> MutexGuard<'_, BTreeMap<String, BTreeMap<usize, usize>>> {
+        self.0.lock().expect("coverage lock poisoned")
+    }
+
+    /// Note the lines of a scriptlet which contain statements, so that those which never execute
+    /// are reported.
+    pub fn add_statements(&self, ast: &AstModule) {
+        let mut files = self.files();
+        for span in ast.stmt_locations() {
+            let span = span.resolve();
+            files
+                .entry(span.file)
+                .or_default()
+                .entry(span.span.begin.line + 1)
+                .or_insert(0);
+        }
+    }
+
+    /// Start recording the statements executed by the given evaluator.
+    pub fn instrument(&self, eval: &mut Evaluator<'_, '_>) -> Result<()> {
+        eval.enable_profile(&ProfileMode::Coverage)?;
+        Ok(())
+    }
+
+    /// Count each line executed by the given instrumented evaluator, whether or not its
+    /// evaluation succeeded. Starlark reports only which lines were reached, so a line run many
+    /// times by one evaluation is counted once.
+    pub fn record(&self, eval: &Evaluator<'_, '_>) -> Result<()> {
+        let lines: BTreeSet<_> = eval
+            .coverage()?
+            .into_iter()
+            .map(|span| (span.file, span.span.begin.line + 1))
+            .collect();
+        let mut files = self.files();
+        for (file, line) in lines {
+            *files.entry(file).or_default().entry(line).or_insert(0) += 1;
+        }
+        Ok(())
+    }
+
+    /// Write an lcov tracefile with a record for each scriptlet, whose path is given by
+    /// `source_path` applied to its load path. The hit count of each line is the number of module
+    /// loads and callback runs which executed it, not the number of times it was executed.
+    pub fn write_lcov(
+        &self,
+        writer: &mut impl Write,
+        source_path: impl Fn(&str) -> String,
+    ) -> io::Result<()> {
+        for (file, lines) in self.files().iter() {
+            writeln!(writer, "SF:{}", source_path(file))?;
+            for (line, hits) in lines {
+                writeln!(writer, "DA:{line},{hits}")?;
+            }
+            writeln!(
+                writer,
+                "LH:{}",
+                lines.values().filter(|hits| **hits > 0).count()
+            )?;
+            writeln!(writer, "LF:{}", lines.len())?;
+            writeln!(writer, "end_of_record")?;
+        }
+        Ok(())
+    }
+}
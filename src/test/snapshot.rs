@@ -0,0 +1,87 @@
+use std::{fs, io::ErrorKind};
+
+use camino::{Utf8Path, Utf8PathBuf};
+use lazy_static::lazy_static;
+use regex::Regex;
+use similar::TextDiff;
+
+use crate::{
+    error::{Error, IOAction},
+    irritation::Irritation,
+    result::Result,
+    source_path::PrettyPath,
+};
+
+/// The rendered irritations which a vex produces against its test files.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Snapshot(String);
+
+impl Snapshot {
+    pub const EXTENSION: &'static str = "snap";
+
+    pub fn of(irritations: &[Irritation]) -> Self {
+        lazy_static! {
+            static ref ANSI_ESCAPE: Regex = Regex::new("\x1b\\[[0-9;]*m").unwrap();
+        }
+        let content = irritations
+            .iter()
+            .map(|irritation| {
+                let rendered = irritation.to_string();
+                let rendered = ANSI_ESCAPE.replace_all(&rendered, "");
+                format!("{}\n", rendered.trim_end())
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        Self(content)
+    }
+
+    /// The path of the snapshot file which sits beside the given vex.
+    pub fn path_for(vex_path: &Utf8Path) -> Utf8PathBuf {
+        vex_path.with_extension(Self::EXTENSION)
+    }
+
+    /// Read the snapshot at the given path, returning `None` if none has been recorded.
+    pub fn load(path: &Utf8Path) -> Result<Option<Self>> {
+        match fs::read_to_string(path) {
+            Ok(content) => Ok(Some(Self(content))),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(cause) => Err(Error::IO {
+                path: PrettyPath::new(path),
+                action: IOAction::Read,
+                cause,
+            }),
+        }
+    }
+
+    pub fn save(&self, path: &Utf8Path) -> Result<()> {
+        fs::write(path, &self.0).map_err(|cause| Error::IO {
+            path: PrettyPath::new(path),
+            action: IOAction::Write,
+            cause,
+        })
+    }
+
+    /// Describe how the given snapshot differs from this one as a unified diff.
+    pub fn diff(&self, actual: &Self) -> String {
+        TextDiff::from_lines(&self.0, &actual.0)
+            .unified_diff()
+            .header("snapshot", "actual")
+            .to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff() {
+        let old = Snapshot("a\nb\nc\n".into());
+        let new = Snapshot("a\nB\nc\n".into());
+        assert_eq!(old.diff(&old), "");
+        assert_eq!(
+            old.diff(&new),
+            "--- snapshot\n+++ actual\n@@ -1,3 +1,3 @@\n a\n-b\n+B\n c\n"
+        );
+    }
+}
@@ -13,19 +13,24 @@ use std::os::unix;
 use std::process::Command;
 
 use camino::{Utf8Component, Utf8PathBuf};
+use dupe::Dupe;
 use indoc::indoc;
 use regex::Regex;
+use serde_json::Value;
 use starlark::values::FrozenHeap;
 
 use crate::{
     cli::{MaxConcurrentFileLimit, MaxProblems},
     context::Context,
+    language::Language,
+    lsp::{LanguageServer, MockResponder},
     result::Result,
     scan,
     scriptlets::{
         source::{ScriptSource, TestSource},
         InitOptions, PreinitOptions, PreinitingStore, ScriptArgsValueMap,
     },
+    source_file,
     test::RunTestOptions,
     verbosity::Verbosity,
     ProjectRunData,
@@ -42,6 +47,7 @@ pub struct VexTest<'s> {
     scriptlets: Vec<TestSource<Utf8PathBuf, Cow<'s, str>>>,
     source_files: BTreeMap<Utf8PathBuf, Cow<'s, str>>,
     parser_dir_links: Vec<ParserDirLink>,
+    language_servers: Vec<(Language, MockResponder)>,
 }
 
 struct ParserDirLink {
@@ -143,6 +149,16 @@ impl<'s> VexTest<'s> {
         self
     }
 
+    /// Answer language server requests for the given language using an in-process mock.
+    pub fn with_language_server(
+        mut self,
+        language: Language,
+        responder: impl Fn(&str, &Value) -> Value + Send + 'static,
+    ) -> Self {
+        self.language_servers.push((language, Box::new(responder)));
+        self
+    }
+
     pub fn assert_irritation_free(self) {
         assert_eq!(
             self.try_run().unwrap().irritations,
@@ -224,6 +240,10 @@ impl<'s> VexTest<'s> {
         }
 
         let ctx = Context::acquire_in(&root_path).unwrap();
+        for (language, responder) in self.language_servers {
+            let language_server = LanguageServer::mock(&root_path, language.dupe(), responder)?;
+            ctx.set_language_server(&language, language_server);
+        }
         let script_args_heap = FrozenHeap::new();
         let script_args = ScriptArgsValueMap::with_args(&ctx.script_args, &script_args_heap);
         if !self.bare {
@@ -236,8 +256,12 @@ impl<'s> VexTest<'s> {
                     lsp_enabled: ctx.manifest.run.lsp_enabled,
                     script_args: &script_args,
                     script_sources: &self.scriptlets,
+                    pattern: None,
+                    update_snapshots: false,
                 },
-            )?;
+            )?
+            .into_iter()
+            .try_for_each(|result| result.outcome)?;
             Ok(ProjectRunData::default())
         } else {
             for (path, content) in &self.source_files {
@@ -266,11 +290,12 @@ impl<'s> VexTest<'s> {
             scan::scan_project(
                 &ctx,
                 &store,
+                source_file::sources_in_dir(&ctx, MaxConcurrentFileLimit::new(1))?,
                 warning_filter,
                 self.max_problems,
-                MaxConcurrentFileLimit::new(1),
                 &script_args,
                 verbosity,
+                None,
             )
         }
     }
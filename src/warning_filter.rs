@@ -1,18 +1,30 @@
-use std::{collections::HashSet, hash::Hash};
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+};
 
-use crate::id::{GroupId, LintId};
+use crate::{
+    id::{GroupId, Id, LintId},
+    severity::Severity,
+};
 
 #[derive(Clone, Debug)]
 pub struct WarningFilter {
     active_lints: ExclusionSet<LintId>,
     active_groups: ExclusionSet<GroupId>,
+    levels: HashMap<Id, Severity>,
 }
 
 impl WarningFilter {
-    pub fn new(active_lints: ExclusionSet<LintId>, active_groups: ExclusionSet<GroupId>) -> Self {
+    pub fn new(
+        active_lints: ExclusionSet<LintId>,
+        active_groups: ExclusionSet<GroupId>,
+        levels: HashMap<Id, Severity>,
+    ) -> Self {
         Self {
             active_lints,
             active_groups,
+            levels,
         }
     }
 
@@ -20,9 +32,25 @@ impl WarningFilter {
         Self {
             active_lints: ExclusionSet::all(),
             active_groups: ExclusionSet::all(),
+            levels: HashMap::new(),
         }
     }
 
+    /// Returns the level at which a warning should be reported. Levels set for specific lints take
+    /// precedence over those set for groups, which take precedence over the requested level.
+    pub fn level_of(
+        &self,
+        lint_id: &LintId,
+        group_id: Option<&GroupId>,
+        requested: Severity,
+    ) -> Severity {
+        self.levels
+            .get(lint_id.as_ref())
+            .or_else(|| group_id.and_then(|group_id| self.levels.get(group_id.as_ref())))
+            .copied()
+            .unwrap_or(requested)
+    }
+
     pub fn is_active(&self, id: &LintId) -> bool {
         self.active_lints.is_active(id)
     }
@@ -83,11 +111,50 @@ mod tests {
         );
 
         let raw_active_id = "active";
-        let warning_filter = WarningFilter::new(active_lints, active_groups);
+        let warning_filter = WarningFilter::new(active_lints, active_groups, HashMap::new());
         assert!(warning_filter.is_active(&LintId::try_from(raw_active_id.to_owned()).unwrap()));
         assert!(!warning_filter.is_active(&LintId::try_from(raw_inactive_id.to_owned()).unwrap()));
     }
 
+    #[test]
+    fn levels() {
+        let lint_id = |raw_id: &str| LintId::try_from(raw_id.to_owned()).unwrap();
+        let group_id = |raw_id: &str| GroupId::try_from(raw_id.to_owned()).unwrap();
+        let levels = [
+            ("lint-as-note", Severity::Note),
+            ("group-as-error", Severity::Error),
+        ]
+        .into_iter()
+        .map(|(raw_id, level)| (Id::try_from(raw_id.to_owned()).unwrap(), level))
+        .collect();
+        let warning_filter = WarningFilter::new(ExclusionSet::all(), ExclusionSet::all(), levels);
+
+        assert_eq!(
+            warning_filter.level_of(&lint_id("other-lint"), None, Severity::Warning),
+            Severity::Warning
+        );
+        assert_eq!(
+            warning_filter.level_of(&lint_id("lint-as-note"), None, Severity::Error),
+            Severity::Note
+        );
+        assert_eq!(
+            warning_filter.level_of(
+                &lint_id("other-lint"),
+                Some(&group_id("group-as-error")),
+                Severity::Note
+            ),
+            Severity::Error
+        );
+        assert_eq!(
+            warning_filter.level_of(
+                &lint_id("lint-as-note"),
+                Some(&group_id("group-as-error")),
+                Severity::Warning
+            ),
+            Severity::Note
+        );
+    }
+
     #[test]
     fn all() {
         let warning_filter = WarningFilter::all();
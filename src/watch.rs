@@ -0,0 +1,338 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::mpsc::{self, Receiver, RecvTimeoutError},
+    time::Duration,
+};
+
+use camino::{Utf8Path, Utf8PathBuf};
+use log::{info, log_enabled};
+use notify::{Event, RecursiveMode, Watcher};
+use starlark::values::FrozenHeap;
+
+use crate::{
+    baseline::Baseline,
+    cli::{CheckCmd, MaxProblems},
+    context::{Context, Manifest},
+    error::Error,
+    irritation::Irritation,
+    logger,
+    result::Result,
+    scan::{self, FileScanData},
+    scriptlets::ScriptArgsValueMap,
+    severity::SeverityCounts,
+    source_file,
+    source_path::PrettyPath,
+};
+
+/// How long to wait for further changes before rechecking, so that a burst of writes triggers a
+/// single recheck.
+const DEBOUNCE: Duration = Duration::from_millis(100);
+
+type Events = Receiver<notify::Result<Event>>;
+
+pub fn watch(cmd_args: CheckCmd) -> Result<()> {
+    let project_root = Context::acquire()?.project_root;
+    let watch_error = |cause| Error::Watch {
+        path: project_root.clone(),
+        cause,
+    };
+
+    let (tx, events) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx).map_err(watch_error)?;
+    watcher
+        .watch(project_root.as_std_path(), RecursiveMode::Recursive)
+        .map_err(watch_error)?;
+
+    let requested_paths = crate::requested_paths(&cmd_args)?;
+    loop {
+        if let Err(err) = run_session(&project_root, &cmd_args, &requested_paths, &events) {
+            crate::error!("{err}");
+            next_changes(&events, &project_root)?;
+        }
+    }
+}
+
+/// Check the whole project, then recheck files as they change. Returns when the vexes or manifest
+/// change and hence must be reloaded.
+fn run_session(
+    project_root: &Utf8Path,
+    cmd_args: &CheckCmd,
+    requested_paths: &[Utf8PathBuf],
+    events: &Events,
+) -> Result<()> {
+    let ctx = Context::acquire_in(project_root)?;
+    let verbosity = logger::verbosity();
+    let script_args_heap = FrozenHeap::new();
+    let script_args = ScriptArgsValueMap::with_args(&ctx.script_args, &script_args_heap);
+    let store = crate::load_store(&ctx, &script_args, verbosity)?;
+    let warning_filter = crate::try_make_warning_filter(&ctx.manifest)?;
+
+    let mut results = WatchResults::default();
+    let files = crate::files_to_check(&ctx, cmd_args, None)?;
+    results.update(
+        &[],
+        scan::scan_files(
+            &ctx,
+            &store,
+            &files,
+            &warning_filter,
+            &script_args,
+            verbosity,
+        )?,
+    );
+    report(cmd_args, &results)?;
+
+    loop {
+        let changed_paths = next_changes(events, project_root)?;
+        if requires_reload(&ctx, &changed_paths) {
+            if log_enabled!(log::Level::Info) {
+                info!("reloading: vexes or manifest changed");
+            }
+            return Ok(());
+        }
+
+        let changed_paths: Vec<_> = changed_paths
+            .into_iter()
+            .filter(|path| {
+                requested_paths.is_empty()
+                    || requested_paths
+                        .iter()
+                        .any(|requested_path| path.starts_with(requested_path))
+            })
+            .collect();
+        if changed_paths.is_empty() {
+            continue;
+        }
+        if log_enabled!(log::Level::Info) {
+            info!("rechecking {} changed paths", changed_paths.len());
+        }
+
+        let rescan = || {
+            let existing_paths: Vec<_> = changed_paths
+                .iter()
+                .filter(|path| path.exists())
+                .cloned()
+                .collect();
+            let files =
+                source_file::sources_at(&ctx, &existing_paths, cmd_args.max_concurrent_files)?;
+            scan::scan_files(
+                &ctx,
+                &store,
+                &files,
+                &warning_filter,
+                &script_args,
+                verbosity,
+            )
+        };
+        match rescan() {
+            Ok(scan) => {
+                let changed_paths: Vec<_> = changed_paths
+                    .iter()
+                    .filter_map(|path| path.strip_prefix(project_root).ok())
+                    .map(PrettyPath::new)
+                    .collect();
+                results.update(&changed_paths, scan);
+                report(cmd_args, &results)?;
+            }
+            Err(err) => crate::error!("{err}"),
+        }
+    }
+}
+
+fn report(cmd_args: &CheckCmd, results: &WatchResults) -> Result<()> {
+    let mut irritations = results.irritations();
+    if let Some(baseline_path) = &cmd_args.baseline {
+        irritations = Baseline::load(baseline_path)?.filter(irritations);
+    }
+    if let MaxProblems::Limited(max) = cmd_args.max_problems {
+        irritations.truncate(max as usize);
+    }
+    cmd_args.format.emit(&irritations)?;
+    crate::print_summary(SeverityCounts::of(&irritations));
+    Ok(())
+}
+
+/// Block until a relevant path changes, then collect all further changes made in quick
+/// succession.
+fn next_changes(events: &Events, project_root: &Utf8Path) -> Result<BTreeSet<Utf8PathBuf>> {
+    let mut changed_paths = BTreeSet::new();
+    loop {
+        let event = if changed_paths.is_empty() {
+            events
+                .recv()
+                .expect("internal error: file watcher disconnected")
+        } else {
+            match events.recv_timeout(DEBOUNCE) {
+                Ok(event) => event,
+                Err(RecvTimeoutError::Timeout) => return Ok(changed_paths),
+                Err(RecvTimeoutError::Disconnected) => {
+                    panic!("internal error: file watcher disconnected")
+                }
+            }
+        };
+        let event = event.map_err(|cause| Error::Watch {
+            path: PrettyPath::new(project_root),
+            cause,
+        })?;
+        if event.kind.is_access() {
+            continue;
+        }
+        changed_paths.extend(
+            event
+                .paths
+                .into_iter()
+                .flat_map(Utf8PathBuf::from_path_buf)
+                .filter(|path| is_relevant(project_root, path)),
+        );
+    }
+}
+
+/// Returns whether a change to the given path could affect the project's results. Paths outside
+/// of the project and hidden paths, such as `.git/` and `.vex-cache/`, are never checked.
+fn is_relevant(project_root: &Utf8Path, path: &Utf8Path) -> bool {
+    path.strip_prefix(project_root).is_ok_and(|rel_path| {
+        rel_path
+            .components()
+            .all(|component| !component.as_str().starts_with('.'))
+    })
+}
+
+fn requires_reload(ctx: &Context, changed_paths: &BTreeSet<Utf8PathBuf>) -> bool {
+    let manifest_path = ctx.project_root.join(Manifest::FILE_NAME);
+    let vex_dir = ctx.vex_dir();
+    changed_paths.iter().any(|path| {
+        *path == manifest_path
+            || *path == vex_dir
+            || (path.starts_with(&vex_dir) && path.extension() == Some("star"))
+    })
+}
+
+/// The latest irritations found in each file.
+#[derive(Debug, Default)]
+struct WatchResults {
+    project_irritations: Vec<Irritation>,
+    file_irritations: BTreeMap<PrettyPath, Vec<Irritation>>,
+}
+
+impl WatchResults {
+    /// Replace the results for the given paths, and any files beneath them, with those of a new
+    /// scan.
+    fn update(&mut self, changed_paths: &[PrettyPath], scan: FileScanData) {
+        let FileScanData {
+            project_irritations,
+            file_irritations,
+        } = scan;
+        self.project_irritations = project_irritations;
+        self.file_irritations.retain(|path, _| {
+            !changed_paths
+                .iter()
+                .any(|changed_path| path.starts_with(changed_path.as_str()))
+        });
+        self.file_irritations.extend(file_irritations);
+    }
+
+    fn irritations(&self) -> Vec<Irritation> {
+        let mut irritations: Vec<_> = self
+            .project_irritations
+            .iter()
+            .chain(self.file_irritations.values().flatten())
+            .cloned()
+            .collect();
+        irritations.sort();
+        irritations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use notify::{event::CreateKind, EventKind};
+
+    use super::*;
+
+    #[test]
+    fn relevant_paths() {
+        let root = Utf8Path::new("/project");
+        assert!(is_relevant(root, Utf8Path::new("/project/src/main.rs")));
+        assert!(is_relevant(root, Utf8Path::new("/project/vexes/test.star")));
+        assert!(!is_relevant(root, Utf8Path::new("/elsewhere/main.rs")));
+        assert!(!is_relevant(root, Utf8Path::new("/project/.git/index")));
+        assert!(!is_relevant(
+            root,
+            Utf8Path::new("/project/.vex-cache/results.json")
+        ));
+        assert!(!is_relevant(
+            root,
+            Utf8Path::new("/project/src/.main.rs.swp")
+        ));
+    }
+
+    #[test]
+    fn debounced_changes() {
+        let root = Utf8Path::new("/project");
+        let (tx, events) = mpsc::channel();
+        let create = |paths: &[&str]| {
+            let event = paths.iter().fold(
+                Event::new(EventKind::Create(CreateKind::File)),
+                |event, path| event.add_path(path.into()),
+            );
+            tx.send(Ok(event)).unwrap();
+        };
+        create(&["/project/.git/index"]);
+        create(&["/project/src/main.rs"]);
+        create(&["/project/src/lib.rs", "/project/src/main.rs"]);
+
+        let changed_paths = next_changes(&events, root).unwrap();
+        assert_eq!(
+            changed_paths.iter().map(|p| p.as_str()).collect::<Vec<_>>(),
+            ["/project/src/lib.rs", "/project/src/main.rs"]
+        );
+    }
+
+    #[test]
+    fn reload() {
+        let ctx = Context::new_with_manifest(Utf8Path::new("/project"), Manifest::default());
+        let requires_reload =
+            |path: &str| requires_reload(&ctx, &BTreeSet::from([Utf8PathBuf::from(path)]));
+        assert!(requires_reload("/project/vex.toml"));
+        assert!(requires_reload("/project/vexes"));
+        assert!(requires_reload("/project/vexes/lints/test.star"));
+        assert!(!requires_reload("/project/vexes/README.md"));
+        assert!(!requires_reload("/project/src/main.rs"));
+        assert!(!requires_reload("/project/src/vex.toml"));
+    }
+
+    #[test]
+    fn update() {
+        let paths = |results: &WatchResults| {
+            results
+                .file_irritations
+                .keys()
+                .map(|path| path.as_str().to_owned())
+                .collect::<Vec<_>>()
+        };
+        let scan = |paths: &[&str]| FileScanData {
+            project_irritations: vec![],
+            file_irritations: paths
+                .iter()
+                .map(|path| (PrettyPath::from(*path), vec![]))
+                .collect(),
+        };
+
+        let mut results = WatchResults::default();
+        results.update(&[], scan(&["src/main.rs", "src/a/x.rs", "src/ab.rs"]));
+        assert_eq!(paths(&results), ["src/a/x.rs", "src/ab.rs", "src/main.rs"]);
+
+        // Deleting a directory removes all files beneath it.
+        results.update(&[PrettyPath::from("src/a")], scan(&[]));
+        assert_eq!(paths(&results), ["src/ab.rs", "src/main.rs"]);
+
+        results.update(
+            &[
+                PrettyPath::from("src/main.rs"),
+                PrettyPath::from("src/b.rs"),
+            ],
+            scan(&["src/main.rs", "src/b.rs"]),
+        );
+        assert_eq!(paths(&results), ["src/ab.rs", "src/b.rs", "src/main.rs"]);
+    }
+}
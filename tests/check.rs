@@ -0,0 +1,53 @@
+use std::{fs, process::Command};
+
+use indoc::indoc;
+
+#[test]
+fn notes_shown_by_default() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let root = tempdir.path();
+    fs::write(
+        root.join("vex.toml"),
+        indoc! {r#"
+            [vex]
+            version = "1"
+
+            [lints.level]
+            advice = "note"
+        "#},
+    )
+    .unwrap();
+    fs::create_dir(root.join("vexes")).unwrap();
+    fs::write(
+        root.join("vexes/test.star"),
+        indoc! {r#"
+            def init():
+                vex.observe('open_project', on_open_project)
+
+            def on_open_project(event):
+                vex.search('rust', '(integer_literal) @lit', on_match)
+
+            def on_match(event):
+                vex.warn('advice', 'consider a constant', at=event.captures['lit'])
+        "#},
+    )
+    .unwrap();
+    fs::create_dir(root.join("src")).unwrap();
+    fs::write(root.join("src/main.rs"), "fn main() { let x = 1; }\n").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_vex"))
+        .arg("check")
+        .current_dir(root)
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("consider a constant"),
+        "note not shown:\n{stderr}"
+    );
+    assert!(
+        stderr.contains("found 1 note"),
+        "summary missing:\n{stderr}"
+    );
+    assert_eq!(output.status.code(), Some(0), "stderr:\n{stderr}");
+}